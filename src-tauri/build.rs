@@ -1,3 +1,6 @@
 fn main() {
-    tauri_build::build()
+    // The CLI-only build has no Tauri app to generate a context for
+    if std::env::var_os("CARGO_FEATURE_GUI").is_some() {
+        tauri_build::build()
+    }
 }
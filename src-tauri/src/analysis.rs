@@ -0,0 +1,34 @@
+use crate::engine::{EngineClient, SearchLimits, SearchResult};
+use crate::notation::GameNotation;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionAnalysis {
+    // Index into the notation's moves; the position analyzed is the one before this entry
+    pub ply: usize,
+    pub fen: String,
+    pub played: Option<String>,
+    pub result: SearchResult,
+}
+
+/// Analyzes every position of a game that has a move played from it, plus the final position.
+pub fn analyze_notation(
+    engine: &mut EngineClient,
+    notation: &GameNotation,
+    limits: &SearchLimits,
+    timeout: Option<Duration>,
+) -> Result<Vec<PositionAnalysis>, String> {
+    let positions = notation.replay()?;
+    let mut analyses = Vec::new();
+    for (ply, position) in positions.iter().enumerate() {
+        let played = notation.moves.get(ply);
+        if played.map(|m| !m.is_move()).unwrap_or(false) || position.game_result().is_some() {
+            continue;
+        }
+        let fen = position.to_fen();
+        let result = engine.search(&fen, limits, timeout)?;
+        analyses.push(PositionAnalysis { ply, fen, played: played.map(|m| m.data.clone()), result });
+    }
+    Ok(analyses)
+}
@@ -0,0 +1,204 @@
+use crate::engine::{InfoLine, Score};
+use crate::opening_book::{compute_key_and_transform, transform_uci_move};
+use rusqlite::{Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedAnalysis {
+    pub engine: String,
+    pub depth: u32,
+    pub score: Score,
+    // "lowerbound" or "upperbound" when the score is not exact
+    pub bound: Option<String>,
+    pub pv: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisCacheStats {
+    pub total_positions: i64,
+    pub total_entries: i64,
+}
+
+/// Engine evaluations keyed like the opening book, so mirrored and color-swapped positions
+/// share one entry. Lives in its own table, so it can also share a database file with the book.
+pub struct AnalysisCache {
+    conn: Connection,
+}
+
+impl AnalysisCache {
+    pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+        let cache = AnalysisCache { conn };
+        cache.initialize_database()?;
+        Ok(cache)
+    }
+
+    fn initialize_database(&self) -> Result<()> {
+        self.conn.execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS analysis (
+                key        BLOB NOT NULL,
+                engine     TEXT NOT NULL,
+                depth      INTEGER NOT NULL,
+                score_type TEXT NOT NULL,
+                score      INTEGER NOT NULL,
+                bound      TEXT,
+                pv         TEXT NOT NULL,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (key, engine)
+            );
+            "#,
+            [],
+        )?;
+        Ok(())
+    }
+
+    // Keeps the deeper of the stored and the new result. Returns false if nothing was written.
+    pub fn store(&self, fen: &str, engine: &str, depth: u32, score: Score, bound: Option<&str>, pv: &[String]) -> Result<bool> {
+        let (key_blob, transform_idx) = compute_key_and_transform(fen);
+        let pv = pv.iter().map(|m| transform_pv_move(m, transform_idx)).collect::<Vec<_>>().join(" ");
+        let (score_type, score_value) = match score {
+            Score::Cp(cp) => ("cp", cp),
+            Score::Mate(ply) => ("mate", ply),
+        };
+
+        let affected_rows = self.conn.execute(
+            r#"
+            INSERT INTO analysis (key, engine, depth, score_type, score, bound, pv, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            ON CONFLICT(key, engine) DO UPDATE SET
+                depth=excluded.depth,
+                score_type=excluded.score_type,
+                score=excluded.score,
+                bound=excluded.bound,
+                pv=excluded.pv,
+                updated_at=excluded.updated_at
+            WHERE excluded.depth >= analysis.depth;
+            "#,
+            rusqlite::params![
+                &key_blob,
+                engine,
+                depth,
+                score_type,
+                score_value,
+                bound,
+                &pv,
+                chrono::Utc::now().timestamp(),
+            ],
+        )?;
+
+        Ok(affected_rows > 0)
+    }
+
+    // Info lines without a score are not worth caching
+    pub fn store_info(&self, fen: &str, engine: &str, info: &InfoLine) -> Result<bool> {
+        match info.score {
+            Some(score) => self.store(fen, engine, info.depth, score, info.bound.as_deref(), &info.pv),
+            None => Ok(false),
+        }
+    }
+
+    /// Returns the deepest stored result of at least `min_depth`, restricted to `engine` if given.
+    /// The PV is transformed back to the coordinates of `fen`.
+    pub fn lookup(&self, fen: &str, engine: Option<&str>, min_depth: u32) -> Result<Option<CachedAnalysis>> {
+        let (key_blob, transform_idx) = compute_key_and_transform(fen);
+        let mut stmt = self.conn.prepare(
+            r#"
+            SELECT engine, depth, score_type, score, bound, pv FROM analysis
+            WHERE key = ?1 AND depth >= ?2 AND (?3 IS NULL OR engine = ?3)
+            ORDER BY depth DESC, updated_at DESC
+            LIMIT 1
+            "#,
+        )?;
+        let cached = stmt
+            .query_row(rusqlite::params![&key_blob, min_depth, engine], |row| {
+                let score_type: String = row.get(2)?;
+                let score_value: i32 = row.get(3)?;
+                let pv: String = row.get(5)?;
+                Ok(CachedAnalysis {
+                    engine: row.get(0)?,
+                    depth: row.get(1)?,
+                    score: if score_type == "mate" { Score::Mate(score_value) } else { Score::Cp(score_value) },
+                    bound: row.get(4)?,
+                    pv: pv.split_whitespace().map(|m| transform_pv_move(m, transform_idx)).collect(),
+                })
+            })
+            .optional()?;
+        Ok(cached)
+    }
+
+    pub fn get_stats(&self) -> Result<AnalysisCacheStats> {
+        let total_positions: i64 = self.conn.query_row("SELECT COUNT(DISTINCT key) FROM analysis", [], |row| row.get(0))?;
+        let total_entries: i64 = self.conn.query_row("SELECT COUNT(*) FROM analysis", [], |row| row.get(0))?;
+        Ok(AnalysisCacheStats { total_positions, total_entries })
+    }
+
+    pub fn clear_all(&self) -> Result<()> {
+        self.conn.execute("DELETE FROM analysis", [])?;
+        Ok(())
+    }
+}
+
+// Like transform_uci_move, but keeps reveal suffixes of extended moves. Color swaps also swap
+// the case of the revealed pieces. Applying it twice with the same index restores the move.
+fn transform_pv_move(mv: &str, transform_idx: usize) -> String {
+    if mv.len() < 4 || !mv.is_ascii() {
+        return mv.to_string();
+    }
+    let (base, suffix) = mv.split_at(4);
+    let suffix: String = if transform_idx >= 2 {
+        suffix
+            .chars()
+            .map(|c| if c.is_ascii_uppercase() { c.to_ascii_lowercase() } else { c.to_ascii_uppercase() })
+            .collect()
+    } else {
+        suffix.to_string()
+    };
+    format!("{}{}", transform_uci_move(base, transform_idx), suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Red rook on d1 against the black king on d9
+    const FEN: &str = "3k5/9/9/9/9/9/9/9/3R5/4K4 w - - 0 1";
+    const MIRRORED: &str = "5k3/9/9/9/9/9/9/9/5R3/4K4 w - - 0 1";
+    const COLORS_SWAPPED: &str = "4k4/3r5/9/9/9/9/9/9/9/3K5 b - - 0 1";
+
+    fn pv(moves: &[&str]) -> Vec<String> {
+        moves.iter().map(|m| m.to_string()).collect()
+    }
+
+    #[test]
+    fn lookup_needs_the_minimum_depth() {
+        let cache = AnalysisCache::new(":memory:").unwrap();
+        assert!(cache.store(FEN, "engine", 10, Score::Cp(120), None, &pv(&["d1d5"])).unwrap());
+        // A shallower result does not replace a deeper one
+        assert!(!cache.store(FEN, "engine", 8, Score::Cp(-40), None, &pv(&["d1d2"])).unwrap());
+
+        assert!(cache.lookup(FEN, None, 12).unwrap().is_none());
+        let cached = cache.lookup(FEN, None, 10).unwrap().unwrap();
+        assert_eq!((cached.depth, cached.score), (10, Score::Cp(120)));
+        assert!(cache.lookup(FEN, Some("other"), 0).unwrap().is_none());
+        assert_eq!(cache.get_stats().unwrap().total_entries, 1);
+    }
+
+    #[test]
+    fn transformed_positions_share_an_entry() {
+        let cache = AnalysisCache::new(":memory:").unwrap();
+        let stored = pv(&["d1d5", "d9e9", "e0e1R"]);
+        cache.store(FEN, "engine", 10, Score::Mate(3), Some("lowerbound"), &stored).unwrap();
+
+        let same = cache.lookup(FEN, None, 0).unwrap().unwrap();
+        assert_eq!(same.pv, stored);
+        let mirrored = cache.lookup(MIRRORED, None, 0).unwrap().unwrap();
+        assert_eq!(mirrored.pv, ["f1f5", "f9e9", "e0e1R"]);
+        // Scores are for the side to move, so they carry over to the swapped colors unchanged
+        let swapped = cache.lookup(COLORS_SWAPPED, None, 0).unwrap().unwrap();
+        assert_eq!(swapped.pv, ["d8d4", "d0e0", "e9e8r"]);
+        assert_eq!((swapped.score, swapped.bound.as_deref()), (Score::Mate(3), Some("lowerbound")));
+        assert_eq!(cache.get_stats().unwrap().total_positions, 1);
+    }
+}
@@ -0,0 +1,303 @@
+use crate::engine::{EngineClient, EngineSpec, SearchLimits, SearchResult};
+use crate::position::Position;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisJob {
+    pub id: u64,
+    pub fen: String,
+    pub limits: SearchLimits,
+    // Jobs of the same group (e.g. one game) are related and may share the engine hash
+    #[serde(default)]
+    pub group: Option<String>,
+    pub status: JobStatus,
+    #[serde(default)]
+    pub result: Option<SearchResult>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueueSummary {
+    pub pending: usize,
+    pub running: usize,
+    pub done: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueConfig {
+    pub workers: usize,
+    // Send ucinewgame when a worker moves on to a job from another group
+    pub clear_hash_between_groups: bool,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        QueueConfig { workers: 2, clear_hash_between_groups: true }
+    }
+}
+
+/// Job list persisted as JSON so that an interrupted run can be resumed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnalysisQueue {
+    next_id: u64,
+    pub jobs: Vec<AnalysisJob>,
+}
+
+impl AnalysisQueue {
+    // Jobs left running by an interrupted run are queued again
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(AnalysisQueue::default());
+        }
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read queue file: {}", e))?;
+        let mut queue: AnalysisQueue =
+            serde_json::from_str(&content).map_err(|e| format!("Invalid queue file: {}", e))?;
+        for job in queue.jobs.iter_mut().filter(|j| j.status == JobStatus::Running) {
+            job.status = JobStatus::Pending;
+        }
+        Ok(queue)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, content).map_err(|e| format!("Failed to write queue file: {}", e))?;
+        fs::rename(&temp_path, path).map_err(|e| format!("Failed to replace queue file: {}", e))
+    }
+
+    pub fn add(&mut self, fen: &str, limits: SearchLimits, group: Option<String>) -> Result<u64, String> {
+        Position::from_fen(fen)?;
+        self.next_id += 1;
+        self.jobs.push(AnalysisJob {
+            id: self.next_id,
+            fen: fen.to_string(),
+            limits,
+            group,
+            status: JobStatus::Pending,
+            result: None,
+            error: None,
+        });
+        Ok(self.next_id)
+    }
+
+    pub fn summary(&self) -> QueueSummary {
+        let mut summary = QueueSummary::default();
+        for job in &self.jobs {
+            match job.status {
+                JobStatus::Pending => summary.pending += 1,
+                JobStatus::Running => summary.running += 1,
+                JobStatus::Done => summary.done += 1,
+                JobStatus::Failed => summary.failed += 1,
+            }
+        }
+        summary
+    }
+
+    pub fn retry_failed(&mut self) {
+        for job in self.jobs.iter_mut().filter(|j| j.status == JobStatus::Failed) {
+            job.status = JobStatus::Pending;
+            job.error = None;
+        }
+    }
+
+    pub fn clear_finished(&mut self) {
+        self.jobs.retain(|j| j.status == JobStatus::Pending || j.status == JobStatus::Running);
+    }
+
+    // Prefers a job from the worker's current group so related positions reuse the hash
+    fn take_next(&mut self, current_group: &Option<String>) -> Option<AnalysisJob> {
+        let index = self
+            .jobs
+            .iter()
+            .position(|j| j.status == JobStatus::Pending && current_group.is_some() && &j.group == current_group)
+            .or_else(|| self.jobs.iter().position(|j| j.status == JobStatus::Pending))?;
+        self.jobs[index].status = JobStatus::Running;
+        Some(self.jobs[index].clone())
+    }
+
+    fn finish(&mut self, id: u64, outcome: Result<SearchResult, String>) -> Option<AnalysisJob> {
+        let job = self.jobs.iter_mut().find(|j| j.id == id)?;
+        match outcome {
+            Ok(result) => {
+                job.status = JobStatus::Done;
+                job.result = Some(result);
+                job.error = None;
+            }
+            Err(e) => {
+                job.status = JobStatus::Failed;
+                job.error = Some(e);
+            }
+        }
+        Some(job.clone())
+    }
+}
+
+fn run_worker<F>(
+    queue: &Mutex<AnalysisQueue>,
+    path: &Path,
+    engine_spec: &EngineSpec,
+    config: &QueueConfig,
+    pause: &AtomicBool,
+    on_job: &F,
+) -> Result<(), String>
+where
+    F: Fn(&AnalysisJob) + Sync,
+{
+    let mut engine = EngineClient::start(engine_spec, HANDSHAKE_TIMEOUT)?;
+    let mut current_group: Option<String> = None;
+
+    while !pause.load(Ordering::SeqCst) {
+        let Some(job) = queue.lock().unwrap().take_next(&current_group) else { break };
+
+        if config.clear_hash_between_groups && (job.group != current_group || job.group.is_none()) {
+            engine.new_game(HANDSHAKE_TIMEOUT)?;
+        }
+        current_group = job.group.clone();
+
+        let outcome = engine.search(&job.fen, &job.limits, job.limits.timeout());
+        let engine_failed = outcome.is_err();
+
+        let finished = {
+            let mut queue = queue.lock().unwrap();
+            let finished = queue.finish(job.id, outcome);
+            queue.save(path)?;
+            finished
+        };
+        if let Some(finished) = finished {
+            on_job(&finished);
+        }
+
+        // A timed out or crashed engine is replaced before the next job
+        if engine_failed {
+            engine = EngineClient::start(engine_spec, HANDSHAKE_TIMEOUT)?;
+            current_group = None;
+        }
+    }
+
+    engine.quit();
+    Ok(())
+}
+
+/// Runs pending jobs on `config.workers` engine processes until the queue is empty or `pause`
+/// is set. Progress is saved to `path` after every job, so the run can be resumed later.
+pub fn run_queue<F>(
+    queue: Arc<Mutex<AnalysisQueue>>,
+    path: PathBuf,
+    engine_spec: &EngineSpec,
+    config: &QueueConfig,
+    pause: Arc<AtomicBool>,
+    on_job: F,
+) -> Result<QueueSummary, String>
+where
+    F: Fn(&AnalysisJob) + Sync,
+{
+    let workers = config.workers.max(1);
+    let errors: Vec<String> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| scope.spawn(|| run_worker(&queue, &path, engine_spec, config, &pause, &on_job)))
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|h| match h.join() {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(e),
+                Err(_) => Some("Analysis worker panicked".to_string()),
+            })
+            .collect()
+    });
+
+    let mut queue = queue.lock().unwrap();
+    // Jobs still marked running belonged to a worker that failed
+    for job in queue.jobs.iter_mut().filter(|j| j.status == JobStatus::Running) {
+        job.status = JobStatus::Pending;
+    }
+    queue.save(&path)?;
+
+    if errors.len() == workers {
+        return Err(errors.join("; "));
+    }
+    Ok(queue.summary())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::START_FEN;
+
+    fn queue(groups: &[Option<&str>]) -> AnalysisQueue {
+        let mut queue = AnalysisQueue::default();
+        for group in groups {
+            queue.add(START_FEN, SearchLimits::default(), group.map(str::to_string)).unwrap();
+        }
+        queue
+    }
+
+    #[test]
+    fn next_job_stays_in_the_current_group() {
+        let mut queue = queue(&[Some("a"), Some("b"), None, Some("b")]);
+        let b = Some("b".to_string());
+        assert_eq!(queue.take_next(&b).unwrap().id, 2);
+        assert_eq!(queue.take_next(&b).unwrap().id, 4);
+        // Nothing left in the group, or no group at all: the first pending job
+        assert_eq!(queue.take_next(&b).unwrap().id, 1);
+        assert_eq!(queue.take_next(&None).unwrap().id, 3);
+        assert!(queue.take_next(&None).is_none());
+        assert_eq!(queue.summary().running, 4);
+    }
+
+    #[test]
+    fn interrupted_jobs_are_queued_again_on_load() {
+        let path = std::env::temp_dir().join(format!("jieqibox-queue-{}.json", std::process::id()));
+        let mut queue = queue(&[None, None]);
+        queue.take_next(&None);
+        assert_eq!(queue.summary().running, 1);
+        queue.save(&path).unwrap();
+
+        let loaded = AnalysisQueue::load(&path).unwrap();
+        assert_eq!(loaded.summary().pending, 2);
+        assert!(!path.with_extension("tmp").exists());
+        let _ = fs::remove_file(&path);
+
+        assert!(AnalysisQueue::load(&path).unwrap().jobs.is_empty());
+    }
+
+    #[test]
+    fn failed_jobs_are_retried_and_finished_ones_cleared() {
+        let mut queue = queue(&[None, None, None]);
+        for outcome in [Ok(SearchResult::default()), Err("engine crashed".to_string())] {
+            let job = queue.take_next(&None).unwrap();
+            queue.finish(job.id, outcome);
+        }
+        assert_eq!(queue.jobs[1].error.as_deref(), Some("engine crashed"));
+
+        queue.retry_failed();
+        assert_eq!((queue.jobs[1].status, queue.jobs[1].error.as_ref()), (JobStatus::Pending, None));
+        queue.clear_finished();
+        let ids: Vec<_> = queue.jobs.iter().map(|j| j.id).collect();
+        assert_eq!(ids, [2, 3]);
+        // New jobs keep counting from the last ID
+        assert_eq!(queue.add(START_FEN, SearchLimits::default(), None).unwrap(), 4);
+        assert!(queue.add("not a fen", SearchLimits::default(), None).is_err());
+    }
+}
@@ -0,0 +1,2240 @@
+// The Tauri app: commands for the frontend and the screen and mouse automation behind them
+// --- CẬP NHẬT IMPORT ĐÚNG CHO ENIGO 0.2 VÀ IMAGE ---
+use screenshots::Screen;
+use std::time::Instant;
+use std::time::Duration;
+// ----------------------------------------------------
+
+use tauri_plugin_shell::ShellExt;
+use tauri_plugin_shell::process::{CommandChild, CommandEvent};
+use tauri::{AppHandle, Emitter};
+use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use tauri::async_runtime;
+use std::process::Command;
+use std::path::Path;
+use std::fs;
+use base64::Engine;
+#[cfg(target_os = "android")]
+use std::os::unix::fs::PermissionsExt;
+use clipboard::{ClipboardContext, ClipboardProvider};
+
+use crate::{
+    analysis_queue, auto_play, autosave, board_recognition, board_watcher, calibration, engine_log, engine_options,
+    engine_transport, input_automation, move_verification, notation_formats, opening_book, position, review,
+    screen_capture, settings,
+};
+
+use crate::opening_book::{JieqiOpeningBook, MoveData, OpeningBookStats, AddEntryRequest};
+use crate::engine::{EngineClient, EngineEncoding, EngineSpec, InfoLine, SearchLimits, SearchResult};
+use crate::engine_health::{EngineMonitor, HealthAction, HealthConfig};
+use crate::engine_log::{Direction, EngineLogConfig, EngineLogFile, EngineLogger};
+use crate::engine_options::{EngineInfo, EngineOption, EngineProtocol};
+use crate::engine_registry::{EngineEntry, EngineHealth, EngineRegistry, NewEngine};
+use crate::engine_transport::{Connection, EngineOutput, EngineTransport, ReconnectConfig};
+use crate::settings::AppSettings;
+use crate::autosave::{AutosaveStore, SnapshotInfo};
+use crate::board_recognition::{BoardOrientation, BoardRect, PieceSet, Recognition, RecognitionConfig};
+use crate::calibration::Calibration;
+use crate::board_watcher::{BoardWatcher, FrameSource, WatchEvent, WatcherConfig};
+use crate::input_automation::{AutomationConfig, EnigoDevice, InputAction};
+use crate::move_verification::{VerifyConfig, VerifyOutcome};
+use crate::auto_play::{AutoPlayConfig, AutoPlayControl, AutoPlayEvent};
+use crate::session_log::{LoggedDevice, LoggedFrames, SessionExport, SessionLog};
+use crate::screen_capture::{CaptureFormat, CaptureOptions, CaptureRegion, CapturedFrame};
+use tauri::ipc::{Channel, InvokeResponseBody};
+use crate::game_db::{GameDatabase, GameDbStats, GamePage, GameQuery};
+use tauri::Manager;
+use crate::analysis_cache::{AnalysisCache, AnalysisCacheStats, CachedAnalysis};
+use crate::notation::{GameNotation, NotationProblem, RepairReport};
+use crate::notation_formats::NotationFormat;
+use crate::game_tree::GameTree;
+use crate::review::ReviewReport;
+use crate::analysis_queue::{AnalysisJob, AnalysisQueue, QueueConfig, QueueSummary};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// -------------------------------------------------------------
+// type definition for the engine process state
+type EngineProcess = Arc<Mutex<Option<RunningEngine>>>;
+// I/O log of the engine process, when enabled at spawn
+type EngineIoLog = Arc<Mutex<Option<EngineLogger>>>;
+// Handshake and health of the engine process, when spawned with a protocol
+type EngineWatch = Arc<Mutex<Option<EngineMonitor>>>;
+
+static NEXT_ENGINE_SESSION: AtomicU64 = AtomicU64::new(1);
+
+// The interactive engine; `session` tells successive spawns apart and is kept across reconnects
+struct RunningEngine {
+    session: u64,
+    link: EngineLink,
+    // `quit` was sent, so a closed connection is expected
+    quit: bool,
+    // Last `setoption` per option, sent again to a reconnected engine
+    options: Vec<String>,
+}
+
+enum EngineLink {
+    // Local engines run through the shell plugin
+    Shell(CommandChild),
+    Remote(Connection),
+}
+
+impl RunningEngine {
+    fn new(session: u64, link: EngineLink) -> Self {
+        RunningEngine { session, link, quit: false, options: Vec::new() }
+    }
+
+    fn write(&mut self, command: &str) -> Result<(), String> {
+        match &mut self.link {
+            EngineLink::Shell(child) => child
+                .write(format!("{}\n", command).as_bytes())
+                .map_err(|e| format!("Failed to write to engine: {}", e))?,
+            EngineLink::Remote(connection) => connection.send(command)?,
+        }
+        let command = command.trim();
+        if command == "quit" {
+            self.quit = true;
+        } else if let Some((name, _)) = command.strip_prefix("setoption name ").and_then(|o| o.split_once(" value ")) {
+            self.options.retain(|sent| !sent.starts_with(&format!("setoption name {} value ", name)));
+            self.options.push(command.to_string());
+        }
+        Ok(())
+    }
+
+    fn kill(self) {
+        match self.link {
+            EngineLink::Shell(child) => {
+                let _ = child.kill();
+            }
+            EngineLink::Remote(mut connection) => connection.close(),
+        }
+    }
+}
+
+// Loaded from the queue file on first use; `pause` and `running` coordinate the worker pool
+#[derive(Default)]
+struct AnalysisQueueState {
+    queue: Mutex<Option<Arc<Mutex<AnalysisQueue>>>>,
+    pause: Arc<AtomicBool>,
+    running: AtomicBool,
+}
+
+// The running watcher, shared so moves we play can be fed into it
+#[derive(Default)]
+struct BoardWatchState {
+    watcher: Mutex<Option<Arc<Mutex<BoardWatcher>>>>,
+    stop: Arc<AtomicBool>,
+    running: AtomicBool,
+}
+
+// Controls of the running auto-play loop
+#[derive(Default)]
+struct AutoPlayHandle {
+    control: Mutex<Option<Arc<AutoPlayControl>>>,
+}
+
+// Lets a mouse action in progress be cancelled; `busy` keeps two from interleaving
+#[derive(Default)]
+struct MouseState {
+    cancel: Arc<AtomicBool>,
+    busy: AtomicBool,
+}
+
+// Engines kept running for `engine_analyze`, by registry ID
+#[derive(Default)]
+struct AnalyzeEngines {
+    engines: Mutex<HashMap<String, Arc<AnalyzeEngine>>>,
+}
+
+// The process with the spec it was started from; one analysis runs at a time
+#[derive(Default)]
+struct AnalyzeEngine {
+    client: Mutex<Option<(EngineSpec, EngineClient)>>,
+    cancel: AtomicBool,
+}
+// -------------------------------------------------------------
+
+// --- [NEW] HÀM CHỤP ẢNH MÀN HÌNH (ĐÃ FIX LỖI BUFFER) ---
+// Returns a data URL; without arguments, a PNG of the first monitor
+#[tauri::command]
+async fn capture_screen(
+    monitor: Option<usize>,
+    region: Option<CaptureRegion>,
+    format: Option<CaptureFormat>,
+) -> Result<String, String> {
+    let options = CaptureOptions {
+        monitor: monitor.unwrap_or(0),
+        region,
+        format: format.unwrap_or_default(),
+        ..Default::default()
+    };
+    let (frame, bytes) = capture_frame_bytes(&options)?;
+    Ok(screen_capture::data_url(&bytes, frame.format))
+}
+
+// Sends the encoded bytes through `channel` when given, which skips base64 and JSON for
+// frequent polling; otherwise they come back as a data URL in the frame
+#[tauri::command]
+async fn capture_frame(options: CaptureOptions, channel: Option<Channel>) -> Result<CapturedFrame, String> {
+    let (mut frame, bytes) = capture_frame_bytes(&options)?;
+    match channel {
+        Some(channel) => channel.send(InvokeResponseBody::Raw(bytes)).map_err(|e| e.to_string())?,
+        None => frame.data = Some(screen_capture::data_url(&bytes, frame.format)),
+    }
+    Ok(frame)
+}
+
+fn capture_frame_bytes(options: &CaptureOptions) -> Result<(CapturedFrame, Vec<u8>), String> {
+    let (image, region) = capture_region(options.monitor, options.region)?;
+    let bytes = screen_capture::encode(image, options.format, options.jpeg_quality)?;
+    Ok((CapturedFrame { monitor: options.monitor, region, format: options.format, data: None }, bytes))
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct MonitorInfo {
+    index: usize,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+    scale_factor: f32,
+    is_primary: bool,
+}
+
+#[tauri::command]
+async fn list_monitors() -> Result<Vec<MonitorInfo>, String> {
+    let screens = Screen::all().map_err(|e| e.to_string())?;
+    Ok(screens
+        .iter()
+        .enumerate()
+        .map(|(index, screen)| {
+            let info = &screen.display_info;
+            MonitorInfo {
+                index,
+                x: info.x,
+                y: info.y,
+                width: info.width,
+                height: info.height,
+                scale_factor: info.scale_factor,
+                is_primary: info.is_primary,
+            }
+        })
+        .collect())
+}
+
+fn recognition_config(
+    board_rect: Option<BoardRect>,
+    orientation: Option<BoardOrientation>,
+    side_to_move: Option<String>,
+) -> Result<RecognitionConfig, String> {
+    let side_to_move = match side_to_move.as_deref() {
+        None | Some("w") | Some("red") => position::Side::Red,
+        Some("b") | Some("black") => position::Side::Black,
+        Some(other) => return Err(format!("Invalid side to move '{}'", other)),
+    };
+    let orientation = orientation.unwrap_or_default();
+    Ok(RecognitionConfig { board_rect, orientation, side_to_move, ..Default::default() })
+}
+
+fn monitor_screen(monitor: usize) -> Result<Screen, String> {
+    let screens = Screen::all().map_err(|e| e.to_string())?;
+    screens.into_iter().nth(monitor).ok_or_else(|| format!("No monitor {}", monitor))
+}
+
+// Capture of one monitor, with the monitor for its desktop position
+fn capture_monitor(monitor: usize) -> Result<(image::RgbaImage, Screen), String> {
+    let screen = monitor_screen(monitor)?;
+    let image = screen.capture().map_err(|e| e.to_string())?;
+    Ok((image, screen))
+}
+
+// Captures only the region of a monitor, with the region the image covers. Regions are in
+// capture pixels while capture_area takes logical coordinates, so the area is rounded
+// outwards and the extra pixels are cropped off.
+fn capture_region(monitor: usize, region: Option<CaptureRegion>) -> Result<(image::RgbaImage, CaptureRegion), String> {
+    let Some(region) = region else {
+        let (image, _) = capture_monitor(monitor)?;
+        return screen_capture::crop(image, None);
+    };
+    let screen = monitor_screen(monitor)?;
+    let scale = Some(screen.display_info.scale_factor).filter(|s| *s > 0.0).unwrap_or(1.0);
+    let left = (region.x as f32 / scale).floor();
+    let top = (region.y as f32 / scale).floor();
+    let right = ((region.x + region.width) as f32 / scale).ceil();
+    let bottom = ((region.y + region.height) as f32 / scale).ceil();
+    let image = screen
+        .capture_area(left as i32, top as i32, (right - left) as u32, (bottom - top) as u32)
+        .map_err(|e| e.to_string())?;
+
+    // Where the captured area starts, in capture pixels
+    let (origin_x, origin_y) = ((left * scale).round() as u32, (top * scale).round() as u32);
+    let inner = CaptureRegion {
+        x: region.x.saturating_sub(origin_x),
+        y: region.y.saturating_sub(origin_y),
+        width: region.width,
+        height: region.height,
+    };
+    let (image, covered) = screen_capture::crop(image, Some(inner))?;
+    Ok((image, CaptureRegion { x: origin_x + covered.x, y: origin_y + covered.y, ..covered }))
+}
+
+fn load_calibration(app: &AppHandle) -> Result<Calibration, String> {
+    Calibration::load(get_calibration_path(app)?)?.ok_or_else(|| "The board has not been calibrated".to_string())
+}
+
+#[tauri::command]
+async fn get_calibration(app: AppHandle) -> Result<Option<Calibration>, String> {
+    Calibration::load(get_calibration_path(&app)?)
+}
+
+#[tauri::command]
+async fn save_calibration(app: AppHandle, calibration: Calibration) -> Result<(), String> {
+    calibration.save(get_calibration_path(&app)?)
+}
+
+// Finds the board on a monitor and stores it as the calibration. The piece set, if given,
+// is used to tell which way up the board is.
+#[tauri::command]
+async fn calibrate_board(
+    app: AppHandle,
+    monitor: Option<usize>,
+    piece_set: Option<String>,
+) -> Result<Calibration, String> {
+    let monitor = monitor.unwrap_or(0);
+    let pieces = piece_set.map(|name| get_piece_set_path(&app, &name).and_then(PieceSet::load_dir)).transpose()?;
+    let (image, screen) = capture_monitor(monitor)?;
+    let (board_rect, orientation) = calibration::detect_board(&image, pieces.as_ref())?;
+    let calibration = Calibration {
+        monitor,
+        monitor_x: screen.display_info.x,
+        monitor_y: screen.display_info.y,
+        scale_factor: screen.display_info.scale_factor,
+        board_rect,
+        orientation,
+    };
+    calibration.save(get_calibration_path(&app)?)?;
+    Ok(calibration)
+}
+
+#[tauri::command]
+async fn square_to_screen(app: AppHandle, square: String) -> Result<(i32, i32), String> {
+    load_calibration(&app)?.square_to_screen(&square)
+}
+
+#[tauri::command]
+async fn screen_to_square(app: AppHandle, x: i32, y: i32) -> Result<Option<String>, String> {
+    Ok(load_calibration(&app)?.screen_to_square(x, y))
+}
+
+#[tauri::command]
+async fn list_piece_sets(app: AppHandle) -> Result<Vec<String>, String> {
+    let dir = get_piece_sets_dir(&app)?;
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().is_dir())
+                .map(|e| e.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    Ok(names)
+}
+
+// Recognizes a saved screenshot with one of the piece sets in the piece set directory
+#[tauri::command]
+async fn recognize_board_image(
+    app: AppHandle,
+    path: String,
+    piece_set: String,
+    board_rect: Option<BoardRect>,
+    orientation: Option<BoardOrientation>,
+    side_to_move: Option<String>,
+) -> Result<Recognition, String> {
+    let pieces = PieceSet::load_dir(get_piece_set_path(&app, &piece_set)?)?;
+    let image = image::open(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?.to_rgba8();
+    board_recognition::recognize(&image, &pieces, &recognition_config(board_rect, orientation, side_to_move)?)
+}
+
+// Uses the calibrated monitor, board rectangle and orientation unless they are given
+#[tauri::command]
+async fn recognize_screen(
+    app: AppHandle,
+    piece_set: String,
+    board_rect: Option<BoardRect>,
+    orientation: Option<BoardOrientation>,
+    side_to_move: Option<String>,
+) -> Result<Recognition, String> {
+    let pieces = PieceSet::load_dir(get_piece_set_path(&app, &piece_set)?)?;
+    let calibration = Calibration::load(get_calibration_path(&app)?)?;
+    let (image, _) = capture_monitor(calibration.as_ref().map_or(0, |c| c.monitor))?;
+    let config = recognition_config(
+        board_rect.or(calibration.as_ref().map(|c| c.board_rect)),
+        orientation.or(calibration.as_ref().map(|c| c.orientation)),
+        side_to_move,
+    )?;
+    board_recognition::recognize(&image, &pieces, &config)
+}
+
+// Captures the calibrated board region of its monitor
+struct ScreenFrames {
+    monitor: usize,
+    region: CaptureRegion,
+}
+
+impl FrameSource for ScreenFrames {
+    fn next_frame(&mut self) -> Result<Option<image::RgbaImage>, String> {
+        capture_region(self.monitor, Some(self.region)).map(|(image, _)| Some(image))
+    }
+}
+
+// Frames of the calibrated board, with the matching recognition settings
+fn calibrated_frames(
+    calibration: &Calibration,
+    side_to_move: Option<String>,
+) -> Result<(ScreenFrames, RecognitionConfig), String> {
+    let region = calibration.capture_region();
+    let config =
+        recognition_config(Some(calibration.board_rect_in(&region)), Some(calibration.orientation), side_to_move)?;
+    Ok((ScreenFrames { monitor: calibration.monitor, region }, config))
+}
+
+/// Watches the calibrated board until stopped. Every watcher event is emitted as `board-watch`;
+/// moves by the side other than `our_side` also as `opponent-move`. Without a FEN the first
+/// stable board is taken as the position. Returns the last known FEN.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn board_watch_start(
+    app: AppHandle,
+    state: tauri::State<'_, BoardWatchState>,
+    piece_set: String,
+    fen: Option<String>,
+    side_to_move: Option<String>,
+    our_side: Option<String>,
+    interval_ms: Option<u64>,
+    stable_frames: Option<u32>,
+) -> Result<Option<String>, String> {
+    let pieces = PieceSet::load_dir(get_piece_set_path(&app, &piece_set)?)?;
+    let calibration = load_calibration(&app)?;
+    let position = fen.as_deref().map(position::Position::from_fen).transpose()?;
+    let mut config = WatcherConfig::default();
+    if let Some(ms) = interval_ms {
+        config.interval = Duration::from_millis(ms);
+    }
+    if let Some(frames) = stable_frames {
+        config.stable_frames = frames.max(1);
+    }
+    let (mut source, recognition) = calibrated_frames(&calibration, side_to_move)?;
+
+    if state.running.swap(true, Ordering::SeqCst) {
+        return Err("Board watch is already running".to_string());
+    }
+    let watcher = Arc::new(Mutex::new(BoardWatcher::new(position, config)));
+    *state.watcher.lock().unwrap() = Some(watcher.clone());
+    state.stop.store(false, Ordering::SeqCst);
+    let stop = state.stop.clone();
+    let shared = watcher.clone();
+
+    let result = async_runtime::spawn_blocking(move || {
+        board_watcher::run_watcher(&mut source, &pieces, &recognition, &shared, &stop, |event| {
+            if let WatchEvent::Move { side, .. } = &event {
+                if our_side.as_deref() != Some(side.as_str()) {
+                    let _ = app.emit("opponent-move", &event);
+                }
+            }
+            let _ = app.emit("board-watch", &event);
+        })
+    })
+    .await
+    .map_err(|e| e.to_string());
+    state.running.store(false, Ordering::SeqCst);
+    *state.watcher.lock().unwrap() = None;
+    result??;
+    let fen = watcher.lock().unwrap().position().map(|p| p.to_fen());
+    Ok(fen)
+}
+
+#[tauri::command]
+async fn board_watch_stop(state: tauri::State<'_, BoardWatchState>) -> Result<(), String> {
+    state.stop.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+// Replaces the watched position, e.g. after playing our own move on the board
+#[tauri::command]
+async fn board_watch_set_position(state: tauri::State<'_, BoardWatchState>, fen: String) -> Result<(), String> {
+    let position = position::Position::from_fen(&fen)?;
+    let slot = state.watcher.lock().unwrap();
+    let watcher = slot.as_ref().ok_or("Board watch is not running")?;
+    watcher.lock().unwrap().set_position(position);
+    Ok(())
+}
+
+// --- [NEW] HÀM AUTO CLICK CHUỘT (ĐÃ FIX CHO ENIGO 0.2) ---
+// Drags from start to end by default; `config` picks click-click mode, delays and path shape.
+// Returns the actions performed.
+#[tauri::command]
+async fn perform_mouse_move(
+    state: tauri::State<'_, MouseState>,
+    start_x: i32,
+    start_y: i32,
+    end_x: i32,
+    end_y: i32,
+    config: Option<AutomationConfig>,
+) -> Result<Vec<InputAction>, String> {
+    if state.busy.swap(true, Ordering::SeqCst) {
+        return Err("Another mouse action is in progress".to_string());
+    }
+    state.cancel.store(false, Ordering::SeqCst);
+    let cancel = state.cancel.clone();
+    let config = config.unwrap_or_default();
+    // Off the async runtime, since the steps sleep between them
+    let result = async_runtime::spawn_blocking(move || {
+        let mut device = EnigoDevice::new()?;
+        input_automation::perform_move(&mut device, (start_x, start_y), (end_x, end_y), &config, &cancel)
+    })
+    .await
+    .map_err(|e| e.to_string());
+    state.busy.store(false, Ordering::SeqCst);
+    result?
+}
+
+// Plays a move such as `h2e2` on the calibrated board
+#[tauri::command]
+async fn perform_uci_move(
+    app: AppHandle,
+    state: tauri::State<'_, MouseState>,
+    uci: String,
+    config: Option<AutomationConfig>,
+) -> Result<Vec<InputAction>, String> {
+    let ((start_x, start_y), (end_x, end_y)) = load_calibration(&app)?.move_to_screen(&uci)?;
+    perform_mouse_move(state, start_x, start_y, end_x, end_y, config).await
+}
+
+/// Plays a move from `fen` on the calibrated board and checks on screen that it happened,
+/// retrying while the board is unchanged. A desync is also emitted as `automation-desync`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn perform_verified_move(
+    app: AppHandle,
+    state: tauri::State<'_, MouseState>,
+    fen: String,
+    uci: String,
+    piece_set: String,
+    config: Option<AutomationConfig>,
+    verify: Option<VerifyConfig>,
+) -> Result<VerifyOutcome, String> {
+    let position = position::Position::from_fen(&fen)?;
+    let mv = position::JieqiMove::parse(&uci, position.side_to_move)?;
+    let pieces = PieceSet::load_dir(get_piece_set_path(&app, &piece_set)?)?;
+    let calibration = load_calibration(&app)?;
+    let points = calibration.move_to_screen(&uci)?;
+    let (mut source, recognition) = calibrated_frames(&calibration, None)?;
+    if state.busy.swap(true, Ordering::SeqCst) {
+        return Err("Another mouse action is in progress".to_string());
+    }
+    state.cancel.store(false, Ordering::SeqCst);
+    let cancel = state.cancel.clone();
+    let (config, verify) = (config.unwrap_or_default(), verify.unwrap_or_default());
+    let result = async_runtime::spawn_blocking(move || {
+        let mut device = EnigoDevice::new()?;
+        move_verification::play_verified(
+            &mut device,
+            &mut source,
+            &pieces,
+            &recognition,
+            &position,
+            &mv,
+            points,
+            &config,
+            &verify,
+            &cancel,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string());
+    state.busy.store(false, Ordering::SeqCst);
+    let outcome = result??;
+    if let VerifyOutcome::Desync(desync) = &outcome {
+        let _ = app.emit("automation-desync", desync);
+    }
+    Ok(outcome)
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct AutoPlaySummary {
+    fen: Option<String>,
+    log: SessionExport,
+}
+
+/// Plays `our_side` ("red" or "black") on the calibrated board with the engine until stopped
+/// or the game ends. Every step is emitted as `auto-play`; opponent moves also as
+/// `opponent-move` and desyncs as `automation-desync`. The session is logged to the
+/// auto-play log directory, with the key screenshots if `screenshots` is set.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn auto_play_start(
+    app: AppHandle,
+    state: tauri::State<'_, AutoPlayHandle>,
+    engine: EngineSpec,
+    piece_set: String,
+    our_side: String,
+    fen: Option<String>,
+    config: Option<AutoPlayConfig>,
+    screenshots: Option<bool>,
+) -> Result<AutoPlaySummary, String> {
+    let pieces = PieceSet::load_dir(get_piece_set_path(&app, &piece_set)?)?;
+    let calibration = load_calibration(&app)?;
+    let start = fen.as_deref().map(position::Position::from_fen).transpose()?;
+    // Without a FEN, auto-play is started on our turn
+    let (mut source, recognition) = calibrated_frames(&calibration, Some(our_side))?;
+    let side = recognition.side_to_move;
+    let config = config.unwrap_or_default();
+    let log_dir = get_auto_play_logs_dir(&app)?;
+
+    let control = Arc::new(AutoPlayControl::default());
+    {
+        let mut slot = state.control.lock().unwrap();
+        if slot.is_some() {
+            return Err("Auto-play is already running".to_string());
+        }
+        *slot = Some(control.clone());
+    }
+    let ours = if side == position::Side::Red { "red" } else { "black" };
+    let result = async_runtime::spawn_blocking(move || {
+        let name = chrono::Local::now().format("autoplay-%Y%m%d-%H%M%S").to_string();
+        let screenshot_dir = Path::new(&log_dir).join(format!("{}-screenshots", name));
+        let log = Mutex::new(SessionLog::new(screenshots.unwrap_or(false).then_some(screenshot_dir)));
+        let session = || {
+            let mut client = EngineClient::start(&engine, Duration::from_secs(10))?;
+            client.new_game(Duration::from_secs(10))?;
+            let mut enigo = EnigoDevice::new()?;
+            let last = auto_play::run_auto_play(
+                &mut client,
+                &mut LoggedDevice { device: &mut enigo, log: &log },
+                &mut LoggedFrames { source: &mut source, log: &log },
+                &pieces,
+                &calibration,
+                &recognition,
+                side,
+                start,
+                &config,
+                &control,
+                |event| {
+                    log.lock().unwrap().record_event(&event);
+                    match &event {
+                        AutoPlayEvent::Board { event: board @ WatchEvent::Move { side, .. } } if side != ours => {
+                            let _ = app.emit("opponent-move", board);
+                        }
+                        AutoPlayEvent::Desync(desync) => {
+                            let _ = app.emit("automation-desync", desync);
+                        }
+                        _ => {}
+                    }
+                    let _ = app.emit("auto-play", &event);
+                },
+            );
+            client.quit();
+            last
+        };
+        let last = session();
+        // The log is written even when the session ended with an error
+        let export = log.lock().unwrap().export(&log_dir, &name)?;
+        match last {
+            Ok(last) => Ok(AutoPlaySummary { fen: last.map(|p| p.to_fen()), log: export }),
+            Err(e) => Err(format!("{} (session log saved to {})", e, export.log)),
+        }
+    })
+    .await
+    .map_err(|e| e.to_string());
+    *state.control.lock().unwrap() = None;
+    result?
+}
+
+fn auto_play_control(state: &AutoPlayHandle) -> Result<Arc<AutoPlayControl>, String> {
+    state.control.lock().unwrap().clone().ok_or_else(|| "Auto-play is not running".to_string())
+}
+
+// Finishes the move in progress, then only follows the board
+#[tauri::command]
+async fn auto_play_pause(state: tauri::State<'_, AutoPlayHandle>) -> Result<(), String> {
+    auto_play_control(&state)?.pause.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+#[tauri::command]
+async fn auto_play_resume(state: tauri::State<'_, AutoPlayHandle>) -> Result<(), String> {
+    auto_play_control(&state)?.pause.store(false, Ordering::SeqCst);
+    Ok(())
+}
+
+#[tauri::command]
+async fn auto_play_stop(state: tauri::State<'_, AutoPlayHandle>) -> Result<(), String> {
+    auto_play_control(&state)?.stop.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+// Stops the mouse action in progress, releasing the button if it is held
+#[tauri::command]
+async fn cancel_mouse_action(state: tauri::State<'_, MouseState>) -> Result<(), String> {
+    state.cancel.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+/// Check if the engine file exists and is a file on Android.
+#[cfg(target_os = "android")]
+fn check_android_engine_file(path: &str) -> Result<(), String> {
+    let engine_path = Path::new(path);
+    if !engine_path.exists() {
+        return Err(format!("Engine file not found: {}", path));
+    }
+    if let Ok(metadata) = fs::metadata(engine_path) {
+        if !metadata.is_file() {
+            return Err(format!("Path is not a file: {}", path));
+        }
+    } else {
+        return Err(format!("Cannot access engine file metadata: {}", path));
+    }
+    Ok(())
+}
+
+/// Copy a file from a user-accessible directory to the app's internal storage.
+#[cfg(target_os = "android")]
+fn copy_file_to_internal_storage(source_path_str: &str, app_handle: &AppHandle) -> Result<String, String> {
+    let source_path = Path::new(source_path_str);
+    if !source_path.exists() {
+        let error_msg = format!("Source file not found: {}", source_path.display());
+        let _ = app_handle.emit("engine-output", format!("[DEBUG] {}", error_msg));
+        return Err(error_msg);
+    }
+
+    let bundle_identifier = &app_handle.config().identifier;
+    let internal_dir = format!("/data/data/{}/files/engines", bundle_identifier);
+    if let Err(e) = fs::create_dir_all(&internal_dir) {
+        let error_msg = format!("Failed to create internal directory: {}", e);
+        let _ = app_handle.emit("engine-output", format!("[DEBUG] {}", error_msg));
+        return Err(error_msg);
+    }
+
+    let filename = source_path.file_name()
+        .ok_or_else(|| "Invalid source path".to_string())?
+        .to_str()
+        .ok_or_else(|| "Invalid filename encoding".to_string())?;
+    let dest_path_str = format!("{}/{}", internal_dir, filename);
+    let dest_path = Path::new(&dest_path_str);
+
+    let _ = app_handle.emit("engine-output", format!("[DEBUG] Copying file from {} to {}", source_path.display(), dest_path.display()));
+
+    if let Err(e) = fs::copy(source_path, dest_path) {
+        let error_msg = format!("Failed to copy file: {}", e);
+        let _ = app_handle.emit("engine-output", format!("[DEBUG] {}", error_msg));
+        return Err(error_msg);
+    }
+
+    let _ = app_handle.emit("engine-output", "[DEBUG] Setting executable permission...");
+    
+    match fs::metadata(dest_path) {
+        Ok(metadata) => {
+            let mut permissions = metadata.permissions();
+            permissions.set_mode(0o755);   
+
+            if let Err(e) = fs::set_permissions(dest_path, permissions) {
+                let error_msg = format!("Failed to set executable permission: {}", e);
+                let _ = app_handle.emit("engine-output", format!("[DEBUG] {}", error_msg));
+                return Err(error_msg);
+            }
+        },
+        Err(e) => {
+            let error_msg = format!("Failed to get metadata for setting permissions: {}", e);
+            let _ = app_handle.emit("engine-output", format!("[DEBUG] {}", error_msg));
+            return Err(error_msg);
+        }
+    }
+    
+    let _ = app_handle.emit("engine-output", format!("[DEBUG] Successfully copied and made executable: {}", dest_path.display()));
+    Ok(dest_path_str)
+}
+
+/// Save game notation to Android's external storage.
+#[tauri::command]
+async fn save_game_notation(content: String, filename: String, app: AppHandle) -> Result<String, String> {
+    if !cfg!(target_os = "android") {
+        return Err("This function is only available on Android".to_string());
+    }
+
+    let bundle_identifier = &app.config().identifier;
+    let external_dir = format!("/storage/emulated/0/Android/data/{}/files/notations", bundle_identifier);
+    
+    if let Err(e) = fs::create_dir_all(&external_dir) {
+        return Err(format!("Failed to create notations directory: {}", e));
+    }
+
+    let file_path_str = format!("{}/{}", external_dir, filename);
+    let file_path = Path::new(&file_path_str);
+
+    if let Err(e) = fs::write(file_path, content) {
+        return Err(format!("Failed to write notation file: {}", e));
+    }
+
+    Ok(file_path_str)
+}
+
+/// Save chart image to Android's external storage.
+#[tauri::command]
+async fn save_chart_image(content: String, filename: String, app: AppHandle) -> Result<String, String> {
+    if !cfg!(target_os = "android") {
+        return Err("This function is only available on Android".to_string());
+    }
+
+    let bundle_identifier = &app.config().identifier;
+    let external_dir = format!("/storage/emulated/0/Android/data/{}/files/charts", bundle_identifier);
+    
+    if let Err(e) = fs::create_dir_all(&external_dir) {
+        return Err(format!("Failed to create charts directory: {}", e));
+    }
+
+    let cleaned_content = content.replace("data:image/png;base64,", "");
+    let decoded_content = match base64::engine::general_purpose::STANDARD.decode(&cleaned_content) {
+        Ok(data) => data,
+        Err(e) => return Err(format!("Failed to decode image data: {}", e)),
+    };
+
+    let file_path_str = format!("{}/{}", external_dir, filename);
+    let file_path = Path::new(&file_path_str);
+
+    if let Err(e) = fs::write(file_path, decoded_content) {
+        return Err(format!("Failed to write chart image file: {}", e));
+    }
+
+    Ok(file_path_str)
+}
+
+fn get_config_file_path(app: &AppHandle) -> Result<String, String> {
+    if cfg!(target_os = "android") {
+        let bundle_identifier = &app.config().identifier;
+        Ok(format!("/data/data/{}/files/config.ini", bundle_identifier))
+    } else {
+        Ok("config.ini".to_string())
+    }
+}
+
+// Per-user data directory on desktop, so files do not depend on where the app was started from
+fn get_data_path(app: &AppHandle, name: &str) -> Result<String, String> {
+    if cfg!(target_os = "android") {
+        let bundle_identifier = &app.config().identifier;
+        Ok(format!("/data/data/{}/files/{}", bundle_identifier, name))
+    } else {
+        let data_dir = app.path().app_data_dir().map_err(|e| format!("Failed to resolve data directory: {}", e))?;
+        fs::create_dir_all(&data_dir).map_err(|e| format!("Failed to create {}: {}", data_dir.display(), e))?;
+        Ok(data_dir.join(name).to_string_lossy().to_string())
+    }
+}
+
+fn get_autosave_file_path(app: &AppHandle) -> Result<String, String> {
+    let path = get_data_path(app, "Autosave.json")?;
+    // Earlier versions kept the autosave in the working directory
+    let legacy = Path::new("Autosave.json");
+    if !cfg!(target_os = "android") && !Path::new(&path).exists() && legacy.exists() {
+        fs::copy(legacy, &path).map_err(|e| format!("Failed to move the autosave file: {}", e))?;
+    }
+    Ok(path)
+}
+
+fn get_analysis_queue_path(app: &AppHandle) -> Result<String, String> {
+    get_data_path(app, "analysis_queue.json")
+}
+
+fn get_engine_registry_path(app: &AppHandle) -> Result<String, String> {
+    get_data_path(app, "engines.json")
+}
+
+fn get_game_db_path(app: &AppHandle) -> Result<String, String> {
+    get_data_path(app, "jieqi_games.db")
+}
+
+fn get_opening_book_db_path(app: &AppHandle) -> Result<String, String> {
+    if cfg!(target_os = "android") {
+        let bundle_identifier = &app.config().identifier;
+        Ok(format!("/data/data/{}/files/jieqi_openings.jb", bundle_identifier))
+    } else {
+        Ok("jieqi_openings.jb".to_string())
+    }
+}
+
+fn get_analysis_cache_path(app: &AppHandle) -> Result<String, String> {
+    get_data_path(app, "analysis_cache.db")
+}
+
+fn get_calibration_path(app: &AppHandle) -> Result<String, String> {
+    get_data_path(app, "calibration.json")
+}
+
+fn get_piece_sets_dir(app: &AppHandle) -> Result<String, String> {
+    get_data_path(app, "piece_sets")
+}
+
+fn get_engine_logs_dir(app: &AppHandle) -> Result<String, String> {
+    get_data_path(app, "engine_logs")
+}
+
+fn get_auto_play_logs_dir(app: &AppHandle) -> Result<String, String> {
+    get_data_path(app, "auto_play_logs")
+}
+
+// One directory of templates per piece set
+fn get_piece_set_path(app: &AppHandle, name: &str) -> Result<std::path::PathBuf, String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name == ".." {
+        return Err(format!("Invalid piece set name: {}", name));
+    }
+    Ok(Path::new(&get_piece_sets_dir(app)?).join(name))
+}
+
+// Serializes read-modify-write cycles on the settings file
+static SETTINGS_LOCK: Mutex<()> = Mutex::new(());
+
+fn get_settings_file_path(app: &AppHandle) -> Result<std::path::PathBuf, String> {
+    if cfg!(target_os = "android") {
+        let bundle_identifier = &app.config().identifier;
+        Ok(std::path::PathBuf::from(format!("/data/data/{}/files/settings.json", bundle_identifier)))
+    } else {
+        let config_dir = app.path().app_config_dir().map_err(|e| format!("Failed to resolve config directory: {}", e))?;
+        Ok(config_dir.join("settings.json"))
+    }
+}
+
+// Migrates a legacy config.ini (and its engine list) on first use
+fn load_settings(app: &AppHandle) -> Result<AppSettings, String> {
+    let settings_path = get_settings_file_path(app)?;
+    let legacy_path = get_config_file_path(app)?;
+    let migrating = !settings_path.exists() && Path::new(&legacy_path).exists();
+    let settings = settings::load_or_migrate(&settings_path, Path::new(&legacy_path))?;
+
+    if migrating {
+        let list = settings.extra.get("Engines").and_then(|e| e.get("list")).and_then(|l| l.as_str());
+        if let Some(list) = list {
+            let registry_path = get_engine_registry_path(app)?;
+            let mut registry = EngineRegistry::load(&registry_path)?;
+            if registry.import_legacy_list(list)? > 0 {
+                registry.save(&registry_path)?;
+            }
+        }
+    }
+    Ok(settings)
+}
+
+// The frontend still exchanges INI text; it is converted to and from the typed settings file
+#[tauri::command]
+async fn load_config(app: AppHandle) -> Result<String, String> {
+    let _guard = SETTINGS_LOCK.lock().unwrap();
+    load_settings(&app)?.to_ini()
+}
+
+#[tauri::command]
+async fn save_config(content: String, app: AppHandle) -> Result<(), String> {
+    let _guard = SETTINGS_LOCK.lock().unwrap();
+    load_settings(&app)?;
+    AppSettings::from_ini(&content)?.save(get_settings_file_path(&app)?)
+}
+
+#[tauri::command]
+async fn clear_config(app: AppHandle) -> Result<(), String> {
+    let _guard = SETTINGS_LOCK.lock().unwrap();
+    for path in [get_settings_file_path(&app)?, std::path::PathBuf::from(get_config_file_path(&app)?)] {
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| format!("Failed to delete config file: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn settings_get(app: AppHandle) -> Result<AppSettings, String> {
+    let _guard = SETTINGS_LOCK.lock().unwrap();
+    load_settings(&app)
+}
+
+#[tauri::command]
+async fn settings_get_section(section: String, app: AppHandle) -> Result<serde_json::Value, String> {
+    let _guard = SETTINGS_LOCK.lock().unwrap();
+    load_settings(&app)?.get_section(&section)
+}
+
+/// Merges `value` into one section and saves. Returns notes about values that were reset.
+#[tauri::command]
+async fn settings_set_section(section: String, value: serde_json::Value, app: AppHandle) -> Result<Vec<String>, String> {
+    let _guard = SETTINGS_LOCK.lock().unwrap();
+    let mut settings = load_settings(&app)?;
+    let fixes = settings.set_section(&section, value)?;
+    settings.save(get_settings_file_path(&app)?)?;
+    Ok(fixes)
+}
+
+#[tauri::command]
+async fn settings_reset(app: AppHandle) -> Result<AppSettings, String> {
+    let _guard = SETTINGS_LOCK.lock().unwrap();
+    let settings = AppSettings::default();
+    settings.save(get_settings_file_path(&app)?)?;
+    Ok(settings)
+}
+
+#[tauri::command]
+async fn save_autosave(content: String, app: AppHandle) -> Result<(), String> {
+    let autosave_path = get_autosave_file_path(&app)?;
+    AutosaveStore::new(autosave_path, autosave::DEFAULT_SNAPSHOTS).save(&content)
+}
+
+#[tauri::command]
+async fn load_autosave(app: AppHandle) -> Result<String, String> {
+    let autosave_path = get_autosave_file_path(&app)?;
+    AutosaveStore::new(autosave_path, autosave::DEFAULT_SNAPSHOTS).load()
+}
+
+/// Lists the autosave snapshots, newest first, for recovery when the current file is corrupt.
+#[tauri::command]
+async fn list_autosave_snapshots(app: AppHandle) -> Result<Vec<SnapshotInfo>, String> {
+    let autosave_path = get_autosave_file_path(&app)?;
+    Ok(AutosaveStore::new(autosave_path, autosave::DEFAULT_SNAPSHOTS).list_snapshots())
+}
+
+#[tauri::command]
+async fn recover_autosave(file_name: String, app: AppHandle) -> Result<String, String> {
+    let autosave_path = get_autosave_file_path(&app)?;
+    AutosaveStore::new(autosave_path, autosave::DEFAULT_SNAPSHOTS).recover(&file_name)
+}
+
+#[cfg(target_os = "android")]
+fn get_user_engine_directory() -> String {
+    "/storage/emulated/0/jieqibox/engines".to_string()
+}
+
+#[cfg(target_os = "android")]
+fn sync_and_list_engines(app_handle: &AppHandle) -> Result<Vec<String>, String> {
+    let bundle_identifier = &app_handle.config().identifier;
+    let source_dirs = vec![
+        get_user_engine_directory(),
+        format!("/storage/emulated/0/Android/data/{}/files/engines", bundle_identifier),
+    ];
+    let internal_dir_str = format!("/data/data/{}/files/engines", bundle_identifier);
+    
+    let _ = app_handle.emit("engine-output", format!("[DEBUG] Syncing engines. Internal dir: {}. Source dirs: {:?}", internal_dir_str, source_dirs));
+    
+    if let Err(e) = fs::create_dir_all(&internal_dir_str) {
+        let error_msg = format!("Failed to create internal directory '{}': {}", internal_dir_str, e);
+        let _ = app_handle.emit("engine-output", format!("[DEBUG] {}", error_msg));
+        return Err(error_msg);
+    } else {
+        let _ = app_handle.emit("engine-output", format!("[DEBUG] Internal directory created/exists: {}", internal_dir_str));
+    }
+
+    for user_dir in &source_dirs {
+        let _ = app_handle.emit("engine-output", format!("[DEBUG] Checking source directory: {}", user_dir));
+        let user_path = Path::new(user_dir);
+
+        if !user_path.exists() {
+            let _ = app_handle.emit("engine-output", format!("[DEBUG] Source directory does not exist, skipping: {}", user_dir));
+            continue;
+        }
+
+        if let Ok(entries) = fs::read_dir(user_path) {
+            for entry_result in entries {
+                if let Ok(entry) = entry_result {
+                    let path = entry.path();
+                    if path.is_file() {
+                        if let Err(e) = copy_file_to_internal_storage(path.to_str().unwrap_or(""), app_handle) {
+                            let _ = app_handle.emit("engine-output", format!("[DEBUG] Failed to copy file {}: {}", path.display(), e));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut available_engines = Vec::new();
+    if let Ok(entries) = fs::read_dir(&internal_dir_str) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_file() {
+                if let Some(path_str) = path.to_str() {
+                    available_engines.push(path_str.to_string());
+                }
+            }
+        }
+    }
+    
+    let _ = app_handle.emit("engine-output", format!("[DEBUG] Available internal engines: {:?}", available_engines));
+    Ok(available_engines)
+}
+
+#[tauri::command]
+async fn kill_engine(process_state: tauri::State<'_, EngineProcess>) -> Result<(), String> {
+    if let Some(engine) = process_state.lock().unwrap().take() {
+        engine.kill();
+    }
+    Ok(())
+}
+
+/// With `transport`, the engine is reached over TCP or through a wrapper command such as `ssh`
+/// instead of being started here; a dropped connection is re-established per `reconnect`, unless
+/// `quit` was sent, and the options set so far are sent again. `engine-reconnected` is emitted
+/// then, or `engine-disconnected` once it gives up. `encoding` is that of the engine's output,
+/// `auto` when absent. With `log`, every line sent to and received from the engine is also
+/// written to a rotating file in the engine log directory. With `protocol`, the handshake is
+/// done here and the engine's identity and options returned; the engine is then pinged with
+/// `isready` while idle, and `engine-unresponsive` is emitted when it stops answering.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn spawn_engine(
+    path: String,
+    args: Vec<String>,
+    transport: Option<EngineTransport>,
+    reconnect: Option<ReconnectConfig>,
+    encoding: Option<EngineEncoding>,
+    log: Option<EngineLogConfig>,
+    protocol: Option<EngineProtocol>,
+    handshake_timeout_ms: Option<u64>,
+    health: Option<HealthConfig>,
+    app: AppHandle,
+    process_state: tauri::State<'_, EngineProcess>,
+    log_state: tauri::State<'_, EngineIoLog>,
+    watch_state: tauri::State<'_, EngineWatch>,
+) -> Result<Option<EngineInfo>, String> {
+    let transport = transport.unwrap_or_default();
+    let encoding = encoding.unwrap_or_default();
+    if cfg!(target_os = "android") {
+        let _ = app.emit("engine-output", format!("[DEBUG] Spawning engine: Path={}, Args={:?}", path, args));
+    }
+    
+    let final_path = path;
+
+    #[cfg(target_os = "android")]
+    {
+        if !transport.is_remote() {
+            if let Err(e) = check_android_engine_file(&final_path) {
+                let _ = app.emit("engine-output", format!("[DEBUG] Engine file validation failed: {}", e));
+                return Err(e);
+            }
+            let _ = app.emit("engine-output", "[DEBUG] Engine file validation passed.");
+        }
+    }
+    
+    kill_engine(process_state.clone()).await.ok();
+    let session = NEXT_ENGINE_SESSION.fetch_add(1, Ordering::Relaxed);
+    *log_state.lock().unwrap() = match log {
+        Some(config) => Some(EngineLogger::new(get_engine_logs_dir(&app)?, &final_path, config)?),
+        None => None,
+    };
+    *watch_state.lock().unwrap() = protocol.map(EngineMonitor::new);
+
+    if transport.is_remote() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let connection = Connection::open(&transport, &final_path, &args, encoding, &tx)?;
+        *process_state.lock().unwrap() = Some(RunningEngine::new(session, EngineLink::Remote(connection)));
+
+        let app = app.clone();
+        let process = process_state.inner().clone();
+        let (io_log, watch) = (log_state.inner().clone(), watch_state.inner().clone());
+        let (path, args, reconnect) = (final_path.clone(), args.clone(), reconnect.unwrap_or_default());
+        std::thread::spawn(move || {
+            for output in rx.iter() {
+                let closed = match output {
+                    EngineOutput::Line(line) => {
+                        forward_engine_output(&app, &io_log, &watch, line);
+                        continue;
+                    }
+                    EngineOutput::Closed(id) => id,
+                };
+                let current = process.lock().unwrap().as_ref().and_then(|engine| match &engine.link {
+                    EngineLink::Remote(connection) if engine.session == session => Some((connection.id(), engine.quit)),
+                    _ => None,
+                });
+                match current {
+                    // Killed or replaced by another engine, or told to quit
+                    None | Some((_, true)) => break,
+                    // A connection given up earlier
+                    Some((id, _)) if id != closed => continue,
+                    Some(_) => {}
+                }
+
+                let _ = app.emit("engine-output", "[DEBUG] Lost the connection to the engine, reconnecting...");
+                let wanted = || process.lock().unwrap().as_ref().is_some_and(|e| e.session == session && !e.quit);
+                let mut result = Err("no attempts configured".to_string());
+                for attempt in 1..=reconnect.attempts {
+                    std::thread::sleep(Duration::from_millis(reconnect.delay_ms));
+                    if !wanted() {
+                        break;
+                    }
+                    result = Connection::open(&transport, &path, &args, encoding, &tx)
+                        .map(|connection| (attempt, connection));
+                    if result.is_ok() {
+                        break;
+                    }
+                }
+                let mut engine = process.lock().unwrap();
+                if !engine.as_ref().is_some_and(|engine| engine.session == session && !engine.quit) {
+                    break;
+                }
+                let (attempt, connection) = match result {
+                    Ok(reconnected) => reconnected,
+                    Err(error) => {
+                        *engine = None;
+                        let _ = app.emit("engine-disconnected", serde_json::json!({ "name": path, "error": error }));
+                        break;
+                    }
+                };
+                let options = match engine.as_mut() {
+                    Some(engine) => {
+                        engine.link = EngineLink::Remote(connection);
+                        engine.options.clone()
+                    }
+                    None => Vec::new(),
+                };
+                drop(engine);
+                // The engine starts over, so its identity is asked for again and the options set so far re-applied
+                if let Some(protocol) = protocol {
+                    *watch.lock().unwrap() = Some(EngineMonitor::new(protocol));
+                    let _ = write_engine(&process, &io_log, &watch, protocol.handshake().0);
+                }
+                for option in &options {
+                    let _ = write_engine(&process, &io_log, &watch, option);
+                }
+                let _ = app.emit("engine-reconnected", serde_json::json!({ "name": path, "attempt": attempt }));
+            }
+        });
+    } else {
+        let engine_dir = Path::new(&final_path)
+            .parent()
+            .ok_or_else(|| "Failed to get engine directory".to_string())?
+            .to_str()
+            .ok_or_else(|| "Failed to convert engine directory to string".to_string())?;
+
+        let (mut rx, child) = match app.shell().command(&final_path)
+            .args(args)
+            .current_dir(engine_dir)
+            .spawn() 
+        {
+            Ok(result) => result,
+            Err(e) => {
+                let error_msg = format!("Failed to spawn engine: {}", e);
+                if cfg!(target_os = "android") {
+                    let _ = app.emit("engine-output", format!("[DEBUG] {}", error_msg));
+                }
+                return Err(error_msg);
+            }
+        };
+        *process_state.lock().unwrap() = Some(RunningEngine::new(session, EngineLink::Shell(child)));
+
+        let app_clone = app.clone();
+        let io_log = log_state.inner().clone();
+        let watch = watch_state.inner().clone();
+        async_runtime::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Stdout(buf) | CommandEvent::Stderr(buf) = event {
+                    forward_engine_output(&app_clone, &io_log, &watch, engine_transport::decode(&buf, encoding));
+                }
+            }
+        });
+    }
+
+    let Some(protocol) = protocol else { return Ok(None) };
+    let (command, done) = protocol.handshake();
+    write_engine(&process_state, &log_state, &watch_state, command)?;
+    let timeout = Duration::from_millis(handshake_timeout_ms.unwrap_or(10000));
+    let watch = watch_state.inner().clone();
+    let info = async_runtime::spawn_blocking(move || {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(info) = watch.lock().unwrap().as_ref().and_then(|m| m.info().cloned()) {
+                return Some(info);
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    let Some(info) = info else {
+        kill_engine(process_state.clone()).await.ok();
+        return Err(format!("Engine did not answer '{}' with '{}' within {} ms", command, done, timeout.as_millis()));
+    };
+
+    // Runs until this engine is killed or replaced
+    let health = health.unwrap_or_default();
+    let name = if info.name.is_empty() { final_path.clone() } else { info.name.clone() };
+    let process = process_state.inner().clone();
+    let (io_log, watch) = (log_state.inner().clone(), watch_state.inner().clone());
+    std::thread::spawn(move || loop {
+        std::thread::sleep(Duration::from_millis(200));
+        if process.lock().unwrap().as_ref().map(|engine| engine.session) != Some(session) {
+            break;
+        }
+        let action = watch.lock().unwrap().as_mut().and_then(|monitor| monitor.tick(&health));
+        match action {
+            Some(HealthAction::Ping) => {
+                let _ = write_line(&process, &io_log, "isready");
+            }
+            Some(HealthAction::Unresponsive { waited_ms }) => {
+                let _ = app.emit("engine-unresponsive", serde_json::json!({ "name": name, "waited_ms": waited_ms }));
+            }
+            None => {}
+        }
+    });
+    Ok(Some(info))
+}
+
+// Logs a chunk of engine output and passes it on to the UI, without the answers to our own pings
+fn forward_engine_output(app: &AppHandle, io_log: &EngineIoLog, watch: &EngineWatch, text: String) {
+    if let Some(logger) = io_log.lock().unwrap().as_mut() {
+        let _ = logger.log(Direction::Receive, &text);
+    }
+    let text = match watch.lock().unwrap().as_mut() {
+        Some(monitor) => {
+            let kept: Vec<String> = text.lines().filter(|line| monitor.on_output(line)).map(str::to_string).collect();
+            if kept.len() == text.lines().count() { text } else { kept.join("\n") }
+        }
+        None => text,
+    };
+    if !text.is_empty() {
+        let _ = app.emit("engine-output", text);
+    }
+}
+
+fn write_line(process: &EngineProcess, io_log: &EngineIoLog, command: &str) -> Result<(), String> {
+    let mut process = process.lock().unwrap();
+    process.as_mut().ok_or("Engine not running.")?.write(command)?;
+    if let Some(logger) = io_log.lock().unwrap().as_mut() {
+        let _ = logger.log(Direction::Send, command);
+    }
+    Ok(())
+}
+
+// Like `write_line`, and lets the monitor know what the engine was asked
+fn write_engine(
+    process: &EngineProcess,
+    io_log: &EngineIoLog,
+    watch: &EngineWatch,
+    command: &str,
+) -> Result<(), String> {
+    write_line(process, io_log, command)?;
+    if let Some(monitor) = watch.lock().unwrap().as_mut() {
+        monitor.on_command(command);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn send_to_engine(
+    command: String,
+    process_state: tauri::State<'_, EngineProcess>,
+    log_state: tauri::State<'_, EngineIoLog>,
+    watch_state: tauri::State<'_, EngineWatch>,
+) -> Result<(), String> {
+    write_engine(&process_state, &log_state, &watch_state, &command)
+}
+
+/// Engine I/O logs, most recently written first.
+#[tauri::command]
+async fn list_engine_logs(app: AppHandle) -> Result<Vec<EngineLogFile>, String> {
+    Ok(engine_log::list_logs(get_engine_logs_dir(&app)?))
+}
+
+// The whole file, or its last `max_lines` lines
+#[tauri::command]
+async fn read_engine_log(app: AppHandle, file_name: String, max_lines: Option<usize>) -> Result<String, String> {
+    engine_log::read_log(get_engine_logs_dir(&app)?, &file_name, max_lines)
+}
+
+#[cfg(target_os = "android")]
+#[tauri::command]
+async fn get_default_android_engine_path() -> Result<String, String> {
+    Ok(get_user_engine_directory())
+}
+
+#[cfg(target_os = "android")]
+#[tauri::command]
+async fn check_android_file_permissions(path: String) -> Result<bool, String> {
+    if let Ok(metadata) = fs::metadata(Path::new(&path)) {
+        Ok(metadata.is_file())
+    } else {
+        Ok(false)
+    }
+}
+
+#[cfg(target_os = "android")]
+#[tauri::command]
+async fn get_bundle_identifier(app: AppHandle) -> Result<String, String> {
+    Ok(app.config().identifier.clone())
+}
+
+#[cfg(target_os = "android")]
+#[tauri::command]
+async fn scan_android_engines(app: AppHandle) -> Result<Vec<String>, String> {
+    sync_and_list_engines(&app)
+}
+
+#[cfg(target_os = "android")]
+#[tauri::command]
+async fn request_saf_file_selection(name: String, args: String, has_nnue: bool, app: AppHandle) -> Result<(), String> {
+    let _ = app.emit("request-saf-file-selection", serde_json::json!({
+        "name": name,
+        "args": args,
+        "has_nnue": has_nnue
+    }));
+    Ok(())
+}
+
+#[cfg(target_os = "android")]
+#[tauri::command]
+async fn handle_saf_file_result(
+    temp_file_path: String,
+    filename: String,
+    name: String,
+    args: String,
+    has_nnue: bool,
+    app: AppHandle,
+) -> Result<(), String> {
+    let _ = app.emit("engine-output", format!("[DEBUG] SAF result for engine '{}': TempPath={}, Filename={}", name, temp_file_path, filename));
+
+    if temp_file_path.is_empty() {
+        return Err("SAF file processing failed: temporary path is empty.".to_string());
+    }
+
+    let engine_instance_id = format!("{}_{}", name, chrono::Utc::now().timestamp_millis());
+    let bundle_identifier = &app.config().identifier;
+    let engine_base_dir = format!("/data/data/{}/files/engines/{}", bundle_identifier, &engine_instance_id);
+
+    if let Err(e) = fs::create_dir_all(&engine_base_dir) {
+        let error_msg = format!("Failed to create final engine directory: {}", e);
+        let _ = app.emit("engine-output", format!("[DEBUG] {}", error_msg));
+        return Err(error_msg);
+    }
+    
+    let final_path_str = format!("{}/{}", engine_base_dir, &filename);
+
+    if let Err(e) = fs::rename(&temp_file_path, &final_path_str) {
+        let error_msg = format!("Failed to move engine file from temp to final destination: {}", e);
+        let _ = app.emit("engine-output", format!("[DEBUG] {}", error_msg));
+        if let Err(copy_err) = fs::copy(&temp_file_path, &final_path_str) {
+             let copy_error_msg = format!("Fallback copy also failed: {}", copy_err);
+             let _ = app.emit("engine-output", format!("[DEBUG] {}", copy_error_msg));
+             return Err(copy_error_msg);
+        } else {
+            let _ = fs::remove_file(&temp_file_path);
+        }
+    }
+
+    let final_path = Path::new(&final_path_str);
+    let mut perms = fs::metadata(final_path).map_err(|e| e.to_string())?.permissions();
+    perms.set_mode(0o755);
+    fs::set_permissions(final_path, perms).map_err(|e| e.to_string())?;
+
+    if has_nnue {
+        let _ = app.emit("engine-output", "[DEBUG] Engine requires NNUE file, requesting file selection...");
+        let nnue_request_data = serde_json::json!({
+            "engine_name": name,
+            "engine_path": final_path_str,
+            "args": args,
+            "engine_instance_id": engine_instance_id
+        });
+        app.emit("request-nnue-file", nnue_request_data).map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+
+    let new_engine_data = serde_json::json!({
+        "id": format!("engine_{}", chrono::Utc::now().timestamp_millis()),
+        "name": name,
+        "path": final_path_str,
+        "args": args
+    });
+
+    app.emit("android-engine-added", new_engine_data).map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
+
+#[cfg(target_os = "android")]
+#[tauri::command]
+async fn handle_nnue_file_result(
+    temp_file_path: String,
+    filename: String,
+    engine_name: String,
+    engine_path: String,
+    args: String,
+    engine_instance_id: String,
+    app: AppHandle,
+) -> Result<(), String> {
+    let _ = app.emit("engine-output", format!("[DEBUG] NNUE file result for engine '{}': TempPath={}, Filename={}", engine_name, temp_file_path, filename));
+
+    if temp_file_path.is_empty() {
+        return Err("NNUE file processing failed: temporary path is empty.".to_string());
+    }
+
+    let bundle_identifier = &app.config().identifier;
+    let engine_base_dir = format!("/data/data/{}/files/engines/{}", bundle_identifier, &engine_instance_id);
+    let final_nnue_path_str = format!("{}/{}", engine_base_dir, &filename);
+
+    if let Err(e) = fs::rename(&temp_file_path, &final_nnue_path_str) {
+        let error_msg = format!("Failed to move NNUE file from temp to final destination: {}", e);
+        let _ = app.emit("engine-output", format!("[DEBUG] {}", error_msg));
+        if let Err(copy_err) = fs::copy(&temp_file_path, &final_nnue_path_str) {
+             let copy_error_msg = format!("Fallback copy also failed: {}", copy_err);
+             let _ = app.emit("engine-output", format!("[DEBUG] {}", copy_error_msg));
+             return Err(copy_error_msg);
+        } else {
+            let _ = fs::remove_file(&temp_file_path);
+        }
+    }
+
+    let _ = app.emit("engine-output", format!("[DEBUG] NNUE file successfully copied to: {}", final_nnue_path_str));
+
+    let new_engine_data = serde_json::json!({
+        "id": format!("engine_{}", chrono::Utc::now().timestamp_millis()),
+        "name": engine_name,
+        "path": engine_path,
+        "args": args
+    });
+
+    app.emit("android-engine-added", new_engine_data).map_err(|e| e.to_string())?;
+    
+    Ok(())
+}
+
+#[tauri::command]
+async fn open_external_url(url: String, app: AppHandle) -> Result<(), String> {
+    let result = if cfg!(target_os = "windows") {
+        Command::new("cmd").args(["/C", "start", &url]).spawn()
+    } else if cfg!(target_os = "macos") {
+        Command::new("open").arg(&url).spawn()
+    } else if cfg!(target_os = "android") {
+        let _ = app.emit("open-external-url", url);
+        return Ok(());
+    } else {
+        Command::new("xdg-open").arg(&url).spawn()
+    };
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Failed to open URL: {}", e))
+    }
+}
+
+// Opening Book Commands
+
+#[tauri::command]
+async fn opening_book_add_entry(
+    request: AddEntryRequest,
+    app: AppHandle,
+) -> Result<bool, String> {
+    let db_path = get_opening_book_db_path(&app)?;
+    let book = JieqiOpeningBook::new(db_path).map_err(|e| e.to_string())?;
+    book.add_entry(&request)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn opening_book_delete_entry(
+    fen: String,
+    uci_move: String,
+    app: AppHandle,
+) -> Result<bool, String> {
+    let db_path = get_opening_book_db_path(&app)?;
+    let book = JieqiOpeningBook::new(db_path).map_err(|e| e.to_string())?;
+    book.delete_entry(&fen, &uci_move)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn opening_book_query_moves(fen: String, app: AppHandle) -> Result<Vec<MoveData>, String> {
+    let db_path = get_opening_book_db_path(&app)?;
+    let book = JieqiOpeningBook::new(db_path).map_err(|e| e.to_string())?;
+    book.query_moves(&fen).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn opening_book_get_stats(app: AppHandle) -> Result<OpeningBookStats, String> {
+    let db_path = get_opening_book_db_path(&app)?;
+    let book = JieqiOpeningBook::new(db_path).map_err(|e| e.to_string())?;
+    book.get_stats().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn opening_book_clear_all(app: AppHandle) -> Result<(), String> {
+    let db_path = get_opening_book_db_path(&app)?;
+    let book = JieqiOpeningBook::new(db_path).map_err(|e| e.to_string())?;
+    book.clear_all().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn opening_book_export_all(app: AppHandle) -> Result<String, String> {
+    let db_path = get_opening_book_db_path(&app)?;
+    let book = JieqiOpeningBook::new(db_path).map_err(|e| e.to_string())?;
+    let entries = book.export_all().map_err(|e| e.to_string())?;
+    serde_json::to_string(&entries).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn opening_book_import_entries(
+    json_data: String,
+    app: AppHandle,
+) -> Result<(i32, Vec<String>), String> {
+    let db_path = get_opening_book_db_path(&app)?;
+    let book = JieqiOpeningBook::new(db_path).map_err(|e| e.to_string())?;
+
+    let entries: Vec<opening_book::OpeningBookEntry> =
+        serde_json::from_str(&json_data).map_err(|e| e.to_string())?;
+
+    let mut imported = 0;
+    let mut errors = Vec::new();
+
+    for entry in entries {
+        for move_data in entry.moves {
+            let request = AddEntryRequest {
+                fen: entry.fen.clone(),
+                uci_move: move_data.uci_move.clone(),
+                priority: move_data.priority,
+                wins: move_data.wins,
+                draws: move_data.draws,
+                losses: move_data.losses,
+                allowed: move_data.allowed,
+                comment: move_data.comment.clone(),
+            };
+            match book.add_entry(&request) {
+                Ok(_) => imported += 1,
+                Err(e) => errors.push(format!("Failed to import move {}: {}", move_data.uci_move, e)),
+            }
+        }
+    }
+
+    Ok((imported, errors))
+}
+
+#[tauri::command]
+async fn opening_book_export_db(destination_path: String, app: AppHandle) -> Result<(), String> {
+    let source_path = get_opening_book_db_path(&app)?;
+    fs::copy(source_path, destination_path).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn opening_book_import_db(source_path: String, app: AppHandle) -> Result<(), String> {
+    let dest_path = get_opening_book_db_path(&app)?;
+    fs::copy(source_path, dest_path).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn save_game_notation_with_dialog(content: String, default_filename: String, app: AppHandle) -> Result<String, String> {
+    #[cfg(target_os = "android")]
+    {
+        return save_game_notation(content, default_filename, app).await;
+    }
+
+    #[cfg(not(target_os = "android"))]
+    {
+        use tauri_plugin_dialog::{DialogExt, FilePath};
+        
+        let file_path = app.dialog()
+            .file()
+            .set_file_name(&default_filename)
+            .add_filter("JSON files", &["json"])
+            .add_filter("All files", &["*"])
+            .blocking_save_file();
+
+        match file_path {
+            Some(FilePath::Path(path)) => {
+                fs::write(&path, content)
+                    .map_err(|e| format!("Failed to write file: {}", e))?;
+                
+                Ok(path.to_string_lossy().to_string())
+            },
+            Some(FilePath::Url(_)) => {
+                Err("URL paths are not supported".to_string())
+            },
+            None => {
+                Err("Save dialog was cancelled".to_string())
+            }
+        }
+    }
+}
+
+#[tauri::command]
+async fn engine_registry_list(app: AppHandle) -> Result<Vec<EngineEntry>, String> {
+    let registry = EngineRegistry::load(get_engine_registry_path(&app)?)?;
+    Ok(registry.engines)
+}
+
+#[tauri::command]
+async fn engine_registry_add(engine: NewEngine, app: AppHandle) -> Result<EngineEntry, String> {
+    let registry_path = get_engine_registry_path(&app)?;
+    let mut registry = EngineRegistry::load(&registry_path)?;
+    let entry = registry.add(engine)?;
+    registry.save(&registry_path)?;
+    Ok(entry)
+}
+
+#[tauri::command]
+async fn engine_registry_update(engine: EngineEntry, app: AppHandle) -> Result<(), String> {
+    let registry_path = get_engine_registry_path(&app)?;
+    let mut registry = EngineRegistry::load(&registry_path)?;
+    registry.update(engine)?;
+    registry.save(&registry_path)
+}
+
+#[tauri::command]
+async fn engine_registry_remove(id: String, app: AppHandle) -> Result<(), String> {
+    let registry_path = get_engine_registry_path(&app)?;
+    let mut registry = EngineRegistry::load(&registry_path)?;
+    registry.remove(&id)?;
+    registry.save(&registry_path)
+}
+
+/// Reports whether each registered engine (or only `id`) still exists and is executable.
+#[tauri::command]
+async fn engine_registry_check(id: Option<String>, app: AppHandle) -> Result<Vec<EngineHealth>, String> {
+    let registry = EngineRegistry::load(get_engine_registry_path(&app)?)?;
+    match id {
+        Some(id) => registry
+            .get(&id)
+            .map(|engine| vec![engine.check()])
+            .ok_or_else(|| format!("No engine with id '{}'", id)),
+        None => Ok(registry.check_all()),
+    }
+}
+
+/// Starts or connects to the engine just long enough to read its identity and option list.
+#[tauri::command]
+async fn discover_engine_options(engine: EngineSpec, timeout_ms: Option<u64>) -> Result<EngineInfo, String> {
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(10000));
+    async_runtime::spawn_blocking(move || engine_options::discover_options(&engine, timeout))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+/// Checks option values before they are sent with `setoption`; returns the normalized values.
+#[tauri::command]
+async fn validate_engine_options(
+    options: Vec<EngineOption>,
+    values: Vec<(String, String)>,
+) -> Result<Vec<(String, String)>, String> {
+    engine_options::validate_option_values(&options, &values).map_err(|errors| errors.join("\n"))
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+struct GameImportSummary {
+    imported: usize,
+    duplicates: usize,
+    failed: Vec<String>,
+}
+
+#[tauri::command]
+async fn game_db_import_files(paths: Vec<String>, app: AppHandle) -> Result<GameImportSummary, String> {
+    let db_path = get_game_db_path(&app)?;
+    async_runtime::spawn_blocking(move || {
+        let mut db = GameDatabase::new(db_path).map_err(|e| e.to_string())?;
+        let mut summary = GameImportSummary::default();
+        for path in paths {
+            let games = match notation_formats::read_games(&path) {
+                Ok(games) => games,
+                Err(e) => {
+                    summary.failed.push(format!("{}: {}", path, e));
+                    continue;
+                }
+            };
+            for game in games {
+                match db.import_game(&game, Some(&path)) {
+                    Ok(Some(_)) => summary.imported += 1,
+                    Ok(None) => summary.duplicates += 1,
+                    Err(e) => summary.failed.push(format!("{}: {}", path, e)),
+                }
+            }
+        }
+        Ok(summary)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn game_db_search(query: GameQuery, app: AppHandle) -> Result<GamePage, String> {
+    let db_path = get_game_db_path(&app)?;
+    let db = GameDatabase::new(db_path).map_err(|e| e.to_string())?;
+    db.search(&query).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn game_db_get_game(id: i64, app: AppHandle) -> Result<GameNotation, String> {
+    let db_path = get_game_db_path(&app)?;
+    let db = GameDatabase::new(db_path).map_err(|e| e.to_string())?;
+    db.get_game(id)?.ok_or_else(|| format!("No game with id {}", id))
+}
+
+#[tauri::command]
+async fn game_db_delete_game(id: i64, app: AppHandle) -> Result<bool, String> {
+    let db_path = get_game_db_path(&app)?;
+    let db = GameDatabase::new(db_path).map_err(|e| e.to_string())?;
+    db.delete_game(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn game_db_get_stats(app: AppHandle) -> Result<GameDbStats, String> {
+    let db_path = get_game_db_path(&app)?;
+    let db = GameDatabase::new(db_path).map_err(|e| e.to_string())?;
+    db.get_stats().map_err(|e| e.to_string())
+}
+
+/// Reads the first game of a JSON, XQF or PGN file into the notation model.
+#[tauri::command]
+async fn import_notation_file(path: String) -> Result<GameNotation, String> {
+    async_runtime::spawn_blocking(move || notation_formats::read_notation(&path))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct CheckedNotation {
+    notation: GameNotation,
+    problems: Vec<NotationProblem>,
+}
+
+/// Loads a notation file and checks every move and stored FEN against a replay.
+#[tauri::command]
+async fn load_notation_checked(path: String) -> Result<CheckedNotation, String> {
+    async_runtime::spawn_blocking(move || {
+        let notation = notation_formats::read_notation(&path)?;
+        let problems = notation.check();
+        Ok(CheckedNotation { notation, problems })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct RepairedNotation {
+    notation: GameNotation,
+    report: RepairReport,
+}
+
+#[tauri::command]
+async fn repair_notation(mut notation: GameNotation) -> Result<RepairedNotation, String> {
+    let report = notation.repair()?;
+    Ok(RepairedNotation { notation, report })
+}
+
+// Result of a variation edit: the updated game and the path of the affected node
+#[derive(Debug, Clone, serde::Serialize)]
+struct VariationEdit {
+    notation: GameNotation,
+    path: Vec<usize>,
+}
+
+#[tauri::command]
+async fn notation_to_tree(notation: GameNotation) -> Result<GameTree, String> {
+    Ok(GameTree::from_notation(&notation))
+}
+
+/// Plays a move after the node at `path`, creating a variation if the node already has a continuation.
+#[tauri::command]
+async fn variation_add_move(notation: GameNotation, path: Vec<usize>, uci: String) -> Result<VariationEdit, String> {
+    let mut tree = GameTree::from_notation(&notation);
+    let path = tree.add_move(&path, &uci)?;
+    Ok(VariationEdit { notation: tree.to_notation(), path })
+}
+
+#[tauri::command]
+async fn variation_promote(notation: GameNotation, path: Vec<usize>, to_mainline: bool) -> Result<VariationEdit, String> {
+    let mut tree = GameTree::from_notation(&notation);
+    let path = if to_mainline { tree.make_mainline(&path)? } else { tree.promote(&path)? };
+    Ok(VariationEdit { notation: tree.to_notation(), path })
+}
+
+#[tauri::command]
+async fn variation_delete(notation: GameNotation, path: Vec<usize>) -> Result<GameNotation, String> {
+    let mut tree = GameTree::from_notation(&notation);
+    tree.delete(&path)?;
+    Ok(tree.to_notation())
+}
+
+/// Converts notation JSON into another text format for saving.
+#[tauri::command]
+async fn export_notation(content: String, format: NotationFormat) -> Result<String, String> {
+    let notation = GameNotation::from_json(&content)?;
+    notation_formats::export_notation(&notation, format)
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+struct EngineAnalysis {
+    #[serde(flatten)]
+    result: SearchResult,
+    // Stopped by `engine_analyze_cancel` before reaching the limits
+    cancelled: bool,
+}
+
+// Ceiling for analysis limited only by depth or nodes, which the limits give no time for
+const ANALYZE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Analyzes `fen` after `moves` with a registered engine and returns once it answers with
+/// bestmove. The engine is stopped after `timeout_ms` (default: from the limits, or
+/// `ANALYZE_TIMEOUT` when they set no time), and an error follows if it does not answer.
+/// The engine is started on first use and kept running for later requests.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn engine_analyze(
+    app: AppHandle,
+    state: tauri::State<'_, AnalyzeEngines>,
+    engine_id: String,
+    fen: String,
+    moves: Option<Vec<String>>,
+    limits: SearchLimits,
+    timeout_ms: Option<u64>,
+) -> Result<EngineAnalysis, String> {
+    let registry = EngineRegistry::load(get_engine_registry_path(&app)?)?;
+    let spec = registry.get(&engine_id).ok_or_else(|| format!("Unknown engine '{}'", engine_id))?.to_spec();
+    let engine = state.engines.lock().unwrap().entry(engine_id).or_default().clone();
+    let timeout = timeout_ms.map(Duration::from_millis).or(limits.timeout()).unwrap_or(ANALYZE_TIMEOUT);
+    // Cleared here rather than once the engine is free, so a cancel sent while waiting for it still counts
+    engine.cancel.store(false, Ordering::SeqCst);
+    async_runtime::spawn_blocking(move || {
+        let mut slot = engine.client.lock().unwrap();
+        // An engine edited in the registry since it was started gets a fresh process
+        if slot.as_ref().is_some_and(|(running, _)| *running != spec) {
+            if let Some((_, client)) = slot.take() {
+                client.quit();
+            }
+        }
+        if slot.is_none() {
+            *slot = Some((spec.clone(), EngineClient::start(&spec, Duration::from_secs(10))?));
+        }
+        let (_, client) = slot.as_mut().unwrap();
+        match client.analyze(&fen, &moves.unwrap_or_default(), &limits, Some(timeout), &engine.cancel) {
+            Ok(result) => Ok(EngineAnalysis { result, cancelled: engine.cancel.load(Ordering::SeqCst) }),
+            Err(e) => {
+                // Restarted on the next request
+                *slot = None;
+                Err(e)
+            }
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Sends `stop` to a running `engine_analyze`, which then returns the result so far.
+#[tauri::command]
+async fn engine_analyze_cancel(state: tauri::State<'_, AnalyzeEngines>, engine_id: String) -> Result<(), String> {
+    if let Some(engine) = state.engines.lock().unwrap().get(&engine_id) {
+        engine.cancel.store(true, Ordering::SeqCst);
+    }
+    Ok(())
+}
+
+// Quits an engine kept running for analysis
+#[tauri::command]
+async fn engine_analyze_release(state: tauri::State<'_, AnalyzeEngines>, engine_id: String) -> Result<(), String> {
+    let Some(engine) = state.engines.lock().unwrap().remove(&engine_id) else { return Ok(()) };
+    engine.cancel.store(true, Ordering::SeqCst);
+    async_runtime::spawn_blocking(move || {
+        if let Some((_, client)) = engine.client.lock().unwrap().take() {
+            client.quit();
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Reviews a saved notation file with a separate engine instance and writes the annotated game back.
+#[tauri::command]
+async fn review_notation_file(
+    path: String,
+    engine: EngineSpec,
+    limits: SearchLimits,
+    app: AppHandle,
+) -> Result<ReviewReport, String> {
+    async_runtime::spawn_blocking(move || {
+        let mut notation = GameNotation::load(&path)?;
+        let mut client = EngineClient::start(&engine, Duration::from_secs(10))?;
+        client.new_game(Duration::from_secs(10))?;
+        let report = review::review_notation(&mut client, &mut notation, &limits, limits.timeout(), |done, total| {
+            let _ = app.emit("review-progress", serde_json::json!({ "done": done, "total": total }));
+        })?;
+        client.quit();
+        notation.save(&path)?;
+        Ok(report)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Returns a cached evaluation searched to at least `depth`, from `engine` or any engine.
+#[tauri::command]
+async fn analysis_cache_lookup(
+    fen: String,
+    engine: Option<String>,
+    depth: u32,
+    app: AppHandle,
+) -> Result<Option<CachedAnalysis>, String> {
+    let db_path = get_analysis_cache_path(&app)?;
+    let cache = AnalysisCache::new(db_path).map_err(|e| e.to_string())?;
+    cache.lookup(&fen, engine.as_deref(), depth).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn analysis_cache_store(fen: String, engine: String, info: InfoLine, app: AppHandle) -> Result<bool, String> {
+    let db_path = get_analysis_cache_path(&app)?;
+    let cache = AnalysisCache::new(db_path).map_err(|e| e.to_string())?;
+    cache.store_info(&fen, &engine, &info).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn analysis_cache_get_stats(app: AppHandle) -> Result<AnalysisCacheStats, String> {
+    let db_path = get_analysis_cache_path(&app)?;
+    let cache = AnalysisCache::new(db_path).map_err(|e| e.to_string())?;
+    cache.get_stats().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn analysis_cache_clear(app: AppHandle) -> Result<(), String> {
+    let db_path = get_analysis_cache_path(&app)?;
+    let cache = AnalysisCache::new(db_path).map_err(|e| e.to_string())?;
+    cache.clear_all().map_err(|e| e.to_string())
+}
+
+fn loaded_analysis_queue(app: &AppHandle, state: &AnalysisQueueState) -> Result<Arc<Mutex<AnalysisQueue>>, String> {
+    let mut slot = state.queue.lock().unwrap();
+    if let Some(queue) = slot.as_ref() {
+        return Ok(queue.clone());
+    }
+    let queue = Arc::new(Mutex::new(AnalysisQueue::load(get_analysis_queue_path(app)?)?));
+    *slot = Some(queue.clone());
+    Ok(queue)
+}
+
+#[tauri::command]
+async fn analysis_queue_add(
+    fens: Vec<String>,
+    limits: SearchLimits,
+    group: Option<String>,
+    app: AppHandle,
+    state: tauri::State<'_, AnalysisQueueState>,
+) -> Result<Vec<u64>, String> {
+    let queue = loaded_analysis_queue(&app, &state)?;
+    let mut queue = queue.lock().unwrap();
+    let ids = fens
+        .iter()
+        .map(|fen| queue.add(fen, limits.clone(), group.clone()))
+        .collect::<Result<Vec<_>, _>>()?;
+    queue.save(get_analysis_queue_path(&app)?)?;
+    Ok(ids)
+}
+
+#[tauri::command]
+async fn analysis_queue_status(
+    app: AppHandle,
+    state: tauri::State<'_, AnalysisQueueState>,
+) -> Result<Vec<AnalysisJob>, String> {
+    let queue = loaded_analysis_queue(&app, &state)?;
+    let jobs = queue.lock().unwrap().jobs.clone();
+    Ok(jobs)
+}
+
+#[tauri::command]
+async fn analysis_queue_clear(
+    finished_only: bool,
+    app: AppHandle,
+    state: tauri::State<'_, AnalysisQueueState>,
+) -> Result<(), String> {
+    if !finished_only && state.running.load(Ordering::SeqCst) {
+        return Err("Analysis queue is running".to_string());
+    }
+    let queue = loaded_analysis_queue(&app, &state)?;
+    let mut queue = queue.lock().unwrap();
+    if finished_only {
+        queue.clear_finished();
+    } else {
+        *queue = AnalysisQueue::default();
+    }
+    queue.save(get_analysis_queue_path(&app)?)
+}
+
+/// Runs the pending jobs on a pool of engine processes. Each finished job is emitted as
+/// `analysis-queue-job`; the call returns once the queue is drained or paused.
+#[tauri::command]
+async fn analysis_queue_start(
+    engine: EngineSpec,
+    config: QueueConfig,
+    retry_failed: bool,
+    app: AppHandle,
+    state: tauri::State<'_, AnalysisQueueState>,
+) -> Result<QueueSummary, String> {
+    let queue = loaded_analysis_queue(&app, &state)?;
+    if state.running.swap(true, Ordering::SeqCst) {
+        return Err("Analysis queue is already running".to_string());
+    }
+    if retry_failed {
+        queue.lock().unwrap().retry_failed();
+    }
+    state.pause.store(false, Ordering::SeqCst);
+    let pause = state.pause.clone();
+    let path = std::path::PathBuf::from(get_analysis_queue_path(&app)?);
+    let cache_path = get_analysis_cache_path(&app)?;
+
+    let result = async_runtime::spawn_blocking(move || {
+        analysis_queue::run_queue(queue, path, &engine, &config, pause, |job| {
+            if let Some(info) = job.result.as_ref().and_then(|r| r.info.as_ref()) {
+                if let Ok(cache) = AnalysisCache::new(&cache_path) {
+                    let _ = cache.store_info(&job.fen, &engine.name, info);
+                }
+            }
+            let _ = app.emit("analysis-queue-job", job);
+        })
+    })
+    .await
+    .map_err(|e| e.to_string());
+    state.running.store(false, Ordering::SeqCst);
+    result?
+}
+
+/// Lets running searches finish, then stops the pool. Pending jobs stay in the queue file.
+#[tauri::command]
+async fn analysis_queue_pause(state: tauri::State<'_, AnalysisQueueState>) -> Result<(), String> {
+    state.pause.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+#[tauri::command]
+async fn copy_to_clipboard(text: String, _app: AppHandle) -> Result<(), String> {
+    let mut ctx: ClipboardContext = ClipboardProvider::new()
+        .map_err(|e| format!("Failed to access clipboard: {}", e))?;
+    ctx.set_contents(text)
+        .map_err(|e| format!("Failed to copy to clipboard: {}", e))
+}
+
+#[tauri::command]
+async fn paste_from_clipboard(_app: AppHandle) -> Result<String, String> {
+    let mut ctx: ClipboardContext = ClipboardProvider::new()
+        .map_err(|e| format!("Failed to access clipboard: {}", e))?;
+    ctx.get_contents()
+        .map_err(|e| format!("Failed to paste from clipboard: {}", e))
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .manage(Arc::new(Mutex::new(None)) as EngineProcess)
+        .manage(Arc::new(Mutex::new(None)) as EngineIoLog)
+        .manage(Arc::new(Mutex::new(None)) as EngineWatch)
+        .manage(AnalysisQueueState::default())
+        .manage(BoardWatchState::default())
+        .manage(MouseState::default())
+        .manage(AutoPlayHandle::default())
+        .manage(AnalyzeEngines::default())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_shell::init())
+        .invoke_handler(tauri::generate_handler![
+            spawn_engine, 
+            kill_engine,
+            send_to_engine, 
+            list_engine_logs,
+            read_engine_log,
+            open_external_url,
+            save_game_notation,
+            save_chart_image,
+            load_config,
+            save_config,
+            clear_config,
+            settings_get,
+            settings_get_section,
+            settings_set_section,
+            settings_reset,
+            save_autosave,
+            load_autosave,
+            list_autosave_snapshots,
+            recover_autosave,
+            save_game_notation_with_dialog,
+            copy_to_clipboard,
+            paste_from_clipboard,
+            opening_book_add_entry,
+            opening_book_delete_entry,
+            opening_book_query_moves,
+            opening_book_get_stats,
+            opening_book_clear_all,
+            opening_book_export_all,
+            opening_book_import_entries,
+            opening_book_export_db,
+            opening_book_import_db,
+            engine_registry_list,
+            engine_analyze,
+            engine_analyze_cancel,
+            engine_analyze_release,
+            engine_registry_add,
+            engine_registry_update,
+            engine_registry_remove,
+            engine_registry_check,
+            discover_engine_options,
+            validate_engine_options,
+            review_notation_file,
+            game_db_import_files,
+            game_db_search,
+            game_db_get_game,
+            game_db_delete_game,
+            game_db_get_stats,
+            import_notation_file,
+            export_notation,
+            load_notation_checked,
+            repair_notation,
+            notation_to_tree,
+            variation_add_move,
+            variation_promote,
+            variation_delete,
+            analysis_queue_add,
+            analysis_queue_status,
+            analysis_queue_clear,
+            analysis_queue_start,
+            analysis_queue_pause,
+            analysis_cache_lookup,
+            analysis_cache_store,
+            analysis_cache_get_stats,
+            analysis_cache_clear,
+            // COMMANDS MỚI ĐÃ ĐƯỢC THÊM
+            capture_screen, 
+            capture_frame,
+            list_monitors,
+            list_piece_sets,
+            recognize_board_image,
+            recognize_screen,
+            board_watch_start,
+            board_watch_stop,
+            board_watch_set_position,
+            get_calibration,
+            save_calibration,
+            calibrate_board,
+            square_to_screen,
+            screen_to_square,
+            perform_mouse_move, 
+            perform_uci_move,
+            perform_verified_move,
+            cancel_mouse_action,
+            auto_play_start,
+            auto_play_pause,
+            auto_play_resume,
+            auto_play_stop,
+            // Android
+            #[cfg(target_os = "android")]
+            get_bundle_identifier,
+            #[cfg(target_os = "android")]
+            get_default_android_engine_path,
+            #[cfg(target_os = "android")]
+            check_android_file_permissions,
+            #[cfg(target_os = "android")]
+            scan_android_engines,
+            #[cfg(target_os = "android")]
+            request_saf_file_selection,
+            #[cfg(target_os = "android")]
+            handle_saf_file_result,
+            #[cfg(target_os = "android")]
+            handle_nnue_file_result
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
\ No newline at end of file
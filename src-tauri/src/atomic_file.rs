@@ -0,0 +1,21 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+// Writes to a temporary file, syncs it and renames it over `path`
+pub fn write_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let temp_path = path.with_file_name(format!(".{}.tmp", file_name));
+    {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+    }
+    fs::rename(&temp_path, path)?;
+    // Persist the rename itself; directories cannot be opened this way on Windows
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,453 @@
+use crate::board_recognition::{recognize, PieceSet, RecognitionConfig};
+use crate::board_watcher::{infer_move, BoardWatcher, FrameSource, WatchEvent, WatcherConfig};
+use crate::calibration::Calibration;
+use crate::engine::{PlayingEngine, Score, SearchLimits};
+use crate::input_automation::{AutomationConfig, InputAction, InputDevice};
+use crate::move_verification::{play_verified, MoveDesync, VerifyConfig, VerifyOutcome};
+use crate::ponder::{search_after, start_pondering, PonderConfig, PonderOutcome};
+use crate::position::{JieqiMove, Position, Side};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoPlayConfig {
+    pub limits: SearchLimits,
+    // Time between captures while waiting for the opponent
+    pub interval_ms: u64,
+    pub stable_frames: u32,
+    pub automation: AutomationConfig,
+    pub verify: VerifyConfig,
+    // Think on the opponent's time about the reply the engine expects
+    pub ponder: PonderConfig,
+}
+
+impl Default for AutoPlayConfig {
+    fn default() -> Self {
+        AutoPlayConfig {
+            limits: SearchLimits { movetime: Some(1000), ..Default::default() },
+            interval_ms: 300,
+            stable_frames: 2,
+            automation: AutomationConfig::default(),
+            verify: VerifyConfig::default(),
+            ponder: PonderConfig::default(),
+        }
+    }
+}
+
+/// Set from outside while the loop runs. A paused loop keeps following the board but does not
+/// move; stopping also cancels a mouse action in progress.
+#[derive(Debug, Default)]
+pub struct AutoPlayControl {
+    pub pause: AtomicBool,
+    pub stop: AtomicBool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutoPlayState {
+    // Following the board until it is our turn
+    Watching,
+    Thinking,
+    Moving,
+    Paused,
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AutoPlayEvent {
+    State { state: AutoPlayState, fen: Option<String> },
+    // A board that differs from the previous capture, before the stability check
+    Recognized { fen: String, confidence: f32 },
+    // What the board watcher saw, including the opponent's moves
+    Board { event: WatchEvent },
+    EngineMove {
+        fen: String,
+        uci: String,
+        score: Option<Score>,
+        depth: u32,
+        pv: Vec<String>,
+        time_ms: u64,
+        ponder: PonderOutcome,
+    },
+    // Our move as it showed on the board, with the revealed piece if any
+    Played { uci: String, fen: String, attempts: u32, actions: Vec<InputAction> },
+    Desync(MoveDesync),
+    Finished { result: String, fen: String },
+}
+
+struct Emitter<F: FnMut(AutoPlayEvent)> {
+    on_event: F,
+    state: Option<AutoPlayState>,
+}
+
+impl<F: FnMut(AutoPlayEvent)> Emitter<F> {
+    fn emit(&mut self, event: AutoPlayEvent) {
+        (self.on_event)(event);
+    }
+
+    // Only actual changes are emitted
+    fn state(&mut self, state: AutoPlayState, position: Option<&Position>) {
+        if self.state != Some(state) {
+            self.state = Some(state);
+            self.emit(AutoPlayEvent::State { state, fen: position.map(|p| p.to_fen()) });
+        }
+    }
+}
+
+/// Plays `our_side` on the calibrated board: follows the board until it is our turn, asks the
+/// engine for a move, plays it with the mouse and checks it showed up. Runs until stopped, the
+/// game ends or the frames run out. Without a start position the first stable board is used,
+/// with `recognition.side_to_move` to move. A desync pauses the loop. Returns the last position.
+#[allow(clippy::too_many_arguments)]
+pub fn run_auto_play(
+    engine: &mut dyn PlayingEngine,
+    device: &mut dyn InputDevice,
+    source: &mut dyn FrameSource,
+    pieces: &PieceSet,
+    calibration: &Calibration,
+    recognition: &RecognitionConfig,
+    our_side: Side,
+    start: Option<Position>,
+    config: &AutoPlayConfig,
+    control: &AutoPlayControl,
+    on_event: impl FnMut(AutoPlayEvent),
+) -> Result<Option<Position>, String> {
+    let interval = Duration::from_millis(config.interval_ms);
+    let watcher_config = WatcherConfig { interval, stable_frames: config.stable_frames.max(1), ..Default::default() };
+    let mut watcher = BoardWatcher::new(start, watcher_config);
+    let mut events = Emitter { on_event, state: None };
+    let mut last_seen = String::new();
+    // The opponent's last move, to tell a ponderhit from a miss
+    let mut last_move = None;
+
+    while !control.stop.load(Ordering::SeqCst) {
+        let started = Instant::now();
+        let paused = control.pause.load(Ordering::SeqCst);
+        events.state(if paused { AutoPlayState::Paused } else { AutoPlayState::Watching }, watcher.position());
+        if paused {
+            engine.stop_ponder()?;
+        }
+
+        let Some(frame) = source.next_frame()? else { break };
+        // Unreadable frames are skipped like in the board watcher
+        if let Ok(seen) = recognize(&frame, pieces, recognition) {
+            let board = seen.fen.split_whitespace().next().unwrap_or_default();
+            if board != last_seen {
+                last_seen = board.to_string();
+                events.emit(AutoPlayEvent::Recognized { fen: seen.fen.clone(), confidence: seen.confidence });
+            }
+            if let Some(event) = watcher.observe(&seen) {
+                last_move = match &event {
+                    WatchEvent::Move { uci, .. } => JieqiMove::parse(uci, our_side.opponent()).ok(),
+                    _ => None,
+                };
+                events.emit(AutoPlayEvent::Board { event });
+            }
+            if let Some(position) = watcher.position().cloned() {
+                if let Some(result) = position.game_result() {
+                    events.emit(AutoPlayEvent::Finished { result: result.to_string(), fen: position.to_fen() });
+                    break;
+                }
+                let settled = last_seen == position.board_fen();
+                if !paused && settled && position.side_to_move == our_side {
+                    let board = (pieces, calibration, recognition);
+                    let turn = (&position, last_move.take());
+                    match play_turn(engine, device, source, board, turn, config, control, &mut events) {
+                        Ok(Some(next)) => watcher.set_position(next),
+                        Ok(None) => control.pause.store(true, Ordering::SeqCst),
+                        // Stopping cancels the mouse action in progress, which is not an error
+                        Err(_) if control.stop.load(Ordering::SeqCst) => break,
+                        Err(e) => return Err(e),
+                    }
+                    continue;
+                }
+            }
+        }
+
+        while !control.stop.load(Ordering::SeqCst) && started.elapsed() < interval {
+            thread::sleep(interval.saturating_sub(started.elapsed()).min(Duration::from_millis(50)));
+        }
+    }
+    engine.stop_ponder()?;
+    events.state(AutoPlayState::Stopped, watcher.position());
+    Ok(watcher.position().cloned())
+}
+
+// One move of ours, after the opponent's `last_move` if it was seen. Returns the position after
+// it, or None after a desync.
+#[allow(clippy::too_many_arguments)]
+fn play_turn<F: FnMut(AutoPlayEvent)>(
+    engine: &mut dyn PlayingEngine,
+    device: &mut dyn InputDevice,
+    source: &mut dyn FrameSource,
+    (pieces, calibration, recognition): (&PieceSet, &Calibration, &RecognitionConfig),
+    (position, last_move): (&Position, Option<JieqiMove>),
+    config: &AutoPlayConfig,
+    control: &AutoPlayControl,
+    events: &mut Emitter<F>,
+) -> Result<Option<Position>, String> {
+    events.state(AutoPlayState::Thinking, Some(position));
+    let fen = position.to_fen();
+    let (search, ponder) = search_after(engine, position, last_move.as_ref(), &config.limits)?;
+    let mv = JieqiMove::parse(&search.best_move, position.side_to_move)
+        .ok()
+        .filter(|mv| position.is_legal(mv.from, mv.to))
+        .ok_or_else(|| format!("Engine returned an illegal move '{}' in {}", search.best_move, fen))?;
+    let info = search.info.clone().unwrap_or_default();
+    events.emit(AutoPlayEvent::EngineMove {
+        fen: fen.clone(),
+        uci: mv.base_uci(),
+        score: info.score,
+        depth: info.depth,
+        pv: info.pv,
+        time_ms: search.elapsed_ms,
+        ponder,
+    });
+
+    events.state(AutoPlayState::Moving, Some(position));
+    let points = calibration.move_to_screen(&mv.base_uci())?;
+    let outcome = play_verified(
+        device,
+        source,
+        pieces,
+        recognition,
+        position,
+        &mv,
+        points,
+        &config.automation,
+        &config.verify,
+        &control.stop,
+    )?;
+    let verified = match outcome {
+        VerifyOutcome::Verified(verified) => verified,
+        VerifyOutcome::Desync(desync) => {
+            events.emit(AutoPlayEvent::Desync(desync));
+            return Ok(None);
+        }
+    };
+
+    // The capture also shows which piece a dark piece turned out to be
+    let board = Position::from_fen(&verified.fen)?.board;
+    let played = infer_move(position, &board).filter(|played| played.from == mv.from && played.to == mv.to);
+    let Some(played) = played else {
+        events.emit(AutoPlayEvent::Desync(MoveDesync {
+            uci: mv.base_uci(),
+            attempts: verified.attempts,
+            before: fen,
+            after: verified.fen,
+            reason: "The board does not match the move".to_string(),
+        }));
+        return Ok(None);
+    };
+    let mut next = position.clone();
+    next.apply(&played)?;
+    start_pondering(engine, &next, &search, &config.limits, &config.ponder)?;
+    events.emit(AutoPlayEvent::Played {
+        uci: played.to_string(),
+        fen: next.to_fen(),
+        attempts: verified.attempts,
+        actions: verified.actions,
+    });
+    Ok(Some(next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_recognition::BoardOrientation;
+    use crate::board_watcher::RecordedFrames;
+    use crate::engine::SearchResult;
+    use crate::input_automation::RecordingDevice;
+    use crate::test_boards::{piece_set, render, GRID};
+    use std::collections::VecDeque;
+
+    // Answers searches with the given moves in turn and keeps the calls it got
+    #[derive(Default)]
+    struct ScriptedEngine {
+        moves: VecDeque<(&'static str, Option<&'static str>)>,
+        pondering: Option<String>,
+        calls: Vec<String>,
+    }
+
+    impl ScriptedEngine {
+        fn next_result(&mut self) -> Result<SearchResult, String> {
+            let (best_move, ponder) = self.moves.pop_front().ok_or("No scripted move left")?;
+            let ponder = ponder.map(str::to_string);
+            Ok(SearchResult { best_move: best_move.to_string(), ponder, ..Default::default() })
+        }
+    }
+
+    impl PlayingEngine for ScriptedEngine {
+        fn search(&mut self, fen: &str, _: &SearchLimits, _: Option<Duration>) -> Result<SearchResult, String> {
+            self.calls.push(format!("search {}", fen));
+            self.next_result()
+        }
+
+        fn start_ponder(&mut self, _: &str, ponder_move: &str, _: &SearchLimits) -> Result<(), String> {
+            self.calls.push(format!("ponder {}", ponder_move));
+            self.pondering = Some(ponder_move.to_string());
+            Ok(())
+        }
+
+        fn pondering(&self) -> Option<&str> {
+            self.pondering.as_deref()
+        }
+
+        fn ponder_hit(&mut self, _: Option<Duration>) -> Result<SearchResult, String> {
+            self.calls.push("ponderhit".to_string());
+            self.pondering = None;
+            self.next_result()
+        }
+
+        fn stop_ponder(&mut self) -> Result<(), String> {
+            if self.pondering.take().is_some() {
+                self.calls.push("stop".to_string());
+            }
+            Ok(())
+        }
+    }
+
+    fn after(position: &Position, uci: &str) -> Position {
+        let mut next = position.clone();
+        next.apply(&JieqiMove::parse(uci, position.side_to_move).unwrap()).unwrap();
+        next
+    }
+
+    fn frame(position: &Position) -> image::RgbaImage {
+        render(&position.to_fen(), BoardOrientation::RedBottom)
+    }
+
+    fn setup() -> (Calibration, RecognitionConfig, AutoPlayConfig) {
+        let calibration = Calibration {
+            monitor: 0,
+            monitor_x: 0,
+            monitor_y: 0,
+            scale_factor: 1.0,
+            board_rect: GRID,
+            orientation: BoardOrientation::RedBottom,
+        };
+        let recognition = RecognitionConfig { board_rect: Some(GRID), ..Default::default() };
+        let mut config = AutoPlayConfig { interval_ms: 1, stable_frames: 1, ..Default::default() };
+        config.automation = AutomationConfig { press_delay_ms: 0, hold_ms: 0, settle_ms: 0, ..Default::default() };
+        config.verify = VerifyConfig { attempts: 1, checks: 1, check_interval_ms: 0 };
+        config.ponder = PonderConfig { enabled: true, dark_moves: true };
+        (calibration, recognition, config)
+    }
+
+    #[test]
+    fn plays_turns_from_recorded_frames() {
+        let start = Position::startpos();
+        let first = after(&start, "h2e2C");
+        let reply = after(&first, "h9g7n");
+        let second = after(&reply, "h0g2N");
+        // Each turn captures the board before and after the mouse move
+        let frames = [&start, &start, &first, &first, &reply, &reply, &second].map(frame).to_vec();
+
+        let (calibration, recognition, config) = setup();
+        let moves = [("h2e2", Some("h9g7n")), ("h0g2", None)].into();
+        let mut engine = ScriptedEngine { moves, ..Default::default() };
+        let mut device = RecordingDevice::default();
+        let mut events = Vec::new();
+        let last = run_auto_play(
+            &mut engine,
+            &mut device,
+            &mut RecordedFrames::new(frames),
+            &piece_set(),
+            &calibration,
+            &recognition,
+            Side::Red,
+            Some(start.clone()),
+            &config,
+            &AutoPlayControl::default(),
+            |event| events.push(event),
+        )
+        .unwrap();
+        assert_eq!(last, Some(second.clone()));
+
+        // The reply was predicted, so the second move came from the ponder search
+        let search = format!("search {}", start.to_fen());
+        assert_eq!(engine.calls, [search.as_str(), "ponder h9g7n", "ponderhit"]);
+
+        let played: Vec<(String, String)> = events
+            .iter()
+            .filter_map(|e| match e {
+                AutoPlayEvent::Played { uci, fen, .. } => Some((uci.clone(), fen.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(played, [("h2e2C".to_string(), first.to_fen()), ("h0g2N".to_string(), second.to_fen())]);
+        let ponder: Vec<PonderOutcome> = events
+            .iter()
+            .filter_map(|e| if let AutoPlayEvent::EngineMove { ponder, .. } = e { Some(*ponder) } else { None })
+            .collect();
+        assert_eq!(ponder, [PonderOutcome::None, PonderOutcome::Hit]);
+        assert!(events.iter().any(|e| matches!(
+            e,
+            AutoPlayEvent::Board { event: WatchEvent::Move { uci, side, .. } } if uci == "h9g7n" && side == "black"
+        )));
+        assert!(!events.iter().any(|e| matches!(e, AutoPlayEvent::Desync(_))));
+        assert!(matches!(events.last(), Some(AutoPlayEvent::State { state: AutoPlayState::Stopped, .. })));
+
+        // Two drags from square to square
+        let to_screen = |uci: &str| calibration.move_to_screen(uci).unwrap();
+        let (h2, e2) = to_screen("h2e2");
+        let (h0, g2) = to_screen("h0g2");
+        let actions: Vec<InputAction> = device.actions.iter().map(|(_, a)| *a).collect();
+        use InputAction::{MoveTo, Press, Release};
+        assert_eq!(actions, [MoveTo(h2), Press, MoveTo(e2), Release, MoveTo(h0), Press, MoveTo(g2), Release]);
+    }
+
+    // Stops the loop as soon as the button is pressed
+    struct StoppingDevice<'a> {
+        inner: RecordingDevice,
+        control: &'a AutoPlayControl,
+    }
+
+    impl InputDevice for StoppingDevice<'_> {
+        fn move_to(&mut self, point: crate::calibration::ScreenPoint) -> Result<(), String> {
+            self.inner.move_to(point)
+        }
+
+        fn press(&mut self) -> Result<(), String> {
+            self.control.stop.store(true, Ordering::SeqCst);
+            self.inner.press()
+        }
+
+        fn release(&mut self) -> Result<(), String> {
+            self.inner.release()
+        }
+    }
+
+    #[test]
+    fn stopping_during_a_move_is_a_clean_stop() {
+        let start = Position::startpos();
+        let (calibration, recognition, mut config) = setup();
+        config.automation.path_steps = 3;
+        let control = AutoPlayControl::default();
+        let mut engine = ScriptedEngine { moves: [("h2e2", None)].into(), ..Default::default() };
+        let mut device = StoppingDevice { inner: RecordingDevice::default(), control: &control };
+        let mut events = Vec::new();
+        let last = run_auto_play(
+            &mut engine,
+            &mut device,
+            &mut RecordedFrames::new(vec![frame(&start), frame(&start)]),
+            &piece_set(),
+            &calibration,
+            &recognition,
+            Side::Red,
+            Some(start.clone()),
+            &config,
+            &control,
+            |event| events.push(event),
+        );
+        assert_eq!(last, Ok(Some(start)));
+        let actions: Vec<InputAction> = device.inner.actions.iter().map(|(_, a)| *a).collect();
+        assert_eq!(actions.last(), Some(&InputAction::Release));
+        assert!(!events.iter().any(|e| matches!(e, AutoPlayEvent::Played { .. })));
+        assert!(matches!(events.last(), Some(AutoPlayEvent::State { state: AutoPlayState::Stopped, .. })));
+    }
+}
@@ -0,0 +1,222 @@
+use crate::atomic_file::write_atomic;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_SNAPSHOTS: usize = 10;
+
+const SNAPSHOT_PREFIX: &str = "Autosave-";
+
+// Stored form of an autosave; `content` is the notation JSON from the frontend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Envelope {
+    saved_at: String,
+    sha256: String,
+    content: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub file_name: String,
+    pub saved_at: Option<String>,
+    pub size: u64,
+    // False when the file is unreadable or its checksum does not match
+    pub valid: bool,
+}
+
+fn checksum(content: &str) -> String {
+    hex::encode(Sha256::digest(content.as_bytes()))
+}
+
+// Files written before checksums were added hold the bare content and are accepted as-is
+fn decode(raw: &str) -> Result<(String, Option<String>), String> {
+    match serde_json::from_str::<Envelope>(raw) {
+        Ok(envelope) if checksum(&envelope.content) == envelope.sha256 => Ok((envelope.content, Some(envelope.saved_at))),
+        Ok(_) => Err("checksum mismatch".to_string()),
+        Err(_) if serde_json::from_str::<serde_json::Value>(raw).is_ok() => Ok((raw.to_string(), None)),
+        Err(e) => Err(format!("unreadable ({})", e)),
+    }
+}
+
+/// The current autosave plus a rotating set of timestamped snapshots in `snapshot_dir`.
+/// Every write goes through a temp file, fsync and rename.
+pub struct AutosaveStore {
+    path: PathBuf,
+    snapshot_dir: PathBuf,
+    keep: usize,
+}
+
+impl AutosaveStore {
+    pub fn new<P: AsRef<Path>>(path: P, keep: usize) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let snapshot_dir = path.with_file_name("autosave_snapshots");
+        AutosaveStore { path, snapshot_dir, keep: keep.max(1) }
+    }
+
+    // The frontend saves on a timer; unchanged games are not written again
+    pub fn save(&self, content: &str) -> Result<(), String> {
+        if self.load().map(|current| current == content).unwrap_or(false) {
+            return Ok(());
+        }
+        let now = chrono::Utc::now();
+        let envelope = Envelope { saved_at: now.to_rfc3339(), sha256: checksum(content), content: content.to_string() };
+        let data = serde_json::to_string(&envelope).map_err(|e| e.to_string())?;
+
+        fs::create_dir_all(&self.snapshot_dir).map_err(|e| format!("Failed to create autosave directory: {}", e))?;
+        let stamp = now.format("%Y%m%dT%H%M%S%3fZ").to_string();
+        let mut snapshot = self.snapshot_dir.join(format!("{}{}.json", SNAPSHOT_PREFIX, stamp));
+        let mut counter = 1;
+        while snapshot.exists() {
+            counter += 1;
+            snapshot = self.snapshot_dir.join(format!("{}{}_{}.json", SNAPSHOT_PREFIX, stamp, counter));
+        }
+        write_atomic(&snapshot, data.as_bytes()).map_err(|e| format!("Failed to write autosave snapshot: {}", e))?;
+        write_atomic(&self.path, data.as_bytes()).map_err(|e| format!("Failed to write autosave file: {}", e))?;
+
+        // Rotation is best effort; a leftover snapshot is harmless
+        for old in self.snapshot_files().into_iter().skip(self.keep) {
+            let _ = fs::remove_file(old);
+        }
+        Ok(())
+    }
+
+    /// Returns the newest autosave, or an empty string if there is none. A corrupt file is an
+    /// error so the caller can offer the snapshots instead.
+    pub fn load(&self) -> Result<String, String> {
+        if !self.path.exists() {
+            return Ok(String::new());
+        }
+        let raw = fs::read_to_string(&self.path).map_err(|e| format!("Failed to read autosave file: {}", e))?;
+        decode(&raw)
+            .map(|(content, _)| content)
+            .map_err(|e| format!("Autosave file is corrupt: {}", e))
+    }
+
+    // Snapshot paths, newest first (names sort by timestamp)
+    fn snapshot_files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.snapshot_dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| {
+                        let name = p.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+                        name.starts_with(SNAPSHOT_PREFIX) && name.ends_with(".json")
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        files.sort();
+        files.reverse();
+        files
+    }
+
+    pub fn list_snapshots(&self) -> Vec<SnapshotInfo> {
+        self.snapshot_files()
+            .into_iter()
+            .map(|path| {
+                let decoded = fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|raw| decode(&raw));
+                SnapshotInfo {
+                    file_name: path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+                    saved_at: decoded.as_ref().ok().and_then(|(_, saved_at)| saved_at.clone()),
+                    size: fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+                    valid: decoded.is_ok(),
+                }
+            })
+            .collect()
+    }
+
+    /// Restores a snapshot as the current autosave and returns its content.
+    pub fn recover(&self, file_name: &str) -> Result<String, String> {
+        if file_name.contains(['/', '\\']) || file_name.contains("..") || !file_name.starts_with(SNAPSHOT_PREFIX) {
+            return Err(format!("Invalid snapshot name: {}", file_name));
+        }
+        let snapshot = self.snapshot_dir.join(file_name);
+        let raw = fs::read_to_string(&snapshot).map_err(|e| format!("Failed to read snapshot: {}", e))?;
+        let (content, _) = decode(&raw).map_err(|e| format!("Snapshot {} is corrupt: {}", file_name, e))?;
+        write_atomic(&self.path, raw.as_bytes()).map_err(|e| format!("Failed to restore autosave: {}", e))?;
+        Ok(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(name: &str, keep: usize) -> AutosaveStore {
+        let dir = std::env::temp_dir().join(format!("jieqibox-autosave-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        AutosaveStore::new(dir.join("Autosave.json"), keep)
+    }
+
+    fn game(moves: usize) -> String {
+        format!(r#"{{"moves":{}}}"#, moves)
+    }
+
+    #[test]
+    fn corrupt_files_are_reported() {
+        let store = store("corrupt", 5);
+        store.save(&game(1)).unwrap();
+        store.save(&game(2)).unwrap();
+        assert_eq!(store.load().unwrap(), game(2));
+
+        let tampered = fs::read_to_string(&store.path).unwrap().replace(r#"\"moves\":2"#, r#"\"moves\":3"#);
+        fs::write(&store.path, tampered).unwrap();
+        assert!(store.load().unwrap_err().contains("checksum"));
+        let newest = store.snapshot_dir.join(&store.list_snapshots()[0].file_name);
+        fs::write(&newest, "{ truncated").unwrap();
+
+        let snapshots = store.list_snapshots();
+        let valid: Vec<_> = snapshots.iter().map(|s| s.valid).collect();
+        assert_eq!(valid, [false, true]);
+        assert!(snapshots[1].saved_at.is_some());
+        assert!(store.recover(&snapshots[0].file_name).is_err());
+        assert_eq!(store.recover(&snapshots[1].file_name).unwrap(), game(1));
+        assert_eq!(store.load().unwrap(), game(1));
+    }
+
+    #[test]
+    fn snapshots_are_rotated() {
+        let store = store("rotate", 3);
+        for moves in 1..=5 {
+            store.save(&game(moves)).unwrap();
+        }
+        let snapshots = store.list_snapshots();
+        assert_eq!(snapshots.len(), 3);
+        // The newest are kept
+        assert_eq!(store.recover(&snapshots[0].file_name).unwrap(), game(5));
+        assert_eq!(store.recover(&snapshots[2].file_name).unwrap(), game(3));
+    }
+
+    #[test]
+    fn unchanged_content_is_not_written_again() {
+        let store = store("unchanged", 5);
+        assert_eq!(store.load().unwrap(), "");
+        store.save(&game(1)).unwrap();
+        let written = fs::read_to_string(&store.path).unwrap();
+        store.save(&game(1)).unwrap();
+        assert_eq!(store.list_snapshots().len(), 1);
+        assert_eq!(fs::read_to_string(&store.path).unwrap(), written);
+    }
+
+    #[test]
+    fn recover_stays_in_the_snapshot_directory() {
+        let store = store("names", 5);
+        store.save(&game(1)).unwrap();
+        let names = ["../Autosave.json", "Autosave-../../secret.json", "Autosave-..", "Autosave-a\\b.json", "other.json"];
+        for name in names {
+            assert!(store.recover(name).unwrap_err().starts_with("Invalid snapshot name"), "{}", name);
+        }
+    }
+
+    #[test]
+    fn legacy_autosaves_are_accepted() {
+        let store = store("legacy", 5);
+        fs::write(&store.path, game(7)).unwrap();
+        assert_eq!(store.load().unwrap(), game(7));
+        fs::write(&store.path, "not json").unwrap();
+        assert!(store.load().is_err());
+    }
+}
@@ -0,0 +1,756 @@
+// Headless entry point: engine matches, opening books, batch analysis and FEN checks
+// without a display or webview. Shares all logic with the GUI through jieqibox_lib, and builds
+// with `--no-default-features` to leave out the GUI dependencies.
+use jieqibox_lib::analysis::analyze_notation;
+use jieqibox_lib::analysis_cache::AnalysisCache;
+use jieqibox_lib::analysis_queue::{run_queue, AnalysisQueue, JobStatus, QueueConfig};
+use jieqibox_lib::board_recognition::{recognize, PieceSet, RecognitionConfig};
+use jieqibox_lib::board_watcher::{run_watcher, BoardWatcher, RecordedFrames, WatchEvent, WatcherConfig};
+use jieqibox_lib::engine::{EngineClient, EngineEncoding, EngineSpec, SearchLimits};
+use jieqibox_lib::engine_match::{run_match, MatchConfig, TimeControl};
+use jieqibox_lib::engine_options::{discover_options, validate_option_values, EngineProtocol};
+use jieqibox_lib::engine_registry::{EngineRegistry, NewEngine};
+use jieqibox_lib::engine_transport::{EngineTransport, ReconnectConfig};
+use jieqibox_lib::game_db::{GameDatabase, GameQuery};
+use jieqibox_lib::notation::GameNotation;
+use jieqibox_lib::notation_formats::{export_notation, read_games, read_notation, NotationFormat};
+use jieqibox_lib::opening_book::{import_game, JieqiOpeningBook};
+use jieqibox_lib::ponder::PonderConfig;
+use jieqibox_lib::position::{Position, Side};
+use jieqibox_lib::review::review_notation;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+const USAGE: &str = "Usage: jieqibox-cli <command> [options]
+
+Commands:
+  match --engine1 PATH --engine2 PATH [--games N] [--fen FEN] [--max-plies N]
+        [--seed N] [--out DIR] [--tc BASE_MS+INC_MS] [--ponder true [--ponder-dark true]]
+        [LIMITS]
+  book build --db PATH [--max-plies N] FILE...
+  book merge --db PATH OTHER_DB...
+  book stats --db PATH
+  analyze --engine PATH [--out DIR] [LIMITS] FILE...
+  review --engine PATH [--out DIR] [LIMITS] FILE...   (writes in place without --out)
+  queue add --file QUEUE [--group NAME] [LIMITS] FEN...
+  queue add-games --file QUEUE [LIMITS] NOTATION...
+  queue run --file QUEUE --engine PATH [--workers N] [--clear-hash true|false]
+            [--cache DB]
+  queue status --file QUEUE
+  options --engine PATH   (checks --engine-option values if given)
+  engines list|check --registry PATH
+  engines add --registry PATH --name NAME --path EXE [--args \"ARGS\"] [--protocol uci|jai]
+              [--nnue FILE] [--encoding auto|utf8|gbk] [--option NAME=VALUE]
+              [--tcp HOST:PORT | --wrap \"COMMAND\"]
+  engines remove --registry PATH ID
+  games import --db PATH FILE...   (JSON, XQF or PGN)
+  games search --db PATH [--player NAME] [--red NAME] [--black NAME] [--event TEXT]
+               [--from DATE] [--to DATE] [--result R] [--fen FEN [--move UCI]] [--page N]
+  games export --db PATH --out DIR [--format json|pgn|wxf|chinese] ID...
+  convert --to json|pgn|wxf|chinese|chinese_traditional [--out DIR] FILE...
+  check-notation [--repair true] [--out DIR] FILE...   (repairs in place without --out)
+  validate-fen [--file PATH] [FEN...]
+  recognize --pieces DIR [--rect X,Y,W,H] [--orientation red_bottom|red_top] [--side w|b]
+            [--min-score N] IMAGE...
+  watch --pieces DIR [--fen FEN] [--stable N] [RECOGNIZE OPTIONS] FRAME_DIR
+
+Engine options:
+  --registry PATH             lets --engineN name an entry of an engine registry
+  --engineN-args \"ARGS\"       arguments passed to engine N (also --engine-args)
+  --engineN-option NAME=VALUE  setoption sent after the handshake (repeatable)
+  --engineN-protocol uci|jai  handshake spoken by engine N (default uci)
+  --engineN-tcp HOST:PORT     talks to an engine served over TCP; --engineN is then a label
+  --engineN-wrap \"COMMAND\"    runs the engine through COMMAND, e.g. \"ssh host\"
+
+Limits:
+  --depth N | --movetime MS | --nodes N";
+
+// Flags taking a value; everything else not starting with `--` is positional
+struct Args {
+    values: HashMap<String, Vec<String>>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    fn parse(raw: &[String]) -> Result<Self, String> {
+        let mut values: HashMap<String, Vec<String>> = HashMap::new();
+        let mut positional = Vec::new();
+        let mut iter = raw.iter();
+        while let Some(arg) = iter.next() {
+            if let Some(flag) = arg.strip_prefix("--") {
+                let value = iter.next().ok_or_else(|| format!("Missing value for --{}", flag))?;
+                values.entry(flag.to_string()).or_default().push(value.clone());
+            } else {
+                positional.push(arg.clone());
+            }
+        }
+        Ok(Args { values, positional })
+    }
+
+    fn get(&self, flag: &str) -> Option<&str> {
+        self.values.get(flag).and_then(|v| v.last()).map(|s| s.as_str())
+    }
+
+    fn all(&self, flag: &str) -> Vec<String> {
+        self.values.get(flag).cloned().unwrap_or_default()
+    }
+
+    fn require(&self, flag: &str) -> Result<&str, String> {
+        self.get(flag).ok_or_else(|| format!("--{} is required", flag))
+    }
+
+    fn number<T: std::str::FromStr>(&self, flag: &str) -> Result<Option<T>, String> {
+        self.get(flag)
+            .map(|v| v.parse::<T>().map_err(|_| format!("Invalid value for --{}: {}", flag, v)))
+            .transpose()
+    }
+
+    // Enum flags use the same lowercase names as the JSON files
+    fn choice<T: serde::de::DeserializeOwned>(&self, flag: &str) -> Result<Option<T>, String> {
+        self.get(flag)
+            .map(|v| {
+                serde_json::from_value(serde_json::Value::String(v.to_string()))
+                    .map_err(|_| format!("Invalid value for --{}: {}", flag, v))
+            })
+            .transpose()
+    }
+
+    fn limits(&self) -> Result<SearchLimits, String> {
+        let limits = SearchLimits {
+            depth: self.number("depth")?,
+            movetime: self.number("movetime")?,
+            nodes: self.number("nodes")?,
+            ..Default::default()
+        };
+        if limits.depth.is_none() && limits.movetime.is_none() && limits.nodes.is_none() {
+            return Ok(SearchLimits { movetime: Some(1000), ..Default::default() });
+        }
+        Ok(limits)
+    }
+
+    // `--engine` may name a registry entry (by ID or name) when `--registry` is given
+    fn engine_spec(&self, prefix: &str) -> Result<EngineSpec, String> {
+        let engine = self.require(prefix)?.to_string();
+        let registered = match self.get("registry") {
+            Some(registry) => EngineRegistry::load(registry)?.find(&engine).map(|e| e.to_spec()),
+            None => None,
+        };
+        let mut spec = registered.unwrap_or_else(|| EngineSpec {
+            name: Path::new(&engine)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| engine.clone()),
+            path: engine.clone(),
+            args: Vec::new(),
+            options: Vec::new(),
+            protocol: EngineProtocol::Uci,
+            encoding: EngineEncoding::Auto,
+            transport: EngineTransport::Local,
+            reconnect: ReconnectConfig::default(),
+        });
+        if let Some(args) = self.get(&format!("{}-args", prefix)) {
+            spec.args = args.split_whitespace().map(|s| s.to_string()).collect();
+        }
+        if let Some(protocol) = self.choice(&format!("{}-protocol", prefix))? {
+            spec.protocol = protocol;
+        }
+        if let Some(transport) = self.transport(&format!("{}-tcp", prefix), &format!("{}-wrap", prefix))? {
+            spec.transport = transport;
+        }
+        spec.options.extend(self.options(&format!("{}-option", prefix))?);
+        Ok(spec)
+    }
+
+    fn transport(&self, tcp_flag: &str, wrap_flag: &str) -> Result<Option<EngineTransport>, String> {
+        match (self.get(tcp_flag), self.get(wrap_flag)) {
+            (Some(_), Some(_)) => Err(format!("--{} and --{} cannot be combined", tcp_flag, wrap_flag)),
+            (Some(address), None) => Ok(Some(EngineTransport::Tcp { address: address.to_string() })),
+            (None, Some(wrapper)) => {
+                let mut words = wrapper.split_whitespace().map(|s| s.to_string());
+                let program = words.next().ok_or_else(|| format!("--{} must not be empty", wrap_flag))?;
+                Ok(Some(EngineTransport::Command { program, args: words.collect() }))
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    fn options(&self, flag: &str) -> Result<Vec<(String, String)>, String> {
+        self.all(flag)
+            .iter()
+            .map(|option| {
+                option
+                    .split_once('=')
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .ok_or_else(|| format!("Engine option must be NAME=VALUE: {}", option))
+            })
+            .collect()
+    }
+}
+
+fn output_path(out_dir: Option<&str>, file_name: &str) -> Result<Option<PathBuf>, String> {
+    let Some(dir) = out_dir else { return Ok(None) };
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir, e))?;
+    Ok(Some(Path::new(dir).join(file_name)))
+}
+
+// "60000+1000": base time and increment in milliseconds
+fn parse_time_control(value: &str) -> Result<TimeControl, String> {
+    let (base, increment) = value.split_once('+').unwrap_or((value, "0"));
+    match (base.parse(), increment.parse()) {
+        (Ok(base_ms), Ok(increment_ms)) => Ok(TimeControl { base_ms, increment_ms }),
+        _ => Err(format!("Invalid value for --tc: {}", value)),
+    }
+}
+
+fn cmd_match(args: &Args) -> Result<(), String> {
+    let first = args.engine_spec("engine1")?;
+    let second = args.engine_spec("engine2")?;
+    let defaults = MatchConfig::default();
+    let time_control = args.get("tc").map(parse_time_control).transpose()?;
+    // With a clock the engines manage their own time unless a limit is given
+    let limits = match ["depth", "movetime", "nodes"].iter().any(|flag| args.get(flag).is_some()) {
+        false if time_control.is_some() => SearchLimits::default(),
+        _ => args.limits()?,
+    };
+    let config = MatchConfig {
+        games: args.number("games")?.unwrap_or(defaults.games),
+        start_fen: args.get("fen").map(|s| s.to_string()).unwrap_or(defaults.start_fen),
+        limits,
+        max_plies: args.number("max-plies")?.unwrap_or(defaults.max_plies),
+        seed: args.number("seed")?,
+        time_control,
+        ponder: PonderConfig {
+            enabled: args.number("ponder")?.unwrap_or(false),
+            dark_moves: args.number("ponder-dark")?.unwrap_or(false),
+        },
+    };
+    Position::from_fen(&config.start_fen)?;
+    let out_dir = args.get("out");
+
+    let score = run_match([&first, &second], &config, |index, game, score| {
+        println!(
+            "Game {}: {} vs {} {} ({} plies) | {} +{} ={} -{}",
+            index + 1,
+            game.metadata.white.as_deref().unwrap_or("?"),
+            game.metadata.black.as_deref().unwrap_or("?"),
+            game.metadata.result.as_deref().unwrap_or("*"),
+            game.moves.len(),
+            first.name,
+            score.wins,
+            score.draws,
+            score.losses
+        );
+        if let Some(path) = output_path(out_dir, &format!("game_{:04}.json", index + 1))? {
+            game.save(path)?;
+        }
+        Ok(())
+    })?;
+
+    println!("Final: {} +{} ={} -{} vs {}", first.name, score.wins, score.draws, score.losses, second.name);
+    Ok(())
+}
+
+fn cmd_book(args: &Args) -> Result<(), String> {
+    let db_path = args.require("db")?;
+    let book = JieqiOpeningBook::new(db_path).map_err(|e| e.to_string())?;
+    let (action, inputs) = args.positional.split_first().ok_or("book needs build, merge or stats")?;
+    match action.as_str() {
+        "build" => {
+            let max_plies = args.number("max-plies")?.unwrap_or(usize::MAX);
+            let mut total = 0;
+            for file in inputs {
+                match GameNotation::load(file).and_then(|game| import_game(&book, &game, max_plies)) {
+                    Ok(count) => total += count,
+                    Err(e) => eprintln!("{}: {}", file, e),
+                }
+            }
+            println!("Recorded {} moves from {} files", total, inputs.len());
+        }
+        "merge" => {
+            for other in inputs {
+                let merged = book.merge_from(other).map_err(|e| format!("{}: {}", other, e))?;
+                println!("{}: merged {} moves", other, merged);
+            }
+        }
+        "stats" => {
+            let stats = book.get_stats().map_err(|e| e.to_string())?;
+            println!("{}", serde_json::to_string_pretty(&stats).map_err(|e| e.to_string())?);
+        }
+        other => return Err(format!("Unknown book action '{}'", other)),
+    }
+    Ok(())
+}
+
+fn cmd_analyze(args: &Args) -> Result<(), String> {
+    let spec = args.engine_spec("engine")?;
+    let limits = args.limits()?;
+    let timeout = limits.timeout();
+    let mut engine = EngineClient::start(&spec, HANDSHAKE_TIMEOUT)?;
+
+    for file in &args.positional {
+        let notation = GameNotation::load(file)?;
+        engine.new_game(HANDSHAKE_TIMEOUT)?;
+        let analyses = analyze_notation(&mut engine, &notation, &limits, timeout)?;
+        let json = serde_json::to_string_pretty(&analyses).map_err(|e| e.to_string())?;
+        let file_name = format!(
+            "{}.analysis.json",
+            Path::new(file).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default()
+        );
+        match output_path(args.get("out"), &file_name)? {
+            Some(path) => fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?,
+            None => println!("{}", json),
+        }
+    }
+    engine.quit();
+    Ok(())
+}
+
+fn cmd_review(args: &Args) -> Result<(), String> {
+    let spec = args.engine_spec("engine")?;
+    let limits = args.limits()?;
+    let mut engine = EngineClient::start(&spec, HANDSHAKE_TIMEOUT)?;
+
+    for file in &args.positional {
+        let mut notation = GameNotation::load(file)?;
+        engine.new_game(HANDSHAKE_TIMEOUT)?;
+        let report = review_notation(&mut engine, &mut notation, &limits, limits.timeout(), |done, total| {
+            eprint!("\r{}: {}/{}", file, done, total);
+        })?;
+        eprintln!();
+        let file_name = Path::new(file).file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let target = output_path(args.get("out"), &file_name)?.unwrap_or_else(|| PathBuf::from(file));
+        notation.save(&target)?;
+        for (side, player) in [("Red", &report.red), ("Black", &report.black)] {
+            println!(
+                "{} {}: accuracy {:.1}%, avg loss {:.0}cp, !! {} ! {} !? {} ?! {} ? {} ?? {}",
+                file,
+                side,
+                player.accuracy,
+                player.average_cp_loss,
+                player.brilliant,
+                player.good,
+                player.interesting,
+                player.dubious,
+                player.mistakes,
+                player.blunders
+            );
+        }
+    }
+    engine.quit();
+    Ok(())
+}
+
+// Interrupted runs resume from the queue file; jobs left running are picked up again
+fn cmd_queue(args: &Args) -> Result<(), String> {
+    let path = PathBuf::from(args.require("file")?);
+    let mut queue = AnalysisQueue::load(&path)?;
+    let (action, inputs) = args.positional.split_first().ok_or("queue needs add, add-games, run or status")?;
+    match action.as_str() {
+        "add" => {
+            let limits = args.limits()?;
+            let group = args.get("group").map(|s| s.to_string());
+            for fen in inputs {
+                queue.add(fen, limits.clone(), group.clone())?;
+            }
+            queue.save(&path)?;
+            println!("Queued {} positions", inputs.len());
+        }
+        "add-games" => {
+            let limits = args.limits()?;
+            let mut total = 0;
+            for file in inputs {
+                let positions = GameNotation::load(file).and_then(|game| game.replay())?;
+                for position in positions.iter().filter(|p| p.game_result().is_none()) {
+                    queue.add(&position.to_fen(), limits.clone(), Some(file.clone()))?;
+                    total += 1;
+                }
+            }
+            queue.save(&path)?;
+            println!("Queued {} positions from {} files", total, inputs.len());
+        }
+        "run" => {
+            let spec = args.engine_spec("engine")?;
+            let defaults = QueueConfig::default();
+            let config = QueueConfig {
+                workers: args.number("workers")?.unwrap_or(defaults.workers),
+                clear_hash_between_groups: args.number("clear-hash")?.unwrap_or(defaults.clear_hash_between_groups),
+            };
+            let cache = args
+                .get("cache")
+                .map(|db| AnalysisCache::new(db).map(Mutex::new).map_err(|e| e.to_string()))
+                .transpose()?;
+            let queue = Arc::new(Mutex::new(queue));
+            let pause = Arc::new(AtomicBool::new(false));
+            let summary = run_queue(queue, path, &spec, &config, pause, |job| match (&job.result, &job.error) {
+                (Some(result), _) => {
+                    if let (Some(cache), Some(info)) = (&cache, &result.info) {
+                        if let Err(e) = cache.lock().unwrap().store_info(&job.fen, &spec.name, info) {
+                            eprintln!("#{} not cached: {}", job.id, e);
+                        }
+                    }
+                    println!("#{} {} {}", job.id, result.best_move, job.fen);
+                }
+                (None, error) => eprintln!("#{} failed: {}", job.id, error.as_deref().unwrap_or("unknown error")),
+            })?;
+            println!("Done {} | failed {} | pending {}", summary.done, summary.failed, summary.pending);
+        }
+        "status" => {
+            let summary = queue.summary();
+            println!(
+                "pending {} | running {} | done {} | failed {}",
+                summary.pending, summary.running, summary.done, summary.failed
+            );
+            for job in queue.jobs.iter().filter(|j| j.status == JobStatus::Failed) {
+                println!("#{} {}: {}", job.id, job.fen, job.error.as_deref().unwrap_or(""));
+            }
+        }
+        other => return Err(format!("Unknown queue action '{}'", other)),
+    }
+    Ok(())
+}
+
+fn cmd_engines(args: &Args) -> Result<(), String> {
+    let registry_path = args.require("registry")?;
+    let mut registry = EngineRegistry::load(registry_path)?;
+    let (action, inputs) = args.positional.split_first().ok_or("engines needs list, add, remove or check")?;
+    match action.as_str() {
+        "list" => {
+            for engine in &registry.engines {
+                println!("{}  {}  {}", engine.id, engine.name, engine.path);
+            }
+        }
+        "add" => {
+            let entry = registry.add(NewEngine {
+                name: args.require("name")?.to_string(),
+                path: args.require("path")?.to_string(),
+                args: args.get("args").map(|a| a.split_whitespace().map(|s| s.to_string()).collect()).unwrap_or_default(),
+                protocol: args.choice("protocol")?.unwrap_or_default(),
+                options: args.options("option")?,
+                nnue: args.get("nnue").map(|s| s.to_string()),
+                encoding: args.choice("encoding")?.unwrap_or_default(),
+                transport: args.transport("tcp", "wrap")?.unwrap_or_default(),
+                reconnect: ReconnectConfig::default(),
+            })?;
+            registry.save(registry_path)?;
+            println!("Added {}", entry.id);
+        }
+        "remove" => {
+            for id in inputs {
+                let removed = registry.remove(id)?;
+                println!("Removed {} ({})", removed.id, removed.name);
+            }
+            registry.save(registry_path)?;
+        }
+        "check" => {
+            let health = registry.check_all();
+            for (engine, health) in registry.engines.iter().zip(&health) {
+                let status = if health.is_ok() { "OK     " } else { "BROKEN " };
+                println!(
+                    "{}{}  {} (exists: {}, executable: {}, nnue: {})",
+                    status,
+                    engine.id,
+                    engine.name,
+                    health.path_exists,
+                    health.executable,
+                    health.nnue_exists.map(|n| n.to_string()).unwrap_or_else(|| "-".to_string())
+                );
+            }
+            let broken = health.iter().filter(|h| !h.is_ok()).count();
+            if broken > 0 {
+                return Err(format!("{} engine(s) need attention", broken));
+            }
+        }
+        other => return Err(format!("Unknown engines action '{}'", other)),
+    }
+    Ok(())
+}
+
+fn cmd_options(args: &Args) -> Result<(), String> {
+    let spec = args.engine_spec("engine")?;
+    let info = discover_options(&spec, HANDSHAKE_TIMEOUT)?;
+    println!("{}", serde_json::to_string_pretty(&info).map_err(|e| e.to_string())?);
+    validate_option_values(&info.options, &spec.options).map_err(|errors| errors.join("\n"))?;
+    Ok(())
+}
+
+fn cmd_games(args: &Args) -> Result<(), String> {
+    let mut db = GameDatabase::new(args.require("db")?).map_err(|e| e.to_string())?;
+    let (action, inputs) = args.positional.split_first().ok_or("games needs import, search or export")?;
+    match action.as_str() {
+        "import" => {
+            let (mut imported, mut duplicates) = (0, 0);
+            for file in inputs {
+                let games = match read_games(file) {
+                    Ok(games) => games,
+                    Err(e) => {
+                        eprintln!("{}: {}", file, e);
+                        continue;
+                    }
+                };
+                for game in games {
+                    match db.import_game(&game, Some(file)) {
+                        Ok(Some(_)) => imported += 1,
+                        Ok(None) => duplicates += 1,
+                        Err(e) => eprintln!("{}: {}", file, e),
+                    }
+                }
+            }
+            println!("Imported {} games, {} duplicates skipped", imported, duplicates);
+        }
+        "search" => {
+            let text = |flag: &str| args.get(flag).map(|s| s.to_string());
+            let query = GameQuery {
+                player: text("player"),
+                red: text("red"),
+                black: text("black"),
+                event: text("event"),
+                date_from: text("from"),
+                date_to: text("to"),
+                result: text("result"),
+                flip_mode: text("flip-mode"),
+                position_fen: text("fen"),
+                move_uci: text("move"),
+                page: args.number("page")?.unwrap_or(0),
+                page_size: args.number("page-size")?.unwrap_or(0),
+            };
+            let page = db.search(&query).map_err(|e| e.to_string())?;
+            for game in &page.games {
+                println!(
+                    "{:>6}  {}  {} vs {}  {}  ({} plies)",
+                    game.id,
+                    game.date.as_deref().unwrap_or("????-??-??"),
+                    game.red.as_deref().unwrap_or("?"),
+                    game.black.as_deref().unwrap_or("?"),
+                    game.result.as_deref().unwrap_or("*"),
+                    game.ply_count
+                );
+            }
+            println!("Page {} of {} games", page.page + 1, page.total);
+        }
+        "export" => {
+            let out_dir = args.require("out")?;
+            let format = args.choice("format")?.unwrap_or(NotationFormat::Json);
+            for id in inputs {
+                let id: i64 = id.parse().map_err(|_| format!("Invalid game id: {}", id))?;
+                let game = db.get_game(id)?.ok_or_else(|| format!("No game with id {}", id))?;
+                if let Some(path) = output_path(Some(out_dir), &format!("game_{}.{}", id, format.extension()))? {
+                    fs::write(&path, export_notation(&game, format)?)
+                        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+                }
+            }
+        }
+        other => return Err(format!("Unknown games action '{}'", other)),
+    }
+    Ok(())
+}
+
+fn cmd_convert(args: &Args) -> Result<(), String> {
+    let format: NotationFormat = args.choice("to")?.ok_or("convert needs --to FORMAT")?;
+    for file in &args.positional {
+        let games = read_games(file)?;
+        let stem = Path::new(file).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        for (index, game) in games.iter().enumerate() {
+            let text = export_notation(game, format)?;
+            let name = if games.len() > 1 {
+                format!("{}_{}.{}", stem, index + 1, format.extension())
+            } else {
+                format!("{}.{}", stem, format.extension())
+            };
+            match output_path(args.get("out"), &name)? {
+                Some(path) => fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?,
+                None => print!("{}", text),
+            }
+        }
+    }
+    Ok(())
+}
+
+// Returns the number of files with problems left after an optional repair
+fn cmd_check_notation(args: &Args) -> Result<usize, String> {
+    let repair = args.number("repair")?.unwrap_or(false);
+    let mut failed = 0;
+    for file in &args.positional {
+        let mut notation = match read_notation(file) {
+            Ok(notation) => notation,
+            Err(e) => {
+                println!("{}: {}", file, e);
+                failed += 1;
+                continue;
+            }
+        };
+        let problems = notation.check();
+        for problem in &problems {
+            match problem.entry {
+                Some(entry) => println!("{}: entry {}: {}", file, entry, problem.message),
+                None => println!("{}: {}", file, problem.message),
+            }
+        }
+        if problems.is_empty() {
+            println!("{}: OK", file);
+        } else if repair {
+            let report = notation.repair()?;
+            let file_name = Path::new(file).file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            let target = output_path(args.get("out"), &file_name)?.unwrap_or_else(|| PathBuf::from(file));
+            notation.save(&target)?;
+            println!(
+                "{}: repaired {} FEN(s), removed {} annotation(s) and {} unplayable entries -> {}",
+                file,
+                report.fens_fixed,
+                report.annotations_removed,
+                report.entries_removed,
+                target.display()
+            );
+        } else {
+            failed += 1;
+        }
+    }
+    Ok(failed)
+}
+
+// Returns the number of invalid FENs
+fn cmd_validate_fen(args: &Args) -> Result<usize, String> {
+    let mut fens = args.positional.clone();
+    if let Some(file) = args.get("file") {
+        let content = fs::read_to_string(file).map_err(|e| format!("Failed to read {}: {}", file, e))?;
+        fens.extend(content.lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()));
+    }
+    let mut invalid = 0;
+    for fen in &fens {
+        let problems = match Position::from_fen(fen) {
+            Ok(position) => position.validate(),
+            Err(e) => vec![e],
+        };
+        if problems.is_empty() {
+            println!("OK      {}", fen);
+        } else {
+            invalid += 1;
+            println!("INVALID {}", fen);
+            for problem in problems {
+                println!("        - {}", problem);
+            }
+        }
+    }
+    Ok(invalid)
+}
+
+// Prints the FEN read from each screenshot, plus the squares it is unsure about
+fn recognition_config(args: &Args) -> Result<RecognitionConfig, String> {
+    let defaults = RecognitionConfig::default();
+    Ok(RecognitionConfig {
+        board_rect: args.number("rect")?,
+        orientation: args.choice("orientation")?.unwrap_or_default(),
+        side_to_move: match args.get("side") {
+            None | Some("w") => Side::Red,
+            Some("b") => Side::Black,
+            Some(other) => return Err(format!("Invalid value for --side: {}", other)),
+        },
+        min_score: args.number("min-score")?.unwrap_or(defaults.min_score),
+    })
+}
+
+fn cmd_recognize(args: &Args) -> Result<(), String> {
+    let pieces = PieceSet::load_dir(args.require("pieces")?)?;
+    let config = recognition_config(args)?;
+    for file in &args.positional {
+        let image = image::open(file).map_err(|e| format!("Failed to read {}: {}", file, e))?.to_rgba8();
+        let recognition = recognize(&image, &pieces, &config).map_err(|e| format!("{}: {}", file, e))?;
+        let rect = recognition.board_rect;
+        println!(
+            "{}: {} (confidence {:.2}, grid {:.0},{:.0} {:.0}x{:.0})",
+            file, recognition.fen, recognition.confidence, rect.x, rect.y, rect.width, rect.height
+        );
+        for square in recognition.squares.iter().filter(|s| s.confidence < 0.5) {
+            println!("        {} {} {:.2}", square.square, square.label, square.confidence);
+        }
+        for problem in &recognition.problems {
+            println!("        - {}", problem);
+        }
+    }
+    Ok(())
+}
+
+// Replays a directory of recorded frames through the board watcher
+fn cmd_watch(args: &Args) -> Result<(), String> {
+    let pieces = PieceSet::load_dir(args.require("pieces")?)?;
+    let config = recognition_config(args)?;
+    let position = args.get("fen").map(Position::from_fen).transpose()?;
+    let defaults = WatcherConfig::default();
+    let watcher_config = WatcherConfig {
+        interval: Duration::ZERO,
+        stable_frames: args.number("stable")?.unwrap_or(defaults.stable_frames).max(1),
+        ..defaults
+    };
+    let [dir] = args.positional.as_slice() else {
+        return Err("watch takes one directory of frames".to_string());
+    };
+    let mut frames = RecordedFrames::load_dir(dir)?;
+    let watcher = Mutex::new(BoardWatcher::new(position, watcher_config));
+    run_watcher(&mut frames, &pieces, &config, &watcher, &AtomicBool::new(false), |event| match event {
+        WatchEvent::Synced { fen } => println!("synced  {}", fen),
+        WatchEvent::Move { uci, side, fen } => println!("{:<7} {:<6} {}", side, uci, fen),
+        WatchEvent::Unexplained { recognized, .. } => println!("unexplained board {}", recognized),
+    })?;
+    if let Some(position) = watcher.lock().unwrap().position() {
+        println!("final   {}", position.to_fen());
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let raw: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, rest)) = raw.split_first() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+    let args = match Args::parse(rest) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let result = match command.as_str() {
+        "match" => cmd_match(&args),
+        "book" => cmd_book(&args),
+        "analyze" => cmd_analyze(&args),
+        "review" => cmd_review(&args),
+        "queue" => cmd_queue(&args),
+        "options" => cmd_options(&args),
+        "engines" => cmd_engines(&args),
+        "games" => cmd_games(&args),
+        "convert" => cmd_convert(&args),
+        "check-notation" => match cmd_check_notation(&args) {
+            Ok(0) => Ok(()),
+            Ok(failed) => Err(format!("{} file(s) with problems", failed)),
+            Err(e) => Err(e),
+        },
+        "validate-fen" => match cmd_validate_fen(&args) {
+            Ok(0) => Ok(()),
+            Ok(invalid) => Err(format!("{} invalid FEN(s)", invalid)),
+            Err(e) => Err(e),
+        },
+        "recognize" => cmd_recognize(&args),
+        "watch" => cmd_watch(&args),
+        "help" | "-h" | "--help" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => Err(format!("Unknown command '{}'\n\n{}", other, USAGE)),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,594 @@
+use crate::position::{square_to_uci, Piece, Position, Side};
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// Templates and square patches are compared at this size
+const PATCH_SIZE: u32 = 32;
+// Side of a square's patch as a fraction of the grid spacing
+pub(crate) const PATCH_SCALE: f32 = 0.9;
+// Patches are also tried shifted by up to this fraction of the spacing
+const SHIFT_SCALE: f32 = 0.04;
+// Best-vs-runner-up score gap at which a match counts as unambiguous
+const CLEAR_MARGIN: f32 = 0.2;
+
+// Line detection: a pixel is on a line when it differs by LINE_CONTRAST from both pixels
+// LINE_OFFSET away across the line, and only runs of MIN_RUN such pixels count
+const LINE_CONTRAST: f32 = 24.0;
+const LINE_OFFSET: usize = 3;
+const MIN_RUN: usize = 8;
+const MIN_SPACING: f32 = 12.0;
+
+// Same names as the labels of the frontend's detection model
+const PIECE_LABELS: [(&str, char); 14] = [
+    ("r_general", 'K'),
+    ("r_advisor", 'A'),
+    ("r_elephant", 'B'),
+    ("r_horse", 'N'),
+    ("r_chariot", 'R'),
+    ("r_cannon", 'C'),
+    ("r_soldier", 'P'),
+    ("b_general", 'k'),
+    ("b_advisor", 'a'),
+    ("b_elephant", 'b'),
+    ("b_horse", 'n'),
+    ("b_chariot", 'r'),
+    ("b_cannon", 'c'),
+    ("b_soldier", 'p'),
+];
+
+/// Pixel position of the grid: (x, y) is the top-left intersection and the size spans to the
+/// bottom-right one, so intersections are width / 8 and height / 9 apart.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoardRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl BoardRect {
+    // Center of an intersection, counted from the top-left of the image's grid
+    pub fn intersection(&self, row: usize, col: usize) -> (f32, f32) {
+        (self.x + self.width * col as f32 / 8.0, self.y + self.height * row as f32 / 9.0)
+    }
+
+    pub fn spacing(&self) -> f32 {
+        (self.width / 8.0).min(self.height / 9.0)
+    }
+}
+
+/// Which way up the board is shown on screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoardOrientation {
+    #[default]
+    RedBottom,
+    RedTop,
+}
+
+impl BoardOrientation {
+    // Grid row and column, from the top-left of the image, where a board square is shown
+    pub fn image_cell(self, sq: usize) -> (usize, usize) {
+        let (row, col) = (sq / 9, sq % 9);
+        match self {
+            BoardOrientation::RedBottom => (row, col),
+            BoardOrientation::RedTop => (9 - row, 8 - col),
+        }
+    }
+}
+
+// `x,y,width,height`, as given on the command line
+impl std::str::FromStr for BoardRect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let values: Vec<f32> =
+            s.split(',').map(|v| v.trim().parse::<f32>()).collect::<Result<_, _>>().map_err(|e| e.to_string())?;
+        match values.as_slice() {
+            &[x, y, width, height] if width > 0.0 && height > 0.0 => Ok(BoardRect { x, y, width, height }),
+            _ => Err(format!("Board rectangle must be x,y,width,height: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TemplateClass {
+    Empty,
+    Known(char),
+    // Dark pieces without a side take the side whose half of the board they are on
+    Dark(Option<Side>),
+}
+
+impl TemplateClass {
+    fn from_label(label: &str) -> Option<TemplateClass> {
+        match label {
+            "empty" => Some(TemplateClass::Empty),
+            "dark" => Some(TemplateClass::Dark(None)),
+            "r_dark" => Some(TemplateClass::Dark(Some(Side::Red))),
+            "b_dark" => Some(TemplateClass::Dark(Some(Side::Black))),
+            _ => PIECE_LABELS.iter().find(|(name, _)| *name == label).map(|(_, c)| TemplateClass::Known(*c)),
+        }
+    }
+}
+
+struct Template {
+    label: String,
+    class: TemplateClass,
+    features: Vec<f32>,
+}
+
+/// Reference images of the pieces of one board style.
+#[derive(Default)]
+pub struct PieceSet {
+    templates: Vec<Template>,
+}
+
+impl PieceSet {
+    /// Loads every image in `dir` whose file stem is a label (`r_chariot`, `b_soldier`,
+    /// `dark`, `r_dark`, `b_dark` or `empty`), optionally followed by `-` and a variant name.
+    /// Each image is a square crop around one piece.
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> Result<PieceSet, String> {
+        let dir = dir.as_ref();
+        let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read piece set {}: {}", dir.display(), e))?;
+        let mut paths: Vec<_> = entries.filter_map(|e| e.ok().map(|e| e.path())).filter(|p| p.is_file()).collect();
+        paths.sort();
+
+        let mut set = PieceSet::default();
+        for path in paths {
+            let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            let label = stem.split('-').next().unwrap_or_default();
+            if TemplateClass::from_label(label).is_none() {
+                continue;
+            }
+            let image = image::open(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            set.add(label, &image.to_rgba8())?;
+        }
+        if set.templates.is_empty() {
+            return Err(format!("No piece templates in {}", dir.display()));
+        }
+        Ok(set)
+    }
+
+    pub fn add(&mut self, label: &str, image: &RgbaImage) -> Result<(), String> {
+        let class = TemplateClass::from_label(label).ok_or_else(|| format!("Unknown piece label '{}'", label))?;
+        if image.width() == 0 || image.height() == 0 {
+            return Err(format!("Empty template image for {}", label));
+        }
+        self.templates.push(Template { label: label.to_string(), class, features: features(image) });
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+
+    fn has_empty_templates(&self) -> bool {
+        self.templates.iter().any(|t| t.class == TemplateClass::Empty)
+    }
+}
+
+pub struct RecognitionConfig {
+    // Located with locate_grid when not given
+    pub board_rect: Option<BoardRect>,
+    pub orientation: BoardOrientation,
+    pub side_to_move: Side,
+    // Without `empty` templates, squares whose best match scores lower are empty
+    pub min_score: f32,
+}
+
+impl Default for RecognitionConfig {
+    fn default() -> Self {
+        RecognitionConfig {
+            board_rect: None,
+            orientation: BoardOrientation::RedBottom,
+            side_to_move: Side::Red,
+            min_score: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SquareRecognition {
+    pub square: String,
+    // FEN letter, `X`/`x` for dark pieces; None when empty
+    pub piece: Option<char>,
+    // Template that matched best, or `empty` when nothing matched
+    pub label: String,
+    pub confidence: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recognition {
+    pub fen: String,
+    pub board_rect: BoardRect,
+    // In FEN order, from a9 to i0
+    pub squares: Vec<SquareRecognition>,
+    // Lowest square confidence
+    pub confidence: f32,
+    // Position::validate findings, usually a sign of misread squares
+    pub problems: Vec<String>,
+}
+
+/// Reads the position from a screenshot.
+pub fn recognize(image: &RgbaImage, pieces: &PieceSet, config: &RecognitionConfig) -> Result<Recognition, String> {
+    if pieces.is_empty() {
+        return Err("The piece set has no templates".to_string());
+    }
+    let rect = match config.board_rect {
+        Some(rect) => rect,
+        None => locate_grid(image).ok_or("Could not find the board grid in the image")?,
+    };
+    if rect.spacing() < MIN_SPACING {
+        return Err(format!("Board is too small to recognize ({:.0}x{:.0} pixels)", rect.width, rect.height));
+    }
+
+    let mut board = [None; 90];
+    let mut squares = Vec::with_capacity(90);
+    for (sq, cell) in board.iter_mut().enumerate() {
+        let (row, col) = config.orientation.image_cell(sq);
+        let (class, label, confidence) = classify(image, rect, row, col, pieces, config.min_score);
+        let piece = match class {
+            TemplateClass::Empty => None,
+            TemplateClass::Known(c) => Some(Piece::Known(c)),
+            TemplateClass::Dark(Some(side)) => Some(Piece::Dark(side)),
+            TemplateClass::Dark(None) => Some(Piece::Dark(if sq / 9 >= 5 { Side::Red } else { Side::Black })),
+        };
+        *cell = piece;
+        let piece_char = piece.map(|p| p.fen_char());
+        squares.push(SquareRecognition { square: square_to_uci(sq), piece: piece_char, label, confidence });
+    }
+
+    let position = Position {
+        board,
+        side_to_move: config.side_to_move,
+        hidden: hidden_pool(&board),
+        captured_hidden: HashMap::new(),
+        halfmove: 0,
+        fullmove: 1,
+    };
+    let confidence = squares.iter().map(|s| s.confidence).fold(1.0, f32::min);
+    Ok(Recognition { fen: position.to_fen(), board_rect: rect, squares, confidence, problems: position.validate() })
+}
+
+// Which captured pieces were dark is not visible, so every piece type not revealed on the
+// board is assumed to still be in the pool
+fn hidden_pool(board: &[Option<Piece>; 90]) -> HashMap<char, i32> {
+    let initial = [('R', 2), ('N', 2), ('B', 2), ('A', 2), ('C', 2), ('P', 5)];
+    let mut pool = HashMap::new();
+    for (red, count) in initial {
+        for c in [red, red.to_ascii_lowercase()] {
+            let revealed = board.iter().filter(|p| **p == Some(Piece::Known(c))).count() as i32;
+            if count > revealed {
+                pool.insert(c, count - revealed);
+            }
+        }
+    }
+    pool
+}
+
+fn classify(
+    image: &RgbaImage,
+    rect: BoardRect,
+    row: usize,
+    col: usize,
+    pieces: &PieceSet,
+    min_score: f32,
+) -> (TemplateClass, String, f32) {
+    let spacing = rect.spacing();
+    let (cx, cy) = rect.intersection(row, col);
+    let size = (spacing * PATCH_SCALE).round().max(1.0) as i64;
+    let shift = (spacing * SHIFT_SCALE).round() as i64;
+
+    let offsets: Vec<(i64, i64)> = if shift == 0 {
+        vec![(0, 0)]
+    } else {
+        [-shift, 0, shift].iter().flat_map(|&dy| [-shift, 0, shift].map(|dx| (dx, dy))).collect()
+    };
+    let mut scores = vec![f32::MIN; pieces.templates.len()];
+    for (dx, dy) in offsets {
+        let left = (cx.round() as i64 + dx - size / 2).max(0);
+        let top = (cy.round() as i64 + dy - size / 2).max(0);
+        if left >= image.width() as i64 || top >= image.height() as i64 {
+            continue;
+        }
+        let patch = features(&imageops::crop_imm(image, left as u32, top as u32, size as u32, size as u32).to_image());
+        for (score, template) in scores.iter_mut().zip(&pieces.templates) {
+            *score = score.max(dot(&patch, &template.features));
+        }
+    }
+
+    let Some((best, best_score)) = scores.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)).map(|(i, s)| (i, *s))
+    else {
+        return (TemplateClass::Empty, "empty".to_string(), 0.0);
+    };
+    let template = &pieces.templates[best];
+    if !pieces.has_empty_templates() && best_score < min_score {
+        let confidence = ((min_score - best_score) / min_score).clamp(0.0, 1.0);
+        return (TemplateClass::Empty, "empty".to_string(), confidence);
+    }
+    let runner_up = scores
+        .iter()
+        .zip(&pieces.templates)
+        .filter(|(_, t)| t.class != template.class)
+        .map(|(s, _)| *s)
+        .fold(f32::MIN, f32::max);
+    // The match score, halved when another class scores just as well
+    let clarity = if runner_up == f32::MIN { 1.0 } else { ((best_score - runner_up) / CLEAR_MARGIN).clamp(0.0, 1.0) };
+    let confidence = best_score.clamp(0.0, 1.0) * (0.5 + 0.5 * clarity);
+    (template.class, template.label.clone(), confidence)
+}
+
+// RGB values of the image scaled to PATCH_SIZE, inside the inscribed circle so the board
+// around a round piece does not count. Each channel is made zero-mean so the board color
+// itself does not correlate with everything, then the whole vector is scaled to unit length.
+fn features(image: &RgbaImage) -> Vec<f32> {
+    let scaled = imageops::resize(image, PATCH_SIZE, PATCH_SIZE, FilterType::Triangle);
+    let center = PATCH_SIZE as f32 / 2.0;
+    let mut channels: [Vec<f32>; 3] = Default::default();
+    for (x, y, pixel) in scaled.enumerate_pixels() {
+        let (dx, dy) = (x as f32 + 0.5 - center, y as f32 + 0.5 - center);
+        if dx * dx + dy * dy <= center * center {
+            for (channel, &value) in channels.iter_mut().zip(&pixel.0[..3]) {
+                channel.push(value as f32);
+            }
+        }
+    }
+    for channel in channels.iter_mut() {
+        let mean = channel.iter().sum::<f32>() / channel.len() as f32;
+        channel.iter_mut().for_each(|v| *v -= mean);
+    }
+    let mut values = channels.concat();
+    let norm = values.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        values.iter_mut().for_each(|v| *v /= norm);
+    }
+    values
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+/// Finds the 9x10 grid from its lines: ten evenly spaced horizontal lines and nine vertical
+/// lines with about the same spacing.
+pub fn locate_grid(image: &RgbaImage) -> Option<BoardRect> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    if (width as f32) < 8.0 * MIN_SPACING || (height as f32) < 9.0 * MIN_SPACING {
+        return None;
+    }
+    let luma: Vec<f32> =
+        image.pixels().map(|p| 0.299 * p.0[0] as f32 + 0.587 * p.0[1] as f32 + 0.114 * p.0[2] as f32).collect();
+    let horizontal = LineMask::new(&luma, width, height, true);
+    let vertical = LineMask::new(&luma, width, height, false);
+
+    let rows = horizontal.profile(0..width);
+    let (y, row_spacing, row_score) = best_progression(&rows, 10, MIN_SPACING, height as f32 / 9.0)?;
+    let band = (y - row_spacing * 1.5).max(0.0) as usize..((y + row_spacing * 10.5) as usize).min(height);
+    let cols = vertical.profile(band);
+    let max_col_spacing = (row_spacing * 1.25).min(width as f32 / 8.0);
+    let (x, col_spacing, col_score) = best_progression(&cols, 9, row_spacing * 0.8, max_col_spacing)?;
+
+    // When pieces cover an edge line, the grid shifted by one line scores about the same. The
+    // lines across then decide: vertical lines only run between the first and last rows.
+    let y = shifted_starts(&rows, y, row_spacing, 10, row_score)
+        .max_by_key(|&start| vertical.coverage(&positions(x, col_spacing, 9), start, row_spacing * 9.0))?;
+    let x = shifted_starts(&cols, x, col_spacing, 9, col_score)
+        .max_by_key(|&start| horizontal.coverage(&positions(y, row_spacing, 10), start, col_spacing * 8.0))?;
+
+    // Most lines must be visible; pieces hide the rest
+    let visible = |profile: &[f32], start: f32, spacing: f32, count: usize| {
+        positions(start, spacing, count).filter(|&at| sample(profile, at) >= spacing).count()
+    };
+    if visible(&rows, y, row_spacing, 10) < 6 || visible(&cols, x, col_spacing, 9) < 5 {
+        return None;
+    }
+    Some(BoardRect { x, y, width: col_spacing * 8.0, height: row_spacing * 9.0 })
+}
+
+fn positions(start: f32, spacing: f32, count: usize) -> impl Iterator<Item = f32> + Clone {
+    (0..count).map(move |k| start + k as f32 * spacing)
+}
+
+// Pixels on straight runs of line pixels in one direction. A pixel is a line pixel when it
+// differs by LINE_CONTRAST, the same way, from both pixels LINE_OFFSET away across the line.
+struct LineMask {
+    mask: Vec<bool>,
+    width: usize,
+    height: usize,
+    horizontal: bool,
+}
+
+impl LineMask {
+    fn new(luma: &[f32], width: usize, height: usize, horizontal: bool) -> LineMask {
+        let (lines, length) = if horizontal { (height, width) } else { (width, height) };
+        let index = |line: usize, pos: usize| if horizontal { line * width + pos } else { pos * width + line };
+        let mut mask = vec![false; width * height];
+        for line in LINE_OFFSET..lines.saturating_sub(LINE_OFFSET) {
+            let mut run_start = 0;
+            for pos in 0..=length {
+                let on_line = pos < length && {
+                    let value = luma[index(line, pos)];
+                    let before = value - luma[index(line - LINE_OFFSET, pos)];
+                    let after = value - luma[index(line + LINE_OFFSET, pos)];
+                    before.signum() == after.signum() && before.abs().min(after.abs()) >= LINE_CONTRAST
+                };
+                if on_line {
+                    continue;
+                }
+                if pos - run_start >= MIN_RUN {
+                    (run_start..pos).for_each(|p| mask[index(line, p)] = true);
+                }
+                run_start = pos + 1;
+            }
+        }
+        LineMask { mask, width, height, horizontal }
+    }
+
+    fn at(&self, line: usize, pos: usize) -> bool {
+        if self.horizontal { self.mask[line * self.width + pos] } else { self.mask[pos * self.width + line] }
+    }
+
+    // Line pixels per row (or column), counting only positions inside `span`
+    fn profile(&self, span: std::ops::Range<usize>) -> Vec<f32> {
+        let (lines, length) = if self.horizontal { (self.height, self.width) } else { (self.width, self.height) };
+        let span = span.start..span.end.min(length);
+        (0..lines).map(|line| span.clone().filter(|&pos| self.at(line, pos)).count() as f32).collect()
+    }
+
+    // Line pixels on the given lines (allowing a pixel of rounding) from `start` for `length`
+    fn coverage(&self, lines: &(impl Iterator<Item = f32> + Clone), start: f32, length: f32) -> usize {
+        let count = if self.horizontal { self.height } else { self.width };
+        let limit = if self.horizontal { self.width } else { self.height };
+        let span = start.max(0.0).round() as usize..((start + length).round() as usize).min(limit);
+        lines
+            .clone()
+            .map(|at| at.round() as i64)
+            .map(|at| {
+                let near = |pos: usize| {
+                    (at - 1..=at + 1).any(|l| l >= 0 && (l as usize) < count && self.at(l as usize, pos))
+                };
+                span.clone().filter(|&pos| near(pos)).count()
+            })
+            .sum()
+    }
+}
+
+// Profile value near a fractional position, allowing a pixel of rounding either way
+fn sample(profile: &[f32], at: f32) -> f32 {
+    let center = at.round() as i64;
+    (center - 1..=center + 1)
+        .filter(|&i| i >= 0 && (i as usize) < profile.len())
+        .map(|i| profile[i as usize])
+        .fold(0.0, f32::max)
+}
+
+// Sum of the square roots of the line support; the roots keep a single long line, such as a
+// window edge, from outweighing the grid
+fn progression_score(profile: &[f32], start: f32, spacing: f32, count: usize) -> f32 {
+    positions(start, spacing, count).map(|at| sample(profile, at).sqrt()).sum()
+}
+
+// Start, spacing and score of the `count` evenly spaced lines with the most support
+fn best_progression(profile: &[f32], count: usize, min_spacing: f32, max_spacing: f32) -> Option<(f32, f32, f32)> {
+    let gaps = (count - 1) as f32;
+    let mut best: Option<(f32, f32, f32)> = None;
+    let mut spacing = min_spacing;
+    while spacing <= max_spacing {
+        let mut start = 0.0;
+        while start + gaps * spacing < profile.len() as f32 {
+            let score = progression_score(profile, start, spacing, count);
+            if !matches!(best, Some((_, _, s)) if s >= score) {
+                best = Some((start, spacing, score));
+            }
+            start += 1.0;
+        }
+        spacing += 0.25;
+    }
+    best.filter(|(_, _, score)| *score > 0.0)
+}
+
+// The start shifted by up to two lines either way, where the lines still score nearly as well
+fn shifted_starts(
+    profile: &[f32],
+    start: f32,
+    spacing: f32,
+    count: usize,
+    score: f32,
+) -> impl Iterator<Item = f32> + '_ {
+    let end = profile.len() as f32 - (count - 1) as f32 * spacing;
+    (-2..=2)
+        .map(move |k| start + k as f32 * spacing)
+        .filter(move |&s| s >= 0.0 && s < end && progression_score(profile, s, spacing, count) >= score * 0.75)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_boards::{draw_piece, piece_set, render, BOARD_COLOR, GRID};
+
+    fn board_part(fen: &str) -> &str {
+        fen.split_whitespace().next().unwrap()
+    }
+
+    // Line positions are matched to a pixel either way, so the far corner may be off by a few
+    fn assert_close(found: BoardRect, expected: BoardRect) {
+        for (row, col) in [(0, 0), (9, 8)] {
+            let ((fx, fy), (ex, ey)) = (found.intersection(row, col), expected.intersection(row, col));
+            assert!((fx - ex).abs() <= 3.0 && (fy - ey).abs() <= 3.0, "{:?} != {:?}", found, expected);
+        }
+    }
+
+    #[test]
+    fn locates_empty_and_occupied_grids() {
+        let empty = render("9/9/9/9/9/9/9/9/9/9 w - - 0 1", BoardOrientation::RedBottom);
+        assert_close(locate_grid(&empty).unwrap(), GRID);
+
+        // Pieces cover every intersection of the edge rows
+        let start = render(&Position::startpos().to_fen(), BoardOrientation::RedBottom);
+        assert_close(locate_grid(&start).unwrap(), GRID);
+
+        assert_eq!(locate_grid(&RgbaImage::from_pixel(480, 530, BOARD_COLOR)), None);
+        assert_eq!(locate_grid(&RgbaImage::from_pixel(60, 60, BOARD_COLOR)), None);
+    }
+
+    #[test]
+    fn recognizes_a_drawn_position() {
+        let fen = "xxxxkxxxx/9/1x2c4/x1x1x1x1x/9/9/X1X1X1X1X/1X2C4/9/XXXXKXXXX w - - 0 1";
+        let pieces = piece_set();
+        let image = render(fen, BoardOrientation::RedBottom);
+        let recognition = recognize(&image, &pieces, &RecognitionConfig::default()).unwrap();
+        assert_eq!(board_part(&recognition.fen), board_part(fen));
+        assert_close(recognition.board_rect, GRID);
+        assert!(recognition.confidence > 0.3, "{}", recognition.confidence);
+        assert!(recognition.problems.is_empty(), "{:?}", recognition.problems);
+        let e2 = recognition.squares.iter().find(|s| s.square == "e2").unwrap();
+        assert_eq!((e2.piece, e2.label.as_str()), (Some('C'), "r_cannon"));
+
+        // Same board shown the other way up, with the rectangle given
+        let config = RecognitionConfig {
+            board_rect: Some(GRID),
+            orientation: BoardOrientation::RedTop,
+            side_to_move: Side::Black,
+            ..Default::default()
+        };
+        let recognition = recognize(&render(fen, BoardOrientation::RedTop), &pieces, &config).unwrap();
+        assert_eq!(board_part(&recognition.fen), board_part(fen));
+        assert_eq!(recognition.fen.split_whitespace().nth(1), Some("b"));
+
+        assert!(recognize(&image, &PieceSet::default(), &config).is_err());
+    }
+
+    #[test]
+    fn pool_holds_every_piece_not_revealed() {
+        let position = Position::from_fen("4k4/9/9/9/9/2p6/9/R7R/9/4K4 w - - 0 1").unwrap();
+        let pool = hidden_pool(&position.board);
+        assert_eq!(pool.get(&'R'), None);
+        assert_eq!(pool.get(&'r'), Some(&2));
+        assert_eq!(pool.get(&'p'), Some(&4));
+        assert_eq!(pool.get(&'P'), Some(&5));
+        assert_eq!(pool.values().sum::<i32>(), 30 - 3);
+    }
+
+    #[test]
+    fn features_ignore_brightness() {
+        let mut image = RgbaImage::from_pixel(40, 40, BOARD_COLOR);
+        draw_piece(&mut image, (20.0, 20.0), Piece::Known('R'));
+        let mut brighter = image.clone();
+        brighter.pixels_mut().for_each(|p| p.0[..3].iter_mut().for_each(|v| *v = v.saturating_add(10)));
+
+        let (a, b) = (features(&image), features(&brighter));
+        assert_eq!(a.len(), b.len());
+        assert!((dot(&a, &a) - 1.0).abs() < 1e-4);
+        assert!(dot(&a, &b) > 0.99);
+        // A flat image has nothing to correlate
+        assert!(features(&RgbaImage::from_pixel(40, 40, BOARD_COLOR)).iter().all(|v| *v == 0.0));
+    }
+}
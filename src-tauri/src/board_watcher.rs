@@ -0,0 +1,368 @@
+use crate::board_recognition::{recognize, PieceSet, Recognition, RecognitionConfig};
+use crate::position::{JieqiMove, Piece, Position, Side};
+use image::RgbaImage;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Frames that fail to capture in a row before the watcher gives up
+const MAX_FRAME_ERRORS: u32 = 10;
+
+/// Where the watcher's images come from: the screen, or recorded frames in tests.
+pub trait FrameSource: Send {
+    // None when a recorded sequence is exhausted
+    fn next_frame(&mut self) -> Result<Option<RgbaImage>, String>;
+}
+
+pub struct RecordedFrames {
+    frames: VecDeque<RgbaImage>,
+}
+
+impl RecordedFrames {
+    pub fn new(frames: Vec<RgbaImage>) -> Self {
+        RecordedFrames { frames: frames.into() }
+    }
+
+    // Every image in the directory, in file name order
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> Result<Self, String> {
+        let dir = dir.as_ref();
+        let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        let mut paths: Vec<_> = entries.filter_map(|e| e.ok().map(|e| e.path())).filter(|p| p.is_file()).collect();
+        paths.sort();
+        let frames = paths
+            .iter()
+            .filter(|p| image::ImageFormat::from_path(p).is_ok())
+            .map(|p| image::open(p).map(|i| i.to_rgba8()).map_err(|e| format!("Failed to read {}: {}", p.display(), e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(RecordedFrames::new(frames))
+    }
+}
+
+impl FrameSource for RecordedFrames {
+    fn next_frame(&mut self) -> Result<Option<RgbaImage>, String> {
+        Ok(self.frames.pop_front())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WatcherConfig {
+    pub interval: Duration,
+    // Identical recognitions needed before a change counts, so animations and flashing
+    // highlights are not read as moves
+    pub stable_frames: u32,
+    // Frames with a less confident square are skipped
+    pub min_confidence: f32,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        WatcherConfig { interval: Duration::from_millis(500), stable_frames: 2, min_confidence: 0.3 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WatchEvent {
+    // First stable position, when the watcher was started without one
+    Synced { fen: String },
+    Move { uci: String, side: String, fen: String },
+    // A stable board that no legal move from the known position leads to
+    Unexplained { fen: String, recognized: String },
+}
+
+/// Tracks the position on an external board from successive recognitions.
+pub struct BoardWatcher {
+    config: WatcherConfig,
+    position: Option<Position>,
+    // Board FEN seen in the last frames, how often in a row, and whether it was reported
+    candidate: Option<String>,
+    seen: u32,
+    reported: bool,
+}
+
+impl BoardWatcher {
+    pub fn new(position: Option<Position>, config: WatcherConfig) -> Self {
+        BoardWatcher { config, position, candidate: None, seen: 0, reported: false }
+    }
+
+    pub fn config(&self) -> &WatcherConfig {
+        &self.config
+    }
+
+    pub fn position(&self) -> Option<&Position> {
+        self.position.as_ref()
+    }
+
+    // For moves made outside the watcher's view, e.g. our own
+    pub fn set_position(&mut self, position: Position) {
+        self.position = Some(position);
+        self.candidate = None;
+        self.seen = 0;
+        self.reported = false;
+    }
+
+    pub fn observe(&mut self, recognition: &Recognition) -> Option<WatchEvent> {
+        if recognition.confidence < self.config.min_confidence {
+            self.candidate = None;
+            return None;
+        }
+        let board = recognition.fen.split_whitespace().next().unwrap_or_default().to_string();
+        if self.position.as_ref().is_some_and(|p| p.board_fen() == board) {
+            self.candidate = None;
+            return None;
+        }
+        if self.candidate.as_deref() == Some(board.as_str()) {
+            self.seen += 1;
+        } else {
+            self.candidate = Some(board);
+            self.seen = 1;
+            self.reported = false;
+        }
+        if self.seen < self.config.stable_frames || self.reported {
+            return None;
+        }
+
+        let recognized = Position::from_fen(&recognition.fen).ok()?;
+        let Some(position) = self.position.as_mut() else {
+            let fen = recognized.to_fen();
+            self.set_position(recognized);
+            return Some(WatchEvent::Synced { fen });
+        };
+        let played = infer_move(position, &recognized.board).and_then(|mv| {
+            let mut next = position.clone();
+            next.apply(&mv).ok().map(|_| (mv, next))
+        });
+        match played {
+            Some((mv, next)) => {
+                let side = if position.side_to_move == Side::Red { "red" } else { "black" };
+                let event = WatchEvent::Move { uci: mv.to_string(), side: side.to_string(), fen: next.to_fen() };
+                self.set_position(next);
+                Some(event)
+            }
+            None => {
+                self.reported = true;
+                Some(WatchEvent::Unexplained { fen: position.to_fen(), recognized: recognition.fen.clone() })
+            }
+        }
+    }
+}
+
+/// The legal move that turns `before` into `after`, with the revealed type when a dark piece
+/// moved. A captured dark piece is gone from the board, so its type stays unknown. None if
+/// no move or more than one move fits.
+pub fn infer_move(before: &Position, after: &[Option<Piece>; 90]) -> Option<JieqiMove> {
+    let mut found = None;
+    for mut mv in before.legal_moves() {
+        let (Some(piece), None, Some(landed)) = (before.board[mv.from], after[mv.from], after[mv.to]) else {
+            continue;
+        };
+        match (piece, landed) {
+            (Piece::Dark(side), Piece::Known(c)) if Side::of_char(c) == side => mv.reveal = Some(c),
+            _ if piece == landed => {}
+            _ => continue,
+        }
+        if (0..90).any(|sq| sq != mv.from && sq != mv.to && after[sq] != before.board[sq]) {
+            continue;
+        }
+        if found.is_some() {
+            return None;
+        }
+        found = Some(mv);
+    }
+    found
+}
+
+/// Recognizes frames from `source` every `interval` until `stop` is set or the source runs
+/// out, passing what the watcher makes of them to `on_event`. Frames that cannot be
+/// captured or recognized are skipped; only a source failing MAX_FRAME_ERRORS times in a row
+/// ends the watcher with its error.
+pub fn run_watcher(
+    source: &mut dyn FrameSource,
+    pieces: &PieceSet,
+    recognition: &RecognitionConfig,
+    watcher: &Mutex<BoardWatcher>,
+    stop: &AtomicBool,
+    mut on_event: impl FnMut(WatchEvent),
+) -> Result<(), String> {
+    let interval = watcher.lock().unwrap().config().interval;
+    let mut frame_errors = 0;
+    while !stop.load(Ordering::SeqCst) {
+        let started = Instant::now();
+        let frame = match source.next_frame() {
+            Ok(Some(frame)) => {
+                frame_errors = 0;
+                Some(frame)
+            }
+            Ok(None) => break,
+            Err(e) => {
+                frame_errors += 1;
+                if frame_errors >= MAX_FRAME_ERRORS {
+                    return Err(e);
+                }
+                None
+            }
+        };
+        if let Some(Ok(result)) = frame.map(|frame| recognize(&frame, pieces, recognition)) {
+            let event = watcher.lock().unwrap().observe(&result);
+            if let Some(event) = event {
+                on_event(event);
+            }
+        }
+        // Short naps so a stop request is noticed quickly
+        while !stop.load(Ordering::SeqCst) && started.elapsed() < interval {
+            thread::sleep(interval.saturating_sub(started.elapsed()).min(Duration::from_millis(50)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_recognition::BoardRect;
+    use crate::position::square_from_uci;
+
+    fn moved(position: &Position, uci: &str) -> Position {
+        let mut next = position.clone();
+        next.apply(&JieqiMove::parse(uci, position.side_to_move).unwrap()).unwrap();
+        next
+    }
+
+    fn after(position: &Position, uci: &str) -> [Option<Piece>; 90] {
+        moved(position, uci).board
+    }
+
+    fn recognition(position: &Position, confidence: f32) -> Recognition {
+        Recognition {
+            fen: position.to_fen(),
+            board_rect: BoardRect { x: 0.0, y: 0.0, width: 400.0, height: 450.0 },
+            squares: Vec::new(),
+            confidence,
+            problems: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn infers_plain_reveal_and_capture_moves() {
+        let pos = Position::from_fen("3k5/9/9/9/9/9/9/9/4R4/4K4 w - - 0 1").unwrap();
+        assert_eq!(infer_move(&pos, &after(&pos, "e1a1")).unwrap().to_string(), "e1a1");
+
+        let start = Position::startpos();
+        assert_eq!(infer_move(&start, &after(&start, "h2e2C")).unwrap().to_string(), "h2e2C");
+        // The captured dark piece is gone, so only the capturer's reveal is known
+        assert_eq!(infer_move(&start, &after(&start, "b2b9Rn")).unwrap().to_string(), "b2b9R");
+
+        let pos = Position::from_fen("3k5/9/9/9/4p4/9/9/9/4R4/4K4 w - - 0 1").unwrap();
+        assert_eq!(infer_move(&pos, &after(&pos, "e1e5")).unwrap().to_string(), "e1e5");
+    }
+
+    #[test]
+    fn unexplained_boards_give_no_move() {
+        let start = Position::startpos();
+        assert_eq!(infer_move(&start, &start.board), None);
+
+        // Two pieces moved at once
+        let mut board = after(&start, "h2e2C");
+        board.swap(square_from_uci("a3").unwrap(), square_from_uci("a4").unwrap());
+        assert_eq!(infer_move(&start, &board), None);
+
+        // A red dark piece cannot turn into a black one
+        let mut board = after(&start, "h2e2C");
+        board[square_from_uci("e2").unwrap()] = Some(Piece::Known('c'));
+        assert_eq!(infer_move(&start, &board), None);
+
+        // Not a legal move: a chariot jumping over a pawn
+        let pos = Position::from_fen("3k5/9/9/9/4p4/9/9/9/4R4/4K4 w - - 0 1").unwrap();
+        let mut board = pos.board;
+        board[square_from_uci("e8").unwrap()] = board[square_from_uci("e1").unwrap()].take();
+        assert_eq!(infer_move(&pos, &board), None);
+    }
+
+    #[test]
+    fn moves_need_stable_frames() {
+        let start = Position::startpos();
+        let next = moved(&start, "h2e2C");
+        let config = WatcherConfig { stable_frames: 2, ..Default::default() };
+        let mut watcher = BoardWatcher::new(Some(start.clone()), config);
+
+        assert!(watcher.observe(&recognition(&start, 1.0)).is_none());
+        // A one-frame flicker is ignored
+        assert!(watcher.observe(&recognition(&next, 1.0)).is_none());
+        assert!(watcher.observe(&recognition(&start, 1.0)).is_none());
+        assert!(watcher.observe(&recognition(&next, 1.0)).is_none());
+        // Too unsure to count, and it breaks the run
+        assert!(watcher.observe(&recognition(&next, 0.1)).is_none());
+        assert!(watcher.observe(&recognition(&next, 1.0)).is_none());
+        match watcher.observe(&recognition(&next, 1.0)) {
+            Some(WatchEvent::Move { uci, side, fen }) => {
+                assert_eq!((uci.as_str(), side.as_str()), ("h2e2C", "red"));
+                assert_eq!(fen, next.to_fen());
+            }
+            other => panic!("expected a move, got {:?}", other),
+        }
+        assert_eq!(watcher.position(), Some(&next));
+        assert!(watcher.observe(&recognition(&next, 1.0)).is_none());
+
+        let reply = moved(&next, "h9g7n");
+        let mut watcher = BoardWatcher::new(Some(next), WatcherConfig { stable_frames: 3, ..Default::default() });
+        assert!(watcher.observe(&recognition(&reply, 1.0)).is_none());
+        assert!(watcher.observe(&recognition(&reply, 1.0)).is_none());
+        assert!(matches!(watcher.observe(&recognition(&reply, 1.0)), Some(WatchEvent::Move { .. })));
+    }
+
+    #[test]
+    fn syncs_and_reports_unexplained_boards_once() {
+        let start = Position::startpos();
+        let mut watcher = BoardWatcher::new(None, WatcherConfig { stable_frames: 1, ..Default::default() });
+        assert!(matches!(watcher.observe(&recognition(&start, 1.0)), Some(WatchEvent::Synced { .. })));
+        assert_eq!(watcher.position(), Some(&start));
+
+        let mut jump = start.clone();
+        jump.board.swap(square_from_uci("a0").unwrap(), square_from_uci("a5").unwrap());
+        assert!(matches!(watcher.observe(&recognition(&jump, 1.0)), Some(WatchEvent::Unexplained { .. })));
+        assert!(watcher.observe(&recognition(&jump, 1.0)).is_none());
+        assert_eq!(watcher.position(), Some(&start));
+    }
+
+    // Fails `failures` times before each frame it has, then runs out
+    struct FlakySource {
+        failures: u32,
+        failed: u32,
+        frames: u32,
+    }
+
+    impl FrameSource for FlakySource {
+        fn next_frame(&mut self) -> Result<Option<RgbaImage>, String> {
+            if self.failed < self.failures {
+                self.failed += 1;
+                return Err("capture failed".to_string());
+            }
+            if self.frames == 0 {
+                return Ok(None);
+            }
+            self.failed = 0;
+            self.frames -= 1;
+            Ok(Some(RgbaImage::new(8, 8)))
+        }
+    }
+
+    #[test]
+    fn capture_failures_are_skipped() {
+        let config = WatcherConfig { interval: Duration::from_millis(1), ..Default::default() };
+        let watcher = Mutex::new(BoardWatcher::new(None, config));
+        let (pieces, recognition, stop) = (PieceSet::default(), RecognitionConfig::default(), AtomicBool::new(false));
+        let run = |source: &mut FlakySource| run_watcher(source, &pieces, &recognition, &watcher, &stop, |_| {});
+
+        let mut source = FlakySource { failures: MAX_FRAME_ERRORS - 1, failed: 0, frames: 3 };
+        assert_eq!(run(&mut source), Ok(()));
+        assert_eq!(source.frames, 0);
+
+        let mut source = FlakySource { failures: MAX_FRAME_ERRORS, failed: 0, frames: 3 };
+        assert_eq!(run(&mut source), Err("capture failed".to_string()));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::atomic_file::write_atomic;
+use crate::board_recognition::{locate_grid, recognize, BoardOrientation, BoardRect, PieceSet, RecognitionConfig};
+use crate::position::{square, square_from_uci, square_to_uci};
+use crate::screen_capture::CaptureRegion;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+// Mouse coordinates on the desktop
+pub type ScreenPoint = (i32, i32);
+
+/// Where an external board is on screen. The rectangle is in pixels of the monitor's capture;
+/// the monitor's desktop origin and scale factor turn those into mouse coordinates.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Calibration {
+    pub monitor: usize,
+    pub monitor_x: i32,
+    pub monitor_y: i32,
+    pub scale_factor: f32,
+    pub board_rect: BoardRect,
+    pub orientation: BoardOrientation,
+}
+
+impl Calibration {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Option<Calibration>, String> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read calibration: {}", e))?;
+        serde_json::from_str(&content).map(Some).map_err(|e| format!("Invalid calibration: {}", e))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        write_atomic(path.as_ref(), content.as_bytes()).map_err(|e| format!("Failed to write calibration: {}", e))
+    }
+
+    // Center of a square in capture pixels
+    pub fn square_center(&self, sq: usize) -> (f32, f32) {
+        let (row, col) = self.orientation.image_cell(sq);
+        self.board_rect.intersection(row, col)
+    }
+
+    /// Square whose intersection is nearest to a capture pixel, if it is within half the grid
+    /// spacing of it.
+    pub fn square_at(&self, x: f32, y: f32) -> Option<usize> {
+        let rect = &self.board_rect;
+        let col = ((x - rect.x) / (rect.width / 8.0)).round();
+        let row = ((y - rect.y) / (rect.height / 9.0)).round();
+        if !(0.0..=8.0).contains(&col) || !(0.0..=9.0).contains(&row) {
+            return None;
+        }
+        // image_cell is its own inverse, so it also maps a grid cell to its square
+        let sq = self.orientation.image_cell(square(row as usize, col as usize));
+        let sq = square(sq.0, sq.1);
+        let (cx, cy) = self.square_center(sq);
+        ((cx - x).hypot(cy - y) <= rect.spacing() / 2.0).then_some(sq)
+    }
+
+    pub fn to_screen(&self, (x, y): (f32, f32)) -> ScreenPoint {
+        let scale = if self.scale_factor > 0.0 { self.scale_factor } else { 1.0 };
+        (self.monitor_x + (x / scale).round() as i32, self.monitor_y + (y / scale).round() as i32)
+    }
+
+    pub fn from_screen(&self, x: i32, y: i32) -> (f32, f32) {
+        let scale = if self.scale_factor > 0.0 { self.scale_factor } else { 1.0 };
+        ((x - self.monitor_x) as f32 * scale, (y - self.monitor_y) as f32 * scale)
+    }
+
+    /// Mouse coordinates of a square given in UCI, e.g. `e3`.
+    pub fn square_to_screen(&self, uci: &str) -> Result<ScreenPoint, String> {
+        let sq = square_from_uci(uci).ok_or_else(|| format!("Invalid square: {}", uci))?;
+        Ok(self.to_screen(self.square_center(sq)))
+    }
+
+    pub fn screen_to_square(&self, x: i32, y: i32) -> Option<String> {
+        let (px, py) = self.from_screen(x, y);
+        self.square_at(px, py).map(square_to_uci)
+    }
+
+    /// Part of the monitor capture holding the board, with room around the edge pieces.
+    pub fn capture_region(&self) -> CaptureRegion {
+        let rect = &self.board_rect;
+        let margin = rect.spacing() * 0.6;
+        let x = (rect.x - margin).max(0.0).floor();
+        let y = (rect.y - margin).max(0.0).floor();
+        CaptureRegion {
+            x: x as u32,
+            y: y as u32,
+            width: (rect.x + rect.width + margin - x).ceil() as u32,
+            height: (rect.y + rect.height + margin - y).ceil() as u32,
+        }
+    }
+
+    // The board rectangle relative to a capture of `region`
+    pub fn board_rect_in(&self, region: &CaptureRegion) -> BoardRect {
+        BoardRect { x: self.board_rect.x - region.x as f32, y: self.board_rect.y - region.y as f32, ..self.board_rect }
+    }
+
+    /// Start and end mouse coordinates of a move such as `h2e2` (reveal letters are ignored).
+    pub fn move_to_screen(&self, uci: &str) -> Result<(ScreenPoint, ScreenPoint), String> {
+        if uci.len() < 4 || !uci.is_ascii() {
+            return Err(format!("Invalid move: {}", uci));
+        }
+        Ok((self.square_to_screen(&uci[0..2])?, self.square_to_screen(&uci[2..4])?))
+    }
+}
+
+/// Finds the board in a capture. With a piece set the orientation comes from where the kings
+/// are; without one, or when no king is found, red is assumed at the bottom.
+pub fn detect_board(image: &RgbaImage, pieces: Option<&PieceSet>) -> Result<(BoardRect, BoardOrientation), String> {
+    let rect = locate_grid(image).ok_or("Could not find the board grid in the capture")?;
+    let Some(pieces) = pieces else { return Ok((rect, BoardOrientation::RedBottom)) };
+
+    let config = RecognitionConfig { board_rect: Some(rect), ..Default::default() };
+    let recognition = recognize(image, pieces, &config)?;
+    // Kings never leave their palace, so a king on the wrong half means the board is flipped
+    let row_of = |king: char| recognition.squares.iter().position(|s| s.piece == Some(king)).map(|sq| sq / 9);
+    let orientation = match (row_of('K'), row_of('k')) {
+        (Some(row), _) if row <= 2 => BoardOrientation::RedTop,
+        (None, Some(row)) if row >= 7 => BoardOrientation::RedTop,
+        _ => BoardOrientation::RedBottom,
+    };
+    Ok((rect, orientation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+    use crate::test_boards::{piece_set, render, GRID};
+    use image::Rgba;
+
+    const FEN: &str = "3k5/9/9/9/9/9/9/9/4R4/4K4 w - - 0 1";
+    const ORIENTATIONS: [BoardOrientation; 2] = [BoardOrientation::RedBottom, BoardOrientation::RedTop];
+
+    // A 2x HiDPI monitor placed left of and above the primary one
+    fn calibration(orientation: BoardOrientation) -> Calibration {
+        Calibration { monitor: 1, monitor_x: -960, monitor_y: -540, scale_factor: 2.0, board_rect: GRID, orientation }
+    }
+
+    #[test]
+    fn squares_map_to_where_they_are_drawn() {
+        let position = Position::from_fen(FEN).unwrap();
+        for orientation in ORIENTATIONS {
+            let image = render(FEN, orientation);
+            let calibration = calibration(orientation);
+            for (sq, piece) in position.board.iter().enumerate() {
+                let (x, y) = calibration.square_center(sq);
+                assert_eq!(calibration.square_at(x, y), Some(sq));
+                // Piece faces are drawn on their square's intersection
+                if piece.is_some() {
+                    assert_eq!(*image.get_pixel(x as u32, y as u32), Rgba([245, 230, 200, 255]), "{}", sq);
+                }
+            }
+        }
+        let e0 = square_from_uci("e0").unwrap();
+        assert_eq!(calibration(BoardOrientation::RedBottom).square_center(e0), GRID.intersection(9, 4));
+        assert_eq!(calibration(BoardOrientation::RedTop).square_center(e0), GRID.intersection(0, 4));
+    }
+
+    #[test]
+    fn pixels_between_intersections_are_no_square() {
+        for orientation in ORIENTATIONS {
+            let calibration = calibration(orientation);
+            let (x, y) = GRID.intersection(4, 4);
+            let near = calibration.square_at(x + 20.0, y - 5.0);
+            assert_eq!(near, calibration.square_at(x, y));
+            assert_eq!(calibration.square_at(x + 22.0, y + 22.0), None);
+            assert_eq!(calibration.square_at(GRID.x - 30.0, GRID.y), None);
+            assert_eq!(calibration.square_at(GRID.x + GRID.width, GRID.y + GRID.height + 30.0), None);
+        }
+    }
+
+    #[test]
+    fn screen_points_round_trip_in_both_orientations() {
+        for orientation in ORIENTATIONS {
+            let calibration = calibration(orientation);
+            for sq in 0..90 {
+                let uci = square_to_uci(sq);
+                let (x, y) = calibration.square_to_screen(&uci).unwrap();
+                assert_eq!(calibration.screen_to_square(x, y).as_deref(), Some(uci.as_str()));
+            }
+        }
+        // Capture pixels are halved and offset by the monitor origin
+        let (from, to) = calibration(BoardOrientation::RedBottom).move_to_screen("e1e2").unwrap();
+        assert_eq!((from, to), ((-840, -320), (-840, -345)));
+        let (from, to) = calibration(BoardOrientation::RedTop).move_to_screen("e1e2").unwrap();
+        assert_eq!((from, to), ((-840, -495), (-840, -470)));
+        assert!(calibration(BoardOrientation::RedTop).move_to_screen("e1").is_err());
+    }
+
+    #[test]
+    fn detects_a_flipped_board_from_the_kings() {
+        let pieces = piece_set();
+        for orientation in ORIENTATIONS {
+            let (_, detected) = detect_board(&render(FEN, orientation), Some(&pieces)).unwrap();
+            assert_eq!(detected, orientation);
+        }
+    }
+}
@@ -0,0 +1,660 @@
+use crate::engine_options::EngineProtocol;
+use crate::engine_transport::{Connection, EngineOutput, EngineTransport, ReconnectConfig};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Same encoding of mate scores as the notation format (see NOTATION_FORMAT.md)
+pub const MATE_SCORE_BASE: i32 = 30000;
+
+// Handshake and options on a re-established connection
+const RECONNECT_SETUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum Score {
+    Cp(i32),
+    Mate(i32),
+}
+
+impl Score {
+    // Score as stored in `engineScore`, mate encoded around MATE_SCORE_BASE
+    pub fn to_engine_score(self) -> i32 {
+        match self {
+            Score::Cp(cp) => cp,
+            // `mate 0` means the side to move is already mated
+            Score::Mate(ply) if ply > 0 => MATE_SCORE_BASE - ply,
+            Score::Mate(ply) => -(MATE_SCORE_BASE + ply),
+        }
+    }
+
+    pub fn negate(self) -> Score {
+        match self {
+            Score::Cp(cp) => Score::Cp(-cp),
+            Score::Mate(ply) => Score::Mate(-ply),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InfoLine {
+    pub depth: u32,
+    pub seldepth: Option<u32>,
+    pub multipv: u32,
+    pub score: Option<Score>,
+    // "lowerbound" or "upperbound" when the score is not exact
+    pub bound: Option<String>,
+    pub nodes: Option<u64>,
+    pub time: Option<u64>,
+    pub pv: Vec<String>,
+}
+
+impl InfoLine {
+    // Parses an `info ...` line; lines without a depth (e.g. `info string`) are skipped
+    pub fn parse(line: &str) -> Option<InfoLine> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.first() != Some(&"info") || tokens.get(1) == Some(&"string") {
+            return None;
+        }
+        let mut info = InfoLine { multipv: 1, ..Default::default() };
+        let mut has_depth = false;
+        let mut i = 1;
+        while i < tokens.len() {
+            let next = tokens.get(i + 1).copied().unwrap_or("");
+            match tokens[i] {
+                "depth" => {
+                    info.depth = next.parse().ok()?;
+                    has_depth = true;
+                    i += 2;
+                }
+                "seldepth" => {
+                    info.seldepth = next.parse().ok();
+                    i += 2;
+                }
+                "multipv" => {
+                    info.multipv = next.parse().unwrap_or(1);
+                    i += 2;
+                }
+                "score" => {
+                    let value = tokens.get(i + 2).and_then(|v| v.parse().ok());
+                    info.score = match (next, value) {
+                        ("cp", Some(v)) => Some(Score::Cp(v)),
+                        ("mate", Some(v)) => Some(Score::Mate(v)),
+                        _ => None,
+                    };
+                    i += 3;
+                }
+                "lowerbound" | "upperbound" => {
+                    info.bound = Some(tokens[i].to_string());
+                    i += 1;
+                }
+                "nodes" => {
+                    info.nodes = next.parse().ok();
+                    i += 2;
+                }
+                "time" => {
+                    info.time = next.parse().ok();
+                    i += 2;
+                }
+                "pv" => {
+                    info.pv = tokens[i + 1..].iter().map(|s| s.to_string()).collect();
+                    break;
+                }
+                _ => i += 1,
+            }
+        }
+        if has_depth { Some(info) } else { None }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub movetime: Option<u64>,
+    pub nodes: Option<u64>,
+    // Clocks and increments in milliseconds; `w` is red and `b` is black
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+}
+
+impl SearchLimits {
+    fn parts(&self) -> Vec<String> {
+        let mut parts = Vec::new();
+        if let Some(depth) = self.depth {
+            parts.push(format!("depth {}", depth));
+        }
+        if let Some(movetime) = self.movetime {
+            parts.push(format!("movetime {}", movetime));
+        }
+        if let Some(nodes) = self.nodes {
+            parts.push(format!("nodes {}", nodes));
+        }
+        let clock = [("wtime", self.wtime), ("btime", self.btime), ("winc", self.winc), ("binc", self.binc)];
+        for (name, value) in clock {
+            if let Some(value) = value {
+                parts.push(format!("{} {}", name, value));
+            }
+        }
+        parts
+    }
+
+    pub fn go_command(&self) -> String {
+        let mut parts = self.parts();
+        if parts.is_empty() {
+            parts.push("infinite".to_string());
+        }
+        format!("go {}", parts.join(" "))
+    }
+
+    // A ponder search runs until `ponderhit` or `stop`, so it needs no limit of its own
+    pub fn ponder_command(&self) -> String {
+        std::iter::once("go ponder".to_string()).chain(self.parts()).collect::<Vec<_>>().join(" ")
+    }
+
+    // Wall-clock limit for a search: none for depth/nodes, movetime or the clock plus slack otherwise
+    pub fn timeout(&self) -> Option<Duration> {
+        self.movetime.or(self.wtime.max(self.btime)).map(|ms| Duration::from_millis(ms + 5000))
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub best_move: String,
+    pub ponder: Option<String>,
+    // Last info line of the principal variation
+    pub info: Option<InfoLine>,
+    // Last info line for every MultiPV index, ordered by index
+    pub lines: Vec<InfoLine>,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngineSpec {
+    pub name: String,
+    pub path: String,
+    pub args: Vec<String>,
+    pub options: Vec<(String, String)>,
+    #[serde(default)]
+    pub protocol: EngineProtocol,
+    #[serde(default)]
+    pub encoding: EngineEncoding,
+    #[serde(default)]
+    pub transport: EngineTransport,
+    #[serde(default)]
+    pub reconnect: ReconnectConfig,
+}
+
+// Encoding of the engine's output; `Auto` is GBK on Windows and UTF-8 elsewhere
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EngineEncoding {
+    #[default]
+    Auto,
+    Utf8,
+    Gbk,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EngineId {
+    pub name: String,
+    pub author: String,
+}
+
+/// A UCI engine driven over one of the engine transports without the Tauri shell plugin, for
+/// headless use. A remote engine whose connection drops is reconnected and set up again; the
+/// command that was waiting on it fails.
+pub struct EngineClient {
+    connection: Connection,
+    lines: Receiver<EngineOutput>,
+    // Kept for the connections opened when reconnecting
+    sender: Sender<EngineOutput>,
+    spec: EngineSpec,
+    reconnecting: bool,
+    // Predicted move of the running ponder search
+    pondering: Option<String>,
+    pub id: EngineId,
+}
+
+impl EngineClient {
+    pub fn spawn(path: &str, args: &[String]) -> Result<Self, String> {
+        EngineClient::spawn_with_encoding(path, args, EngineEncoding::Auto)
+    }
+
+    pub fn spawn_with_encoding(path: &str, args: &[String], encoding: EngineEncoding) -> Result<Self, String> {
+        EngineClient::connect(&EngineSpec {
+            name: String::new(),
+            path: path.to_string(),
+            args: args.to_vec(),
+            options: Vec::new(),
+            protocol: EngineProtocol::Uci,
+            encoding,
+            transport: EngineTransport::Local,
+            reconnect: ReconnectConfig::default(),
+        })
+    }
+
+    // Opens the connection without any handshake
+    pub fn connect(spec: &EngineSpec) -> Result<Self, String> {
+        let (tx, rx) = mpsc::channel();
+        let connection = Connection::open(&spec.transport, &spec.path, &spec.args, spec.encoding, &tx)?;
+        Ok(EngineClient {
+            connection,
+            lines: rx,
+            sender: tx,
+            spec: spec.clone(),
+            reconnecting: false,
+            pondering: None,
+            id: EngineId::default(),
+        })
+    }
+
+    // Connects to the engine, completes the handshake and applies the configured options
+    pub fn start(spec: &EngineSpec, timeout: Duration) -> Result<Self, String> {
+        let mut engine = EngineClient::connect(spec)?;
+        engine.set_up(timeout)?;
+        Ok(engine)
+    }
+
+    fn set_up(&mut self, timeout: Duration) -> Result<(), String> {
+        self.handshake(timeout)?;
+        for (name, value) in self.spec.options.clone() {
+            self.set_option(&name, &value)?;
+        }
+        self.is_ready(timeout)
+    }
+
+    pub fn send(&mut self, command: &str) -> Result<(), String> {
+        self.connection.send(command)
+    }
+
+    // Next output line, or None on timeout. Errors once the engine has exited or its
+    // connection dropped, after reconnecting a remote engine.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<String>, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(EngineOutput::Line(line)) => return Ok(Some(line)),
+                // Left over from a connection already replaced
+                Ok(EngineOutput::Closed(id)) if id != self.connection.id() => {}
+                Ok(EngineOutput::Closed(_)) => return Err(self.connection_lost()),
+                Err(RecvTimeoutError::Timeout) => return Ok(None),
+                Err(RecvTimeoutError::Disconnected) => return Err("Engine process exited".to_string()),
+            }
+        }
+    }
+
+    // The error for the command that was waiting when the connection dropped
+    fn connection_lost(&mut self) -> String {
+        if !self.spec.transport.is_remote() {
+            return "Engine process exited".to_string();
+        }
+        if self.reconnecting {
+            return "Lost the connection to the engine while reconnecting".to_string();
+        }
+        self.reconnecting = true;
+        let result = self.reconnect();
+        self.reconnecting = false;
+        match result {
+            Ok(()) => "Lost the connection to the engine; reconnected".to_string(),
+            Err(e) => format!("Lost the connection to the engine: {}", e),
+        }
+    }
+
+    fn reconnect(&mut self) -> Result<(), String> {
+        self.pondering = None;
+        let mut last_error = "no attempts configured".to_string();
+        for _ in 0..self.spec.reconnect.attempts {
+            thread::sleep(Duration::from_millis(self.spec.reconnect.delay_ms));
+            let spec = &self.spec;
+            match Connection::open(&spec.transport, &spec.path, &spec.args, spec.encoding, &self.sender) {
+                Ok(connection) => {
+                    self.connection.close();
+                    self.connection = connection;
+                    match self.set_up(RECONNECT_SETUP_TIMEOUT) {
+                        Ok(()) => return Ok(()),
+                        Err(e) => last_error = e,
+                    }
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+
+    // Reads lines until one satisfies `done`, failing after `timeout`
+    fn wait_for<F: FnMut(&str) -> bool>(&mut self, timeout: Duration, what: &str, mut done: F) -> Result<(), String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(format!("Timed out waiting for '{}'", what));
+            }
+            if let Some(line) = self.recv_timeout(remaining)? {
+                if done(line.trim()) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    // `uci`/`uciok` or `jai`/`jaiok`, depending on the spec's protocol
+    pub fn handshake(&mut self, timeout: Duration) -> Result<(), String> {
+        let (command, done) = self.spec.protocol.handshake();
+        self.send(command)?;
+        let mut id = EngineId::default();
+        self.wait_for(timeout, done, |line| {
+            if let Some(name) = line.strip_prefix("id name ") {
+                id.name = name.to_string();
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                id.author = author.to_string();
+            }
+            line == done
+        })?;
+        self.id = id;
+        Ok(())
+    }
+
+    pub fn is_ready(&mut self, timeout: Duration) -> Result<(), String> {
+        self.send("isready")?;
+        self.wait_for(timeout, "readyok", |line| line == "readyok")
+    }
+
+    pub fn set_option(&mut self, name: &str, value: &str) -> Result<(), String> {
+        self.send(&format!("setoption name {} value {}", name, value))
+    }
+
+    pub fn new_game(&mut self, timeout: Duration) -> Result<(), String> {
+        self.send("ucinewgame")?;
+        self.is_ready(timeout)
+    }
+
+    // Searches `fen` and waits for bestmove, sending `stop` past `timeout`
+    pub fn search(&mut self, fen: &str, limits: &SearchLimits, timeout: Option<Duration>) -> Result<SearchResult, String> {
+        self.send(&format!("position fen {}", fen))?;
+        self.send(&limits.go_command())?;
+        self.read_search(Instant::now(), timeout, None)
+    }
+
+    /// Analyzes `fen` after `moves` from a fresh game, like `search`, but stops early once
+    /// `cancel` is set and returns what the engine found so far.
+    pub fn analyze(
+        &mut self,
+        fen: &str,
+        moves: &[String],
+        limits: &SearchLimits,
+        timeout: Option<Duration>,
+        cancel: &AtomicBool,
+    ) -> Result<SearchResult, String> {
+        self.new_game(Duration::from_secs(10))?;
+        if moves.is_empty() {
+            self.send(&format!("position fen {}", fen))?;
+        } else {
+            self.send(&format!("position fen {} moves {}", fen, moves.join(" ")))?;
+        }
+        self.send(&limits.go_command())?;
+        self.read_search(Instant::now(), timeout, Some(cancel))
+    }
+
+    /// Searches `fen` with the predicted reply `ponder_move` played, while the opponent thinks.
+    /// The search runs until `ponder_hit` or `stop_ponder`.
+    pub fn start_ponder(&mut self, fen: &str, ponder_move: &str, limits: &SearchLimits) -> Result<(), String> {
+        self.send(&format!("position fen {} moves {}", fen, ponder_move))?;
+        self.send(&limits.ponder_command())?;
+        self.pondering = Some(ponder_move.to_string());
+        Ok(())
+    }
+
+    pub fn pondering(&self) -> Option<&str> {
+        self.pondering.as_deref()
+    }
+
+    /// The opponent played the predicted move: the ponder search goes on as a normal one.
+    /// `elapsed_ms` counts from the ponderhit, which is the time to charge to the clock.
+    pub fn ponder_hit(&mut self, timeout: Option<Duration>) -> Result<SearchResult, String> {
+        if self.pondering.take().is_none() {
+            return Err("Engine is not pondering".to_string());
+        }
+        self.send("ponderhit")?;
+        self.read_search(Instant::now(), timeout, None)
+    }
+
+    // Stops the ponder search and throws its result away
+    pub fn stop_ponder(&mut self) -> Result<(), String> {
+        if self.pondering.take().is_none() {
+            return Ok(());
+        }
+        self.send("stop")?;
+        self.wait_for(Duration::from_secs(5), "bestmove", |line| line.starts_with("bestmove"))
+    }
+
+    // Collects info lines until bestmove. `stop` is sent past `timeout` or once `cancel` is set,
+    // and the engine then gets a short grace period to answer.
+    fn read_search(
+        &mut self,
+        start: Instant,
+        timeout: Option<Duration>,
+        cancel: Option<&AtomicBool>,
+    ) -> Result<SearchResult, String> {
+        let mut result = SearchResult::default();
+        let mut stopped: Option<Instant> = None;
+        loop {
+            let cancelled = cancel.is_some_and(|c| c.load(Ordering::SeqCst));
+            let timed_out = timeout.is_some_and(|limit| start.elapsed() >= limit);
+            if stopped.is_none() && (cancelled || timed_out) {
+                self.send("stop")?;
+                stopped = Some(Instant::now());
+            }
+            let mut wait = match timeout {
+                Some(limit) if stopped.is_none() => limit.saturating_sub(start.elapsed()),
+                _ => Duration::from_millis(500),
+            };
+            if cancel.is_some() && stopped.is_none() {
+                wait = wait.min(Duration::from_millis(50));
+            }
+            let line = match self.recv_timeout(wait.max(Duration::from_millis(1)))? {
+                Some(line) => line,
+                None => {
+                    if stopped.is_some_and(|at| at.elapsed() > Duration::from_secs(5)) {
+                        return Err("Engine did not answer stop with bestmove".to_string());
+                    }
+                    continue;
+                }
+            };
+            let line = line.trim();
+            if let Some(info) = InfoLine::parse(line) {
+                if info.pv.is_empty() {
+                    continue;
+                }
+                let index = info.multipv.max(1) as usize;
+                if result.lines.len() < index {
+                    result.lines.resize(index, InfoLine::default());
+                }
+                if index == 1 {
+                    result.info = Some(info.clone());
+                }
+                result.lines[index - 1] = info;
+            } else if let Some(rest) = line.strip_prefix("bestmove") {
+                let mut tokens = rest.split_whitespace();
+                result.best_move = tokens.next().unwrap_or("").to_string();
+                if tokens.next() == Some("ponder") {
+                    result.ponder = tokens.next().map(|s| s.to_string());
+                }
+                result.lines.retain(|l| !l.pv.is_empty());
+                result.elapsed_ms = start.elapsed().as_millis() as u64;
+                return Ok(result);
+            }
+        }
+    }
+
+    pub fn quit(mut self) {
+        let _ = self.send("quit");
+        self.connection.finish(Duration::from_secs(2));
+    }
+}
+
+/// The searches auto-play and engine matches need, so a scripted engine can stand in for a
+/// real one in tests.
+pub trait PlayingEngine {
+    fn search(&mut self, fen: &str, limits: &SearchLimits, timeout: Option<Duration>) -> Result<SearchResult, String>;
+    fn start_ponder(&mut self, fen: &str, ponder_move: &str, limits: &SearchLimits) -> Result<(), String>;
+    fn pondering(&self) -> Option<&str>;
+    fn ponder_hit(&mut self, timeout: Option<Duration>) -> Result<SearchResult, String>;
+    fn stop_ponder(&mut self) -> Result<(), String>;
+}
+
+impl PlayingEngine for EngineClient {
+    fn search(&mut self, fen: &str, limits: &SearchLimits, timeout: Option<Duration>) -> Result<SearchResult, String> {
+        EngineClient::search(self, fen, limits, timeout)
+    }
+
+    fn start_ponder(&mut self, fen: &str, ponder_move: &str, limits: &SearchLimits) -> Result<(), String> {
+        EngineClient::start_ponder(self, fen, ponder_move, limits)
+    }
+
+    fn pondering(&self) -> Option<&str> {
+        EngineClient::pondering(self)
+    }
+
+    fn ponder_hit(&mut self, timeout: Option<Duration>) -> Result<SearchResult, String> {
+        EngineClient::ponder_hit(self, timeout)
+    }
+
+    fn stop_ponder(&mut self) -> Result<(), String> {
+        EngineClient::stop_ponder(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::START_FEN;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    // An engine served over TCP that answers each command with `reply` (nothing when empty) and
+    // keeps the commands it got
+    fn stub(reply: fn(&str) -> &'static str) -> (EngineClient, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let commands = received.clone();
+        thread::spawn(move || {
+            let Ok((stream, _)) = listener.accept() else { return };
+            let mut out = stream.try_clone().unwrap();
+            for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                commands.lock().unwrap().push(line.clone());
+                let text = reply(&line);
+                if !text.is_empty() && writeln!(out, "{}", text).is_err() {
+                    break;
+                }
+            }
+        });
+        let spec = EngineSpec {
+            name: "stub".to_string(),
+            path: String::new(),
+            args: Vec::new(),
+            options: Vec::new(),
+            protocol: EngineProtocol::Uci,
+            encoding: EngineEncoding::Auto,
+            transport: EngineTransport::Tcp { address },
+            reconnect: ReconnectConfig { attempts: 0, delay_ms: 0 },
+        };
+        (EngineClient::connect(&spec).unwrap(), received)
+    }
+
+    // Reports one line at go and only answers `stop` with bestmove
+    fn until_stopped(command: &str) -> &'static str {
+        match command.split_whitespace().next() {
+            Some("isready") => "readyok",
+            Some("go") => "info depth 1 score cp 7 pv h2e2",
+            Some("stop") => "bestmove h2e2 ponder h9g7",
+            _ => "",
+        }
+    }
+
+    #[test]
+    fn info_lines_are_parsed() {
+        let line = "info depth 12 seldepth 18 multipv 2 score cp -35 upperbound nodes 123456 time 789 pv h2e2 h9g7";
+        let info = InfoLine::parse(line).unwrap();
+        assert_eq!(
+            info,
+            InfoLine {
+                depth: 12,
+                seldepth: Some(18),
+                multipv: 2,
+                score: Some(Score::Cp(-35)),
+                bound: Some("upperbound".to_string()),
+                nodes: Some(123456),
+                time: Some(789),
+                pv: vec!["h2e2".to_string(), "h9g7".to_string()],
+            }
+        );
+        let mate = InfoLine::parse("info depth 5 currmove a0a1 score mate -3 pv a0a1").unwrap();
+        assert_eq!((mate.multipv, mate.score), (1, Some(Score::Mate(-3))));
+        assert_eq!(Score::Mate(-3).to_engine_score(), -(MATE_SCORE_BASE - 3));
+        assert_eq!(Score::Mate(2).negate().to_engine_score(), -(MATE_SCORE_BASE - 2));
+
+        for line in ["info string depth 3", "info nodes 100 pv h2e2", "info depth x", "bestmove h2e2"] {
+            assert_eq!(InfoLine::parse(line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn multipv_lines_are_collected_by_index() {
+        let (mut engine, _) = stub(|command| match command.split_whitespace().next() {
+            Some("go") => concat!(
+                "info depth 1 multipv 1 score cp 10 pv h2e2\n",
+                "info depth 1 multipv 2 score cp 5 pv b2e2\n",
+                "info depth 2 multipv 2 score cp 3 pv b2e2 h9g7\n",
+                "info depth 2 multipv 1 score cp 12 pv h2e2 h9g7\n",
+                "info depth 2 multipv 3 score cp -1\n",
+                "info depth 1 multipv 4 score cp -50 pv a0a1\n",
+                "info string done\n",
+                "bestmove h2e2 ponder h9g7"
+            ),
+            _ => "",
+        });
+        let limits = SearchLimits { depth: Some(2), ..Default::default() };
+        let result = engine.search(START_FEN, &limits, Some(Duration::from_secs(5))).unwrap();
+        assert_eq!((result.best_move.as_str(), result.ponder.as_deref()), ("h2e2", Some("h9g7")));
+
+        // Index 3 never had a PV and is left out
+        let lines: Vec<_> = result.lines.iter().map(|l| (l.multipv, l.depth, l.score)).collect();
+        assert_eq!(lines, [(1, 2, Some(Score::Cp(12))), (2, 2, Some(Score::Cp(3))), (4, 1, Some(Score::Cp(-50)))]);
+        assert_eq!(result.info.as_ref(), Some(&result.lines[0]));
+    }
+
+    #[test]
+    fn timed_out_search_is_stopped() {
+        let (mut engine, received) = stub(until_stopped);
+        let result = engine.search(START_FEN, &SearchLimits::default(), Some(Duration::from_millis(100))).unwrap();
+        assert_eq!(result.best_move, "h2e2");
+        assert_eq!(result.info.map(|i| i.depth), Some(1));
+        assert!(result.elapsed_ms >= 100, "{}", result.elapsed_ms);
+        let received = received.lock().unwrap();
+        assert_eq!(received[1..], ["go infinite", "stop"]);
+    }
+
+    #[test]
+    fn cancelled_analysis_is_stopped() {
+        let (mut engine, received) = stub(until_stopped);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let canceller = cancel.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            canceller.store(true, Ordering::SeqCst);
+        });
+        let limits = SearchLimits { depth: Some(30), ..Default::default() };
+        let moves = vec!["h2e2".to_string()];
+        let result = engine.analyze(START_FEN, &moves, &limits, None, &cancel).unwrap();
+        assert_eq!((result.best_move.as_str(), result.lines.len()), ("h2e2", 1));
+
+        let position = format!("position fen {} moves h2e2", START_FEN);
+        assert_eq!(*received.lock().unwrap(), ["ucinewgame", "isready", &position, "go depth 30", "stop"]);
+    }
+}
@@ -0,0 +1,201 @@
+use crate::engine_options::{EngineInfo, EngineOption, EngineProtocol};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    // Quiet time after which `isready` is sent to an idle engine
+    pub ping_interval_ms: u64,
+    // Time `readyok` may take before the engine counts as unresponsive
+    pub response_timeout_ms: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig { ping_interval_ms: 10000, response_timeout_ms: 5000 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthAction {
+    // Send `isready`
+    Ping,
+    // No `readyok` for that long; reported once until the engine answers again
+    Unresponsive { waited_ms: u64 },
+}
+
+/// Follows the commands sent to an engine and the lines it prints: its identity from the
+/// handshake, whether it is searching, and whether it still answers `isready`.
+pub struct EngineMonitor {
+    info: EngineInfo,
+    handshake_done: bool,
+    searching: bool,
+    last_activity: Instant,
+    // Unanswered `isready` commands, oldest first; true for the monitor's own pings
+    pending: VecDeque<(bool, Instant)>,
+    unresponsive: bool,
+}
+
+impl EngineMonitor {
+    pub fn new(protocol: EngineProtocol) -> Self {
+        EngineMonitor {
+            info: EngineInfo { name: String::new(), author: String::new(), protocol, options: Vec::new() },
+            handshake_done: false,
+            searching: false,
+            last_activity: Instant::now(),
+            pending: VecDeque::new(),
+            unresponsive: false,
+        }
+    }
+
+    // Set once the handshake has completed
+    pub fn info(&self) -> Option<&EngineInfo> {
+        self.handshake_done.then_some(&self.info)
+    }
+
+    pub fn on_command(&mut self, command: &str) {
+        let (handshake, _) = self.info.protocol.handshake();
+        for line in command.lines().map(str::trim) {
+            if line == handshake {
+                // The options are listed again
+                self.info.options.clear();
+            } else if line == "isready" {
+                self.pending.push_back((false, Instant::now()));
+            } else if line == "go" || line.starts_with("go ") {
+                self.searching = true;
+            }
+        }
+        self.last_activity = Instant::now();
+    }
+
+    /// Takes in one output line. Returns false for a `readyok` answering the monitor's own
+    /// ping, which the UI did not ask for.
+    pub fn on_output(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        let (_, done) = self.info.protocol.handshake();
+        self.last_activity = Instant::now();
+        if line == done {
+            self.handshake_done = true;
+        } else if line == "readyok" {
+            self.unresponsive = false;
+            return !self.pending.pop_front().is_some_and(|(own, _)| own);
+        } else if line.starts_with("bestmove") {
+            self.searching = false;
+        } else if let Some(name) = line.strip_prefix("id name ") {
+            self.info.name = name.to_string();
+        } else if let Some(author) = line.strip_prefix("id author ") {
+            self.info.author = author.to_string();
+        } else if let Some(option) = EngineOption::parse(line) {
+            self.info.options.push(option);
+        }
+        true
+    }
+
+    /// Called regularly; says when to ping an idle engine and when one stopped answering.
+    pub fn tick(&mut self, config: &HealthConfig) -> Option<HealthAction> {
+        if let Some((_, sent)) = self.pending.front() {
+            let waited = sent.elapsed();
+            if !self.unresponsive && waited >= Duration::from_millis(config.response_timeout_ms) {
+                self.unresponsive = true;
+                return Some(HealthAction::Unresponsive { waited_ms: waited.as_millis() as u64 });
+            }
+            return None;
+        }
+        let idle = self.handshake_done && !self.searching;
+        if idle && self.last_activity.elapsed() >= Duration::from_millis(config.ping_interval_ms) {
+            self.pending.push_back((true, Instant::now()));
+            self.last_activity = Instant::now();
+            return Some(HealthAction::Ping);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    const CONFIG: HealthConfig = HealthConfig { ping_interval_ms: 20, response_timeout_ms: 40 };
+
+    fn wait(ms: u64) {
+        thread::sleep(Duration::from_millis(ms));
+    }
+
+    fn ready_monitor(protocol: EngineProtocol) -> EngineMonitor {
+        let mut monitor = EngineMonitor::new(protocol);
+        let (handshake, done) = protocol.handshake();
+        monitor.on_command(handshake);
+        for line in ["id name Pikafish", "option name Hash type spin default 16 min 1 max 1024", done] {
+            assert!(monitor.on_output(line));
+        }
+        monitor
+    }
+
+    #[test]
+    fn idle_engines_are_pinged_after_the_interval() {
+        let mut monitor = EngineMonitor::new(EngineProtocol::Uci);
+        wait(25);
+        // Not before the handshake
+        assert_eq!(monitor.tick(&CONFIG), None);
+        assert!(monitor.info().is_none());
+
+        let mut monitor = ready_monitor(EngineProtocol::Uci);
+        let info = monitor.info().unwrap();
+        assert_eq!((info.name.as_str(), info.options.len()), ("Pikafish", 1));
+        assert_eq!(monitor.tick(&CONFIG), None);
+        wait(25);
+        assert_eq!(monitor.tick(&CONFIG), Some(HealthAction::Ping));
+        assert_eq!(monitor.tick(&CONFIG), None);
+        assert!(!monitor.on_output("readyok"));
+
+        // Nor while searching
+        monitor.on_command("position startpos\ngo depth 20");
+        wait(25);
+        assert_eq!(monitor.tick(&CONFIG), None);
+        monitor.on_output("bestmove h2e2");
+        wait(25);
+        assert_eq!(monitor.tick(&CONFIG), Some(HealthAction::Ping));
+    }
+
+    #[test]
+    fn unanswered_ping_is_reported_once() {
+        let mut monitor = ready_monitor(EngineProtocol::Jai);
+        wait(25);
+        assert_eq!(monitor.tick(&CONFIG), Some(HealthAction::Ping));
+        wait(45);
+        let Some(HealthAction::Unresponsive { waited_ms }) = monitor.tick(&CONFIG) else { panic!("not reported") };
+        assert!(waited_ms >= 40, "{}", waited_ms);
+        wait(10);
+        assert_eq!(monitor.tick(&CONFIG), None);
+
+        // A late answer ends it, and pinging starts over
+        assert!(!monitor.on_output("readyok"));
+        wait(25);
+        assert_eq!(monitor.tick(&CONFIG), Some(HealthAction::Ping));
+    }
+
+    #[test]
+    fn only_the_monitors_own_readyok_is_filtered() {
+        let mut monitor = ready_monitor(EngineProtocol::Uci);
+        monitor.on_command("isready");
+        assert!(monitor.on_output("readyok"));
+
+        wait(25);
+        assert_eq!(monitor.tick(&CONFIG), Some(HealthAction::Ping));
+        // The UI asks too while the ping is out; answers come back in order
+        monitor.on_command("isready");
+        assert!(!monitor.on_output("readyok"));
+        assert!(monitor.on_output("readyok"));
+        // One the monitor was not waiting for is passed on
+        assert!(monitor.on_output("readyok"));
+
+        // A repeated handshake lists the options again
+        monitor.on_command("uci");
+        monitor.on_output("option name Threads type spin default 1 min 1 max 64");
+        let options: Vec<&str> = monitor.info().unwrap().options.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(options, ["Threads"]);
+    }
+}
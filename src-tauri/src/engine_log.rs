@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineLogConfig {
+    // Log file name; the engine's file name when empty
+    pub name: String,
+    // Size at which the file is rotated
+    pub max_bytes: u64,
+    // Rotated files kept besides the current one
+    pub max_files: usize,
+}
+
+impl Default for EngineLogConfig {
+    fn default() -> Self {
+        EngineLogConfig { name: String::new(), max_bytes: 1024 * 1024, max_files: 5 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Send,
+    Receive,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EngineLogFile {
+    pub file_name: String,
+    pub engine: String,
+    pub size: u64,
+    pub modified: Option<String>,
+}
+
+// Keeps log names usable as file names on every platform
+fn sanitize(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if name.is_empty() { "engine".to_string() } else { name }
+}
+
+/// Timestamped engine I/O in `<dir>/<name>.log`, rotated to `<name>.1.log`, `<name>.2.log`, ...
+/// once it reaches `max_bytes`. Lines look like `2024-01-01 12:00:00.000 > go depth 10`, with
+/// `>` for lines sent to the engine and `<` for lines received.
+pub struct EngineLogger {
+    dir: PathBuf,
+    name: String,
+    config: EngineLogConfig,
+    file: Option<File>,
+    size: u64,
+}
+
+impl EngineLogger {
+    pub fn new<P: AsRef<Path>>(dir: P, engine_path: &str, config: EngineLogConfig) -> Result<Self, String> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let name = if config.name.is_empty() {
+            Path::new(engine_path).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default()
+        } else {
+            config.name.clone()
+        };
+        Ok(EngineLogger { dir, name: sanitize(&name), config, file: None, size: 0 })
+    }
+
+    fn path(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self.dir.join(format!("{}.log", self.name)),
+            _ => self.dir.join(format!("{}.{}.log", self.name, index)),
+        }
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file = None;
+        let _ = fs::remove_file(self.path(self.config.max_files));
+        for index in (0..self.config.max_files).rev() {
+            if self.path(index).exists() {
+                fs::rename(self.path(index), self.path(index + 1))?;
+            }
+        }
+        if self.config.max_files == 0 {
+            let _ = fs::remove_file(self.path(0));
+        }
+        Ok(())
+    }
+
+    fn open(&mut self) -> std::io::Result<&mut File> {
+        if self.file.is_none() {
+            let file = OpenOptions::new().create(true).append(true).open(self.path(0))?;
+            self.size = file.metadata()?.len();
+            self.file = Some(file);
+        }
+        Ok(self.file.as_mut().unwrap())
+    }
+
+    /// Appends each line of `text`; engine output may arrive several lines at a time.
+    pub fn log(&mut self, direction: Direction, text: &str) -> Result<(), String> {
+        let marker = if direction == Direction::Send { '>' } else { '<' };
+        let stamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            let entry = format!("{} {} {}\n", stamp, marker, line.trim_end());
+            self.open().map_err(|e| format!("Failed to open engine log: {}", e))?;
+            if self.size > 0 && self.size + entry.len() as u64 > self.config.max_bytes {
+                self.rotate().map_err(|e| format!("Failed to rotate engine log: {}", e))?;
+            }
+            let file = self.open().map_err(|e| format!("Failed to open engine log: {}", e))?;
+            file.write_all(entry.as_bytes()).map_err(|e| format!("Failed to write engine log: {}", e))?;
+            self.size += entry.len() as u64;
+        }
+        Ok(())
+    }
+}
+
+/// Log files in `dir`, most recently written first.
+pub fn list_logs<P: AsRef<Path>>(dir: P) -> Vec<EngineLogFile> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    let mut files: Vec<(std::time::SystemTime, EngineLogFile)> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().extension().is_some_and(|x| x == "log"))
+        .map(|e| {
+            let file_name = e.file_name().to_string_lossy().to_string();
+            // "name.3.log" belongs to "name"
+            let stem = file_name.trim_end_matches(".log");
+            let engine = match stem.rsplit_once('.') {
+                Some((engine, index)) if index.parse::<usize>().is_ok() => engine,
+                _ => stem,
+            };
+            let metadata = e.metadata().ok();
+            let modified = metadata.as_ref().and_then(|m| m.modified().ok()).unwrap_or(std::time::UNIX_EPOCH);
+            let info = EngineLogFile {
+                engine: engine.to_string(),
+                size: metadata.map(|m| m.len()).unwrap_or(0),
+                modified: Some(chrono::DateTime::<chrono::Local>::from(modified).to_rfc3339()),
+                file_name,
+            };
+            (modified, info)
+        })
+        .collect();
+    files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    files.into_iter().map(|(_, info)| info).collect()
+}
+
+/// The content of one log file, or only its last `max_lines` lines.
+pub fn read_log<P: AsRef<Path>>(dir: P, file_name: &str, max_lines: Option<usize>) -> Result<String, String> {
+    if file_name.contains(['/', '\\']) || !file_name.ends_with(".log") {
+        return Err(format!("Invalid log name: {}", file_name));
+    }
+    let path = dir.as_ref().join(file_name);
+    let file = File::open(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let lines: Vec<String> = BufReader::new(file)
+        .lines()
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let skip = max_lines.map(|max| lines.len().saturating_sub(max)).unwrap_or(0);
+    Ok(lines[skip..].iter().map(|l| format!("{}\n", l)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jieqibox-engine-log-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = list_logs(dir).into_iter().map(|f| f.file_name).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn logs_are_rotated_by_size() {
+        let dir = log_dir("rotate");
+        // Every entry is 40 bytes: a 23 byte stamp, the marker and a 14 byte line
+        let config = EngineLogConfig { name: String::new(), max_bytes: 100, max_files: 2 };
+        let mut logger = EngineLogger::new(&dir, "/engines/pika fish.exe", config).unwrap();
+        for n in 0..7 {
+            logger.log(Direction::Send, &format!("go depth {:05}", n)).unwrap();
+        }
+        // Two entries per file; the oldest ones were dropped with the third rotation
+        assert_eq!(file_names(&dir), ["pika_fish.1.log", "pika_fish.2.log", "pika_fish.log"]);
+        assert!(list_logs(&dir).iter().all(|f| f.engine == "pika_fish" && f.size <= 100));
+        let current = read_log(&dir, "pika_fish.log", None).unwrap();
+        assert_eq!(current.lines().count(), 1);
+        assert!(current.ends_with(" > go depth 00006\n"), "{}", current);
+        assert!(read_log(&dir, "pika_fish.2.log", None).unwrap().contains("go depth 00002"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn read_log_returns_the_last_lines() {
+        let dir = log_dir("tail");
+        let mut logger = EngineLogger::new(&dir, "engine", EngineLogConfig::default()).unwrap();
+        logger.log(Direction::Send, "uci").unwrap();
+        // Several lines at once, with blank ones and CRLF endings
+        let output = "id name Test\r\n\noption name Hash type spin default 16 min 1 max 64\nuciok";
+        logger.log(Direction::Receive, output).unwrap();
+
+        let all = read_log(&dir, "engine.log", None).unwrap();
+        let markers: Vec<&str> = all.lines().map(|l| l.split(' ').nth(2).unwrap()).collect();
+        assert_eq!(markers, [">", "<", "<", "<"]);
+        let tail = read_log(&dir, "engine.log", Some(2)).unwrap();
+        let lines: Vec<&str> = tail.lines().map(|l| l.split_once(" < ").unwrap().1).collect();
+        assert_eq!(lines, ["option name Hash type spin default 16 min 1 max 64", "uciok"]);
+        assert_eq!(read_log(&dir, "engine.log", Some(10)).unwrap(), all);
+        assert_eq!(read_log(&dir, "engine.log", Some(0)).unwrap(), "");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn read_log_stays_in_the_log_directory() {
+        let dir = log_dir("names");
+        EngineLogger::new(&dir, "engine", EngineLogConfig::default()).unwrap().log(Direction::Send, "uci").unwrap();
+        for name in ["../engine.log", "sub/engine.log", "sub\\engine.log", "engine.txt"] {
+            assert!(read_log(&dir, name, None).unwrap_err().starts_with("Invalid log name"), "{}", name);
+        }
+        assert!(read_log(&dir, "missing.log", None).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
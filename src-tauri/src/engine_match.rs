@@ -0,0 +1,172 @@
+use crate::engine::{EngineClient, EngineSpec, SearchLimits};
+use crate::notation::{GameNotation, NotationMetadata, NotationMove};
+use crate::ponder::{search_after, start_pondering, PonderConfig};
+use crate::position::{JieqiMove, Position, Rng, Side, START_FEN};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchConfig {
+    pub games: u32,
+    pub start_fen: String,
+    pub limits: SearchLimits,
+    // Games longer than this are adjudicated as draws
+    pub max_plies: u32,
+    pub seed: Option<u64>,
+    // Clocks for both sides, sent along with `limits`; running out of time loses
+    #[serde(default)]
+    pub time_control: Option<TimeControl>,
+    #[serde(default)]
+    pub ponder: PonderConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub base_ms: u64,
+    pub increment_ms: u64,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        MatchConfig {
+            games: 2,
+            start_fen: START_FEN.to_string(),
+            limits: SearchLimits { movetime: Some(1000), ..Default::default() },
+            max_plies: 300,
+            seed: None,
+            time_control: None,
+            ponder: PonderConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MatchScore {
+    // Counted from the first engine's point of view
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Plays one game; `red` and `black` index into `engines`. The runner acts as the arbiter and
+/// draws revealed pieces from the dark pools, like random flip mode in the GUI.
+pub fn play_game(
+    engines: &mut [EngineClient; 2],
+    names: [&str; 2],
+    red: usize,
+    config: &MatchConfig,
+    rng: &mut Rng,
+) -> Result<GameNotation, String> {
+    let mut position = Position::from_fen(&config.start_fen)?;
+    let mut moves = Vec::new();
+    for engine in engines.iter_mut() {
+        engine.new_game(HANDSHAKE_TIMEOUT)?;
+    }
+    // Remaining time of red and black
+    let mut clocks = config.time_control.as_ref().map(|tc| [tc.base_ms; 2]);
+    let mut last_move = None;
+
+    let result = loop {
+        if let Some(result) = position.game_result() {
+            break result;
+        }
+        if moves.len() as u32 >= config.max_plies {
+            break "1/2-1/2";
+        }
+
+        let side = position.side_to_move;
+        let mover = if side == Side::Red { red } else { 1 - red };
+        let limits = clock_limits(&config.limits, config.time_control.as_ref(), clocks);
+        let (search, _) = search_after(&mut engines[mover], &position, last_move.as_ref(), &limits)?;
+        let lost = if side == Side::Red { "0-1" } else { "1-0" };
+        if let (Some(clocks), Some(tc)) = (clocks.as_mut(), config.time_control.as_ref()) {
+            let clock = &mut clocks[if side == Side::Red { 0 } else { 1 }];
+            if search.elapsed_ms > *clock {
+                break lost;
+            }
+            *clock = *clock - search.elapsed_ms + tc.increment_ms;
+        }
+        let bare = match JieqiMove::parse(&search.best_move, side) {
+            Ok(mv) if position.is_legal(mv.from, mv.to) => mv,
+            // An illegal or missing move forfeits the game
+            _ => break lost,
+        };
+        let mv = position.complete_random(&bare, rng)?;
+        position.apply(&mv)?;
+        // The engine ponders on the opponent's time with the clocks as they are now
+        let limits = clock_limits(&config.limits, config.time_control.as_ref(), clocks);
+        start_pondering(&mut engines[mover], &position, &search, &limits, &config.ponder)?;
+
+        moves.push(NotationMove {
+            kind: "move".to_string(),
+            data: mv.to_string(),
+            fen: position.to_fen(),
+            engine_score: search.info.as_ref().and_then(|i| i.score).map(|s| s.to_engine_score() as f64),
+            engine_time: Some(search.elapsed_ms as f64),
+            ..Default::default()
+        });
+        last_move = Some(mv);
+    };
+    for engine in engines.iter_mut() {
+        engine.stop_ponder()?;
+    }
+
+    Ok(GameNotation {
+        metadata: NotationMetadata {
+            event: Some("Engine match".to_string()),
+            site: Some("jieqibox".to_string()),
+            date: Some(chrono::Local::now().format("%Y-%m-%d").to_string()),
+            white: Some(names[red].to_string()),
+            black: Some(names[1 - red].to_string()),
+            result: Some(result.to_string()),
+            initial_fen: Some(config.start_fen.clone()),
+            flip_mode: Some("random".to_string()),
+            current_fen: Some(position.to_fen()),
+            ..Default::default()
+        },
+        moves,
+    })
+}
+
+fn clock_limits(limits: &SearchLimits, time_control: Option<&TimeControl>, clocks: Option<[u64; 2]>) -> SearchLimits {
+    let mut limits = limits.clone();
+    if let (Some(tc), Some([red, black])) = (time_control, clocks) {
+        limits.wtime = Some(red);
+        limits.btime = Some(black);
+        limits.winc = Some(tc.increment_ms);
+        limits.binc = Some(tc.increment_ms);
+    }
+    limits
+}
+
+/// Runs a match, alternating colors every game. `on_game` receives each finished game
+/// with its index and the running score.
+pub fn run_match<F>(specs: [&EngineSpec; 2], config: &MatchConfig, mut on_game: F) -> Result<MatchScore, String>
+where
+    F: FnMut(u32, &GameNotation, &MatchScore) -> Result<(), String>,
+{
+    let mut engines = [EngineClient::start(specs[0], HANDSHAKE_TIMEOUT)?, EngineClient::start(specs[1], HANDSHAKE_TIMEOUT)?];
+    let names = [specs[0].name.as_str(), specs[1].name.as_str()];
+    let mut rng = config.seed.map(Rng::new).unwrap_or_else(Rng::from_time);
+    let mut score = MatchScore::default();
+
+    for game_index in 0..config.games {
+        let red = (game_index % 2) as usize;
+        let game = play_game(&mut engines, names, red, config, &mut rng)?;
+        let first_is_red = red == 0;
+        match game.metadata.result.as_deref() {
+            Some("1-0") if first_is_red => score.wins += 1,
+            Some("0-1") if !first_is_red => score.wins += 1,
+            Some("1-0") | Some("0-1") => score.losses += 1,
+            _ => score.draws += 1,
+        }
+        on_game(game_index, &game, &score)?;
+    }
+
+    let [first, second] = engines;
+    first.quit();
+    second.quit();
+    Ok(score)
+}
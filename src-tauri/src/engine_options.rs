@@ -0,0 +1,274 @@
+use crate::engine::{EngineClient, EngineId, EngineSpec};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EngineProtocol {
+    #[default]
+    Uci,
+    Jai,
+}
+
+impl EngineProtocol {
+    // Command starting the handshake and the line ending it
+    pub fn handshake(self) -> (&'static str, &'static str) {
+        match self {
+            EngineProtocol::Uci => ("uci", "uciok"),
+            EngineProtocol::Jai => ("jai", "jaiok"),
+        }
+    }
+}
+
+// Field names follow UciOption in UciOptionsDialog.vue
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum OptionKind {
+    Spin {
+        #[serde(rename = "defaultValue")]
+        default: i64,
+        min: i64,
+        max: i64,
+    },
+    Check {
+        #[serde(rename = "defaultValue")]
+        default: bool,
+    },
+    Combo {
+        #[serde(rename = "defaultValue")]
+        default: String,
+        vars: Vec<String>,
+    },
+    String {
+        #[serde(rename = "defaultValue")]
+        default: String,
+    },
+    Button,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngineOption {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: OptionKind,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EngineInfo {
+    pub name: String,
+    pub author: String,
+    pub protocol: EngineProtocol,
+    pub options: Vec<EngineOption>,
+}
+
+const OPTION_KEYWORDS: [&str; 6] = ["name", "type", "default", "min", "max", "var"];
+
+impl EngineOption {
+    /// Parses an `option name ... type ...` line. Names and values may contain spaces;
+    /// `<empty>` stands for an empty string default.
+    pub fn parse(line: &str) -> Option<EngineOption> {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("option") {
+            return None;
+        }
+
+        // Group the words following each keyword
+        let mut fields: Vec<(&str, Vec<&str>)> = Vec::new();
+        for token in tokens {
+            match fields.last_mut() {
+                Some((key, words)) if !OPTION_KEYWORDS.contains(&token) || (*key == "name" && token != "type") => {
+                    words.push(token)
+                }
+                _ if OPTION_KEYWORDS.contains(&token) => fields.push((token, Vec::new())),
+                _ => return None,
+            }
+        }
+        let value = |key: &str| {
+            fields.iter().find(|(k, _)| *k == key).map(|(_, words)| {
+                let joined = words.join(" ");
+                if joined == "<empty>" { String::new() } else { joined }
+            })
+        };
+
+        let name = value("name").filter(|n| !n.is_empty())?;
+        let default = value("default");
+        let kind = match value("type")?.as_str() {
+            "spin" => {
+                let number = |key: &str| value(key).and_then(|v| v.parse::<i64>().ok());
+                let min = number("min").unwrap_or(i64::MIN);
+                let max = number("max").unwrap_or(i64::MAX);
+                OptionKind::Spin { default: number("default").unwrap_or(min.max(0).min(max)), min, max }
+            }
+            "check" => OptionKind::Check { default: default.as_deref() == Some("true") },
+            "combo" => OptionKind::Combo {
+                default: default.unwrap_or_default(),
+                vars: fields.iter().filter(|(k, _)| *k == "var").map(|(_, words)| words.join(" ")).collect(),
+            },
+            "string" => OptionKind::String { default: default.unwrap_or_default() },
+            "button" => OptionKind::Button,
+            _ => return None,
+        };
+        Some(EngineOption { name, kind })
+    }
+
+    /// Checks `value` against the option's type and returns it in the form to send with
+    /// `setoption`. Buttons take no value.
+    pub fn validate(&self, value: &str) -> Result<String, String> {
+        let value = value.trim();
+        match &self.kind {
+            OptionKind::Spin { min, max, .. } => {
+                let number = value
+                    .parse::<i64>()
+                    .map_err(|_| format!("{}: '{}' is not an integer", self.name, value))?;
+                if number < *min || number > *max {
+                    return Err(format!("{}: {} is outside {}..={}", self.name, number, min, max));
+                }
+                Ok(number.to_string())
+            }
+            OptionKind::Check { .. } => match value.to_ascii_lowercase().as_str() {
+                "true" => Ok("true".to_string()),
+                "false" => Ok("false".to_string()),
+                _ => Err(format!("{}: expected true or false, got '{}'", self.name, value)),
+            },
+            // Engines compare combo values case-insensitively; send the spelling they announced
+            OptionKind::Combo { vars, .. } => vars
+                .iter()
+                .find(|v| v.eq_ignore_ascii_case(value))
+                .cloned()
+                .ok_or_else(|| format!("{}: '{}' is not one of {}", self.name, value, vars.join(", "))),
+            OptionKind::String { .. } => {
+                if value.contains('\n') || value.contains('\r') {
+                    return Err(format!("{}: value must be a single line", self.name));
+                }
+                Ok(value.to_string())
+            }
+            OptionKind::Button => Err(format!("{} is a button and takes no value", self.name)),
+        }
+    }
+
+    // The `setoption` command for `value`, or a bare one for buttons
+    pub fn setoption_command(&self, value: Option<&str>) -> Result<String, String> {
+        match (&self.kind, value) {
+            (OptionKind::Button, _) => Ok(format!("setoption name {}", self.name)),
+            (_, Some(value)) => Ok(format!("setoption name {} value {}", self.name, self.validate(value)?)),
+            (_, None) => Err(format!("{} needs a value", self.name)),
+        }
+    }
+}
+
+/// Validates `(name, value)` pairs against a discovered option list, returning the
+/// normalized values or one message per invalid or unknown option.
+pub fn validate_option_values(options: &[EngineOption], values: &[(String, String)]) -> Result<Vec<(String, String)>, Vec<String>> {
+    let mut valid = Vec::new();
+    let mut errors = Vec::new();
+    for (name, value) in values {
+        // Option names are case-insensitive in UCI
+        match options.iter().find(|o| o.name.eq_ignore_ascii_case(name)) {
+            Some(option) => match option.validate(value) {
+                Ok(value) => valid.push((option.name.clone(), value)),
+                Err(e) => errors.push(e),
+            },
+            None => errors.push(format!("Unknown option '{}'", name)),
+        }
+    }
+    if errors.is_empty() { Ok(valid) } else { Err(errors) }
+}
+
+/// Starts or connects to the engine, runs the handshake of its protocol and collects its
+/// identity and options, then shuts it down again. The spec's own options are not applied.
+pub fn discover_options(spec: &EngineSpec, timeout: Duration) -> Result<EngineInfo, String> {
+    let mut engine = EngineClient::connect(spec)?;
+    let protocol = spec.protocol;
+    let (command, done) = protocol.handshake();
+    engine.send(command)?;
+
+    let mut id = EngineId::default();
+    let mut options = Vec::new();
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(format!("Timed out waiting for '{}'", done));
+        }
+        let Some(line) = engine.recv_timeout(remaining)? else { continue };
+        let line = line.trim();
+        if line == done {
+            break;
+        } else if let Some(name) = line.strip_prefix("id name ") {
+            id.name = name.to_string();
+        } else if let Some(author) = line.strip_prefix("id author ") {
+            id.author = author.to_string();
+        } else if let Some(option) = EngineOption::parse(line) {
+            options.push(option);
+        }
+    }
+
+    engine.quit();
+    Ok(EngineInfo { name: id.name, author: id.author, protocol, options })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> EngineOption {
+        EngineOption::parse(line).unwrap()
+    }
+
+    #[test]
+    fn names_and_values_keep_their_spaces() {
+        let option = parse("option name Skill Level type spin default 20 min 0 max 20");
+        assert_eq!(option.name, "Skill Level");
+        assert_eq!(option.kind, OptionKind::Spin { default: 20, min: 0, max: 20 });
+        assert_eq!(parse("option name Clear Hash type button").kind, OptionKind::Button);
+        assert_eq!(
+            parse("option name EvalFile type string default <empty>").kind,
+            OptionKind::String { default: String::new() }
+        );
+        assert_eq!(
+            parse("option name Book File type string default books/main book.bin").kind,
+            OptionKind::String { default: "books/main book.bin".to_string() }
+        );
+
+        assert!(EngineOption::parse("info depth 1").is_none());
+        assert!(EngineOption::parse("option name Threads type slider default 1").is_none());
+        assert!(EngineOption::parse("option type check default true").is_none());
+    }
+
+    #[test]
+    fn combo_values_come_from_the_var_list() {
+        let option = parse("option name Play Style type combo default Normal var Solid var Normal var Risky Play");
+        let vars = vec!["Solid".to_string(), "Normal".to_string(), "Risky Play".to_string()];
+        assert_eq!(option.kind, OptionKind::Combo { default: "Normal".to_string(), vars });
+        assert_eq!(option.validate("risky play").unwrap(), "Risky Play");
+        assert!(option.validate("Wild").is_err());
+    }
+
+    #[test]
+    fn values_are_checked_against_the_type() {
+        let spin = parse("option name Threads type spin default 1 min 1 max 64");
+        assert_eq!(spin.validate(" 8 ").unwrap(), "8");
+        for value in ["0", "65", "eight"] {
+            assert!(spin.validate(value).is_err(), "{}", value);
+        }
+        let check = parse("option name Ponder type check default false");
+        assert_eq!(check.kind, OptionKind::Check { default: false });
+        assert_eq!(check.validate("TRUE").unwrap(), "true");
+        assert!(check.validate("yes").is_err());
+        assert!(parse("option name UCI_Opponent type string").validate("a\nb").is_err());
+
+        let button = parse("option name Clear Hash type button");
+        assert_eq!(button.setoption_command(None).unwrap(), "setoption name Clear Hash");
+        assert_eq!(spin.setoption_command(Some("4")).unwrap(), "setoption name Threads value 4");
+        assert!(spin.setoption_command(None).is_err());
+    }
+
+    #[test]
+    fn option_values_are_matched_by_name() {
+        let options = [parse("option name Hash type spin default 16 min 1 max 1024")];
+        let values = vec![("hash".to_string(), "64".to_string())];
+        assert_eq!(validate_option_values(&options, &values).unwrap(), [("Hash".to_string(), "64".to_string())]);
+        let values = vec![("Hash".to_string(), "0".to_string()), ("Threads".to_string(), "2".to_string())];
+        assert_eq!(validate_option_values(&options, &values).unwrap_err().len(), 2);
+    }
+}
@@ -0,0 +1,354 @@
+use crate::atomic_file::write_atomic;
+use crate::engine::{EngineEncoding, EngineSpec};
+use crate::engine_options::EngineProtocol;
+use crate::engine_transport::{EngineTransport, ReconnectConfig};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+// UCI option engines read their NNUE network from
+const NNUE_OPTION: &str = "EvalFile";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewEngine {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub protocol: EngineProtocol,
+    #[serde(default)]
+    pub options: Vec<(String, String)>,
+    #[serde(default)]
+    pub nnue: Option<String>,
+    #[serde(default)]
+    pub encoding: EngineEncoding,
+    #[serde(default)]
+    pub transport: EngineTransport,
+    #[serde(default)]
+    pub reconnect: ReconnectConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineEntry {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub protocol: EngineProtocol,
+    // Default options sent after the handshake
+    #[serde(default)]
+    pub options: Vec<(String, String)>,
+    #[serde(default)]
+    pub nnue: Option<String>,
+    #[serde(default)]
+    pub encoding: EngineEncoding,
+    #[serde(default)]
+    pub transport: EngineTransport,
+    #[serde(default)]
+    pub reconnect: ReconnectConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EngineHealth {
+    pub id: String,
+    pub path_exists: bool,
+    pub executable: bool,
+    // None when no NNUE file is configured
+    pub nnue_exists: Option<bool>,
+}
+
+impl EngineHealth {
+    pub fn is_ok(&self) -> bool {
+        self.path_exists && self.executable && self.nnue_exists != Some(false)
+    }
+}
+
+impl EngineEntry {
+    // The NNUE file is passed as EvalFile unless the options already set it
+    pub fn to_spec(&self) -> EngineSpec {
+        let mut options = self.options.clone();
+        if let Some(nnue) = &self.nnue {
+            if !options.iter().any(|(name, _)| name.eq_ignore_ascii_case(NNUE_OPTION)) {
+                options.push((NNUE_OPTION.to_string(), nnue.clone()));
+            }
+        }
+        EngineSpec {
+            name: self.name.clone(),
+            path: self.path.clone(),
+            args: self.args.clone(),
+            options,
+            protocol: self.protocol,
+            encoding: self.encoding,
+            transport: self.transport.clone(),
+            reconnect: self.reconnect.clone(),
+        }
+    }
+
+    // The executable of a remote engine is not on this machine and is not checked
+    pub fn check(&self) -> EngineHealth {
+        let path = Path::new(&self.path);
+        let remote = self.transport.is_remote();
+        EngineHealth {
+            id: self.id.clone(),
+            path_exists: remote || path.is_file(),
+            executable: remote || is_executable(path),
+            nnue_exists: self.nnue.as_ref().map(|nnue| Path::new(nnue).is_file()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0).unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Managed engines with stable IDs, stored as JSON next to the other app data.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EngineRegistry {
+    pub engines: Vec<EngineEntry>,
+}
+
+impl EngineRegistry {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(EngineRegistry::default());
+        }
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read engine registry: {}", e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Invalid engine registry: {}", e))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        write_atomic(path, content.as_bytes()).map_err(|e| format!("Failed to write engine registry: {}", e))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&EngineEntry> {
+        self.engines.iter().find(|e| e.id == id)
+    }
+
+    // Looks up by ID first, then by display name
+    pub fn find(&self, id_or_name: &str) -> Option<&EngineEntry> {
+        self.get(id_or_name).or_else(|| self.engines.iter().find(|e| e.name == id_or_name))
+    }
+
+    pub fn add(&mut self, engine: NewEngine) -> Result<EngineEntry, String> {
+        validate_engine(&engine.name, &engine.path, &engine.transport)?;
+        // IDs are derived from the creation time and never reused
+        let base = format!("engine-{:x}", chrono::Utc::now().timestamp_millis());
+        let mut id = base.clone();
+        let mut suffix = 1;
+        while self.get(&id).is_some() {
+            suffix += 1;
+            id = format!("{}-{}", base, suffix);
+        }
+
+        let entry = EngineEntry {
+            id,
+            name: engine.name.trim().to_string(),
+            path: stored_path(engine.path, &engine.transport),
+            args: engine.args,
+            protocol: engine.protocol,
+            options: engine.options,
+            nnue: engine.nnue.filter(|n| !n.is_empty()),
+            encoding: engine.encoding,
+            transport: engine.transport,
+            reconnect: engine.reconnect,
+        };
+        self.engines.push(entry.clone());
+        Ok(entry)
+    }
+
+    pub fn update(&mut self, mut engine: EngineEntry) -> Result<(), String> {
+        validate_engine(&engine.name, &engine.path, &engine.transport)?;
+        engine.path = stored_path(engine.path, &engine.transport);
+        let slot = self
+            .engines
+            .iter_mut()
+            .find(|e| e.id == engine.id)
+            .ok_or_else(|| format!("No engine with id '{}'", engine.id))?;
+        *slot = engine;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<EngineEntry, String> {
+        let index = self
+            .engines
+            .iter()
+            .position(|e| e.id == id)
+            .ok_or_else(|| format!("No engine with id '{}'", id))?;
+        Ok(self.engines.remove(index))
+    }
+
+    /// Imports the `[Engines] list` JSON kept in the old config.ini, keeping the frontend's IDs.
+    /// Entries whose ID is already registered are skipped. Returns the number imported.
+    pub fn import_legacy_list(&mut self, list_json: &str) -> Result<usize, String> {
+        #[derive(Deserialize)]
+        struct ManagedEngine {
+            id: String,
+            name: String,
+            path: String,
+            #[serde(default)]
+            args: String,
+        }
+
+        let engines: Vec<ManagedEngine> =
+            serde_json::from_str(list_json).map_err(|e| format!("Invalid legacy engine list: {}", e))?;
+        let mut imported = 0;
+        for engine in engines {
+            if self.get(&engine.id).is_some() {
+                continue;
+            }
+            self.engines.push(EngineEntry {
+                id: engine.id,
+                name: engine.name,
+                path: engine.path,
+                args: engine.args.split_whitespace().map(|s| s.to_string()).collect(),
+                protocol: EngineProtocol::default(),
+                options: Vec::new(),
+                nnue: None,
+                encoding: EngineEncoding::default(),
+                transport: EngineTransport::Local,
+                reconnect: ReconnectConfig::default(),
+            });
+            imported += 1;
+        }
+        Ok(imported)
+    }
+
+    pub fn check_all(&self) -> Vec<EngineHealth> {
+        self.engines.iter().map(|e| e.check()).collect()
+    }
+}
+
+// Local executables are stored absolute so the entry does not depend on the working directory
+fn stored_path(path: String, transport: &EngineTransport) -> String {
+    match transport {
+        EngineTransport::Local => fs::canonicalize(&path).map(|p| p.to_string_lossy().to_string()).unwrap_or(path),
+        _ => path,
+    }
+}
+
+fn validate_engine(name: &str, path: &str, transport: &EngineTransport) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Engine name must not be empty".to_string());
+    }
+    if !transport.is_remote() && !Path::new(path).is_file() {
+        return Err(format!("Engine executable not found: {}", path));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // A directory with an `engine` file to register
+    fn engine_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jieqibox-registry-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("engine"), "").unwrap();
+        dir
+    }
+
+    fn new_engine(name: &str, path: &Path) -> NewEngine {
+        NewEngine {
+            name: name.to_string(),
+            path: path.to_string_lossy().to_string(),
+            args: Vec::new(),
+            protocol: EngineProtocol::Uci,
+            options: Vec::new(),
+            nnue: None,
+            encoding: EngineEncoding::Auto,
+            transport: EngineTransport::Local,
+            reconnect: ReconnectConfig::default(),
+        }
+    }
+
+    #[test]
+    fn engines_are_added_updated_and_removed() {
+        let dir = engine_dir("edit");
+        let absolute = fs::canonicalize(dir.join("engine")).unwrap().to_string_lossy().to_string();
+        let mut registry = EngineRegistry::default();
+        let first = registry.add(new_engine(" First ", &dir.join(".").join("engine"))).unwrap();
+        let second = registry.add(new_engine("Second", &dir.join("engine"))).unwrap();
+        assert_ne!(first.id, second.id);
+        assert_eq!((first.name.as_str(), first.path.as_str()), ("First", absolute.as_str()));
+        assert!(registry.add(new_engine(" ", &dir.join("engine"))).is_err());
+        assert!(registry.add(new_engine("Missing", &dir.join("missing"))).is_err());
+
+        let mut edited = first.clone();
+        edited.name = "Renamed".to_string();
+        edited.path = dir.join(".").join("engine").to_string_lossy().to_string();
+        registry.update(edited).unwrap();
+        let stored = registry.find("Renamed").unwrap();
+        assert_eq!((stored.id.as_str(), stored.path.as_str()), (first.id.as_str(), absolute.as_str()));
+        let unknown = EngineEntry { id: "nope".to_string(), ..stored.clone() };
+        assert!(registry.update(unknown).is_err());
+
+        let path = dir.join("engines.json");
+        registry.save(&path).unwrap();
+        let mut loaded = EngineRegistry::load(&path).unwrap();
+        assert_eq!(loaded.remove(&second.id).unwrap().name, "Second");
+        assert!(loaded.remove(&second.id).is_err());
+        assert_eq!(loaded.engines.len(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn nnue_file_is_passed_as_eval_file() {
+        let dir = engine_dir("nnue");
+        let mut registry = EngineRegistry::default();
+        let mut engine = new_engine("Engine", &dir.join("engine"));
+        engine.nnue = Some("net.nnue".to_string());
+        engine.options = vec![("Hash".to_string(), "64".to_string())];
+        let mut entry = registry.add(engine).unwrap();
+        let options = entry.to_spec().options;
+        assert_eq!(options, [("Hash".to_string(), "64".to_string()), ("EvalFile".to_string(), "net.nnue".to_string())]);
+
+        // An EvalFile option of its own wins
+        entry.options.push(("evalfile".to_string(), "other.nnue".to_string()));
+        assert_eq!(entry.to_spec().options.len(), 2);
+        assert_eq!(entry.check().nnue_exists, Some(false));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn remote_engines_are_not_looked_for_locally() {
+        let mut registry = EngineRegistry::default();
+        let mut engine = new_engine("Remote", Path::new("gpu-box engine"));
+        engine.transport = EngineTransport::Tcp { address: "10.0.0.5:4000".to_string() };
+        let entry = registry.add(engine).unwrap();
+        assert_eq!(entry.path, "gpu-box engine");
+        let health = entry.check();
+        assert!(health.is_ok());
+
+        let local = EngineEntry { transport: EngineTransport::Local, ..entry };
+        assert!(!local.check().is_ok());
+    }
+
+    #[test]
+    fn legacy_engines_keep_their_ids() {
+        let mut registry = EngineRegistry::default();
+        let list = r#"[{"id":"a", "name":"A", "path":"/a", "args":"-t 2"}, {"id":"b", "name":"B", "path":"/b"}]"#;
+        assert_eq!(registry.import_legacy_list(list).unwrap(), 2);
+        assert_eq!(registry.get("a").unwrap().args, ["-t", "2"]);
+        // Imported again, nothing is duplicated
+        let list = r#"[{"id": "b", "name": "B2", "path": "/b"}, {"id": "c", "name": "C", "path": "/c"}]"#;
+        assert_eq!(registry.import_legacy_list(list).unwrap(), 1);
+        assert_eq!(registry.get("b").unwrap().name, "B");
+        assert_eq!(registry.engines.len(), 3);
+        assert!(registry.import_legacy_list("{}").is_err());
+    }
+}
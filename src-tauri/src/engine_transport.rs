@@ -0,0 +1,303 @@
+use crate::engine::EngineEncoding;
+use encoding_rs::GBK;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+static NEXT_CONNECTION: AtomicU64 = AtomicU64::new(1);
+
+/// How the engine is reached. Whatever the transport, commands are written as lines and the
+/// engine's output is read back as lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EngineTransport {
+    // The engine's executable, started in its own directory
+    #[default]
+    Local,
+    // An engine served on `host:port`, e.g. by a stdio-to-TCP bridge; the engine path is only a label
+    Tcp { address: String },
+    // `program args...` followed by the engine path and arguments, e.g. `ssh host` runs
+    // `ssh host ./engine`. The engine protocol is spoken over the command's stdio.
+    Command { program: String, args: Vec<String> },
+}
+
+impl EngineTransport {
+    // The engine runs elsewhere and a lost connection may be re-established
+    pub fn is_remote(&self) -> bool {
+        *self != EngineTransport::Local
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectConfig {
+    // Attempts after a remote engine's connection drops; 0 gives up at once
+    pub attempts: u32,
+    // Wait before each attempt
+    pub delay_ms: u64,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        ReconnectConfig { attempts: 3, delay_ms: 1000 }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineOutput {
+    Line(String),
+    // The output of that connection ended: the process exited or the socket was closed
+    Closed(u64),
+}
+
+// Engine output in the given encoding, invalid sequences replaced
+pub fn decode(buf: &[u8], encoding: EngineEncoding) -> String {
+    let gbk = match encoding {
+        EngineEncoding::Auto => cfg!(target_os = "windows"),
+        EngineEncoding::Utf8 => false,
+        EngineEncoding::Gbk => true,
+    };
+    if gbk {
+        let (cow, ..) = GBK.decode(buf);
+        cow.into_owned()
+    } else {
+        String::from_utf8_lossy(buf).into_owned()
+    }
+}
+
+fn decode_line(buf: &[u8], encoding: EngineEncoding) -> String {
+    decode(buf, encoding).trim_end_matches(['\r', '\n']).to_string()
+}
+
+// `closes` is the connection reported as closed at the end of this stream
+fn forward_lines<R: Read + Send + 'static>(
+    source: R,
+    tx: Sender<EngineOutput>,
+    encoding: EngineEncoding,
+    closes: Option<u64>,
+) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(source);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    if tx.send(EngineOutput::Line(decode_line(&buf, encoding))).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+        if let Some(id) = closes {
+            let _ = tx.send(EngineOutput::Closed(id));
+        }
+    });
+}
+
+/// One connection to an engine. Its output lines are sent to the channel given to `open`,
+/// followed by `Closed` with the connection's ID once the output ends.
+pub struct Connection {
+    id: u64,
+    writer: Box<dyn Write + Send>,
+    child: Option<Child>,
+    socket: Option<TcpStream>,
+}
+
+impl Connection {
+    pub fn open(
+        transport: &EngineTransport,
+        path: &str,
+        args: &[String],
+        encoding: EngineEncoding,
+        tx: &Sender<EngineOutput>,
+    ) -> Result<Self, String> {
+        let id = NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed);
+        match transport {
+            EngineTransport::Local => {
+                // Resolve relative paths before changing the working directory; bare names go through PATH
+                let program = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+                let mut command = Command::new(&program);
+                command.args(args);
+                // Engines look for their NNUE and book files next to the executable
+                if let Some(dir) = program.parent().filter(|d| !d.as_os_str().is_empty()) {
+                    command.current_dir(dir);
+                }
+                Connection::spawn(id, command, encoding, tx)
+            }
+            EngineTransport::Command { program, args: wrapper_args } => {
+                let mut command = Command::new(program);
+                command.args(wrapper_args);
+                if !path.is_empty() {
+                    command.arg(path);
+                }
+                command.args(args);
+                Connection::spawn(id, command, encoding, tx)
+            }
+            EngineTransport::Tcp { address } => {
+                let socket = connect(address)?;
+                let _ = socket.set_nodelay(true);
+                let reader = socket.try_clone().map_err(|e| format!("Failed to read from {}: {}", address, e))?;
+                let writer = socket.try_clone().map_err(|e| format!("Failed to write to {}: {}", address, e))?;
+                forward_lines(reader, tx.clone(), encoding, Some(id));
+                Ok(Connection { id, writer: Box::new(writer), child: None, socket: Some(socket) })
+            }
+        }
+    }
+
+    fn spawn(
+        id: u64,
+        mut command: Command,
+        encoding: EngineEncoding,
+        tx: &Sender<EngineOutput>,
+    ) -> Result<Self, String> {
+        command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        let mut child = command.spawn().map_err(|e| format!("Failed to spawn engine: {}", e))?;
+
+        let stdin = child.stdin.take().ok_or("Failed to open engine stdin")?;
+        let stdout = child.stdout.take().ok_or("Failed to open engine stdout")?;
+        let stderr = child.stderr.take().ok_or("Failed to open engine stderr")?;
+        forward_lines(stdout, tx.clone(), encoding, Some(id));
+        forward_lines(stderr, tx.clone(), encoding, None);
+        Ok(Connection { id, writer: Box::new(stdin), child: Some(child), socket: None })
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn send(&mut self, command: &str) -> Result<(), String> {
+        self.writer
+            .write_all(format!("{}\n", command).as_bytes())
+            .and_then(|_| self.writer.flush())
+            .map_err(|e| format!("Failed to write to engine: {}", e))
+    }
+
+    /// Waits up to `grace` for a process to exit by itself (e.g. after `quit`), then closes.
+    pub fn finish(&mut self, grace: Duration) {
+        if let Some(child) = self.child.as_mut() {
+            let deadline = Instant::now() + grace;
+            while Instant::now() < deadline {
+                if let Ok(Some(_)) = child.try_wait() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+        }
+        self.close();
+    }
+
+    // Kills a process still running; shuts a socket down
+    pub fn close(&mut self) {
+        if let Some(child) = self.child.as_mut() {
+            if let Ok(None) = child.try_wait() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+        }
+        if let Some(socket) = &self.socket {
+            let _ = socket.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+fn connect(address: &str) -> Result<TcpStream, String> {
+    let addrs = address.to_socket_addrs().map_err(|e| format!("Invalid engine address '{}': {}", address, e))?;
+    let mut last_error = format!("No address found for '{}'", address);
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(socket) => return Ok(socket),
+            Err(e) => last_error = format!("Failed to connect to {}: {}", address, e),
+        }
+    }
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{EngineClient, EngineSpec, SearchLimits};
+    use crate::position::START_FEN;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    // A minimal UCI engine served over TCP, one connection at a time, keeping the commands each
+    // connection got. The first connection is dropped when asked to search.
+    fn serve_stub(listener: TcpListener, received: Arc<Mutex<Vec<Vec<String>>>>) {
+        thread::spawn(move || {
+            for (n, stream) in listener.incoming().enumerate() {
+                let Ok(stream) = stream else { return };
+                let mut out = stream.try_clone().unwrap();
+                received.lock().unwrap().push(Vec::new());
+                for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                    received.lock().unwrap()[n].push(line.clone());
+                    let reply = match line.split_whitespace().next() {
+                        Some("uci") => "id name Stub\nuciok",
+                        Some("isready") => "readyok",
+                        Some("go") if n == 0 => {
+                            let _ = out.shutdown(Shutdown::Both);
+                            break;
+                        }
+                        Some("go") => "info depth 1 score cp 12 pv h2e2\nbestmove h2e2",
+                        _ => continue,
+                    };
+                    if writeln!(out, "{}", reply).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn tcp_engine_is_set_up_again_after_the_connection_drops() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        serve_stub(listener, received.clone());
+
+        let spec = EngineSpec {
+            name: "remote".to_string(),
+            path: String::new(),
+            args: Vec::new(),
+            options: vec![("Hash".to_string(), "16".to_string())],
+            protocol: Default::default(),
+            encoding: EngineEncoding::Auto,
+            transport: EngineTransport::Tcp { address },
+            reconnect: ReconnectConfig { attempts: 3, delay_ms: 10 },
+        };
+        let limits = SearchLimits { depth: Some(1), ..Default::default() };
+        let timeout = Some(Duration::from_secs(5));
+        let mut engine = EngineClient::start(&spec, Duration::from_secs(5)).unwrap();
+        assert_eq!(engine.id.name, "Stub");
+
+        // The search waiting on the dropped connection fails, the next one goes to the new connection
+        let error = engine.search(START_FEN, &limits, timeout).unwrap_err();
+        assert!(error.contains("reconnected"), "{}", error);
+        assert_eq!(engine.search(START_FEN, &limits, timeout).unwrap().best_move, "h2e2");
+        engine.quit();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        let setup = ["uci", "setoption name Hash value 16", "isready"];
+        assert_eq!(received[0][..3], setup);
+        assert_eq!(received[1][..3], setup);
+        assert_eq!(received[1][4], "go depth 1");
+    }
+}
@@ -0,0 +1,360 @@
+use crate::notation::GameNotation;
+use crate::opening_book::{compute_key_and_transform, transform_uci_move, uci_to_int};
+use crate::position::JieqiMove;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameSummary {
+    pub id: i64,
+    pub event: Option<String>,
+    pub site: Option<String>,
+    pub date: Option<String>,
+    pub red: Option<String>,
+    pub black: Option<String>,
+    pub result: Option<String>,
+    pub initial_fen: Option<String>,
+    pub flip_mode: Option<String>,
+    pub ply_count: i64,
+    pub source: Option<String>,
+}
+
+/// Search filters; all given filters must match. `move_uci` needs `position_fen`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameQuery {
+    // Matches either side, case-insensitive substring
+    pub player: Option<String>,
+    pub red: Option<String>,
+    pub black: Option<String>,
+    pub event: Option<String>,
+    // Inclusive, YYYY-MM-DD
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    pub result: Option<String>,
+    pub flip_mode: Option<String>,
+    // Games reaching this position, in any mirrored or color-swapped form
+    pub position_fen: Option<String>,
+    // Games where this move was played from `position_fen`
+    pub move_uci: Option<String>,
+    pub page: u32,
+    pub page_size: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GamePage {
+    pub total: i64,
+    pub page: u32,
+    pub page_size: u32,
+    pub games: Vec<GameSummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameDbStats {
+    pub total_games: i64,
+    pub total_positions: i64,
+}
+
+pub struct GameDatabase {
+    conn: Connection,
+}
+
+impl GameDatabase {
+    pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+        let db = GameDatabase { conn };
+        db.initialize_database()?;
+        Ok(db)
+    }
+
+    fn initialize_database(&self) -> Result<()> {
+        self.conn.execute_batch(
+            r#"
+            PRAGMA foreign_keys = ON;
+            CREATE TABLE IF NOT EXISTS games (
+                id           INTEGER PRIMARY KEY,
+                event        TEXT,
+                site         TEXT,
+                date         TEXT,
+                red          TEXT,
+                black        TEXT,
+                result       TEXT,
+                initial_fen  TEXT,
+                flip_mode    TEXT,
+                ply_count    INTEGER NOT NULL,
+                source       TEXT,
+                notation     TEXT NOT NULL,
+                content_hash BLOB NOT NULL UNIQUE,
+                imported_at  INTEGER NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS game_positions (
+                game_id INTEGER NOT NULL REFERENCES games(id) ON DELETE CASCADE,
+                ply     INTEGER NOT NULL,
+                key     BLOB NOT NULL,
+                move    INTEGER,
+                PRIMARY KEY (game_id, ply)
+            );
+            CREATE INDEX IF NOT EXISTS idx_games_red ON games(red COLLATE NOCASE);
+            CREATE INDEX IF NOT EXISTS idx_games_black ON games(black COLLATE NOCASE);
+            CREATE INDEX IF NOT EXISTS idx_games_date ON games(date);
+            CREATE INDEX IF NOT EXISTS idx_games_event ON games(event COLLATE NOCASE);
+            CREATE INDEX IF NOT EXISTS idx_games_result ON games(result);
+            CREATE INDEX IF NOT EXISTS idx_games_initial_fen ON games(initial_fen);
+            CREATE INDEX IF NOT EXISTS idx_games_flip_mode ON games(flip_mode);
+            CREATE INDEX IF NOT EXISTS idx_positions_key_move ON game_positions(key, move);
+            "#,
+        )
+    }
+
+    /// Stores a game with one index row per position before each move, plus the final
+    /// position. Returns None if the same game was imported before.
+    pub fn import_game(&mut self, notation: &GameNotation, source: Option<&str>) -> std::result::Result<Option<i64>, String> {
+        let json = notation.to_json()?;
+        let content_hash = Sha256::digest(json.as_bytes()).to_vec();
+        let positions = notation.replay()?;
+
+        // (ply, key, normalized move) for every move entry and the final position
+        let mut rows = Vec::new();
+        let mut ply = 0;
+        for (entry, position) in notation.moves.iter().zip(positions.iter()) {
+            if !entry.is_move() {
+                continue;
+            }
+            let (key, transform_idx) = compute_key_and_transform(&position.to_fen());
+            let mv = JieqiMove::parse(&entry.data, position.side_to_move)?;
+            let move_int = uci_to_int(&transform_uci_move(&mv.base_uci(), transform_idx)) as i64;
+            rows.push((ply, key, Some(move_int)));
+            ply += 1;
+        }
+        if let Some(last) = positions.last() {
+            rows.push((ply, compute_key_and_transform(&last.to_fen()).0, None));
+        }
+
+        let metadata = &notation.metadata;
+        let tx = self.conn.transaction().map_err(|e| e.to_string())?;
+        let inserted = tx
+            .execute(
+                r#"
+                INSERT OR IGNORE INTO games (event, site, date, red, black, result, initial_fen, flip_mode,
+                                             ply_count, source, notation, content_hash, imported_at)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+                "#,
+                rusqlite::params![
+                    metadata.event,
+                    metadata.site,
+                    metadata.date.as_deref().map(normalize_date),
+                    metadata.white,
+                    metadata.black,
+                    metadata.result,
+                    notation.initial_fen(),
+                    metadata.flip_mode,
+                    ply,
+                    source,
+                    &json,
+                    &content_hash,
+                    chrono::Utc::now().timestamp(),
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        if inserted == 0 {
+            return Ok(None);
+        }
+        let game_id = tx.last_insert_rowid();
+        {
+            let mut stmt = tx
+                .prepare("INSERT INTO game_positions (game_id, ply, key, move) VALUES (?1, ?2, ?3, ?4)")
+                .map_err(|e| e.to_string())?;
+            for (ply, key, move_int) in rows {
+                stmt.execute(rusqlite::params![game_id, ply, key, move_int]).map_err(|e| e.to_string())?;
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(Some(game_id))
+    }
+
+    pub fn get_game(&self, id: i64) -> std::result::Result<Option<GameNotation>, String> {
+        let json: Option<String> = self
+            .conn
+            .query_row("SELECT notation FROM games WHERE id = ?1", [id], |row| row.get(0))
+            .optional()
+            .map_err(|e| e.to_string())?;
+        json.map(|json| GameNotation::from_json(&json)).transpose()
+    }
+
+    pub fn delete_game(&self, id: i64) -> Result<bool> {
+        let affected_rows = self.conn.execute("DELETE FROM games WHERE id = ?1", [id])?;
+        Ok(affected_rows > 0)
+    }
+
+    pub fn search(&self, query: &GameQuery) -> Result<GamePage> {
+        let mut conditions: Vec<String> = Vec::new();
+        let mut params: Vec<SqlValue> = Vec::new();
+
+        if let Some(player) = &query.player {
+            let p = bind(&mut params, SqlValue::Text(like_pattern(player)));
+            conditions.push(format!(r"(red LIKE {p} ESCAPE '\' OR black LIKE {p} ESCAPE '\')", p = p));
+        }
+        for (column, value) in [("red", &query.red), ("black", &query.black), ("event", &query.event)] {
+            if let Some(value) = value {
+                let p = bind(&mut params, SqlValue::Text(like_pattern(value)));
+                conditions.push(format!(r"{} LIKE {} ESCAPE '\'", column, p));
+            }
+        }
+        if let Some(from) = &query.date_from {
+            let p = bind(&mut params, SqlValue::Text(normalize_date(from)));
+            conditions.push(format!("date >= {}", p));
+        }
+        if let Some(to) = &query.date_to {
+            let p = bind(&mut params, SqlValue::Text(normalize_date(to)));
+            conditions.push(format!("date <= {}", p));
+        }
+        for (column, value) in [("result", &query.result), ("flip_mode", &query.flip_mode)] {
+            if let Some(value) = value {
+                let p = bind(&mut params, SqlValue::Text(value.clone()));
+                conditions.push(format!("{} = {}", column, p));
+            }
+        }
+        if let Some(fen) = &query.position_fen {
+            let (key, transform_idx) = compute_key_and_transform(fen);
+            let key_param = bind(&mut params, SqlValue::Blob(key));
+            match &query.move_uci {
+                Some(uci) => {
+                    // Indexed without reveal letters, like `base_uci`
+                    let base = uci.trim().get(..4).unwrap_or_default();
+                    let move_int = uci_to_int(&transform_uci_move(base, transform_idx)) as i64;
+                    let move_param = bind(&mut params, SqlValue::Integer(move_int));
+                    conditions.push(format!(
+                        "id IN (SELECT game_id FROM game_positions WHERE key = {} AND move = {})",
+                        key_param, move_param
+                    ));
+                }
+                None => conditions.push(format!("id IN (SELECT game_id FROM game_positions WHERE key = {})", key_param)),
+            }
+        }
+
+        let where_clause = if conditions.is_empty() { String::new() } else { format!("WHERE {}", conditions.join(" AND ")) };
+        let total: i64 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM games {}", where_clause),
+            rusqlite::params_from_iter(params.iter()),
+            |row| row.get(0),
+        )?;
+
+        let page_size = if query.page_size == 0 { DEFAULT_PAGE_SIZE } else { query.page_size.min(MAX_PAGE_SIZE) };
+        let limit = bind(&mut params, SqlValue::Integer(page_size as i64));
+        let offset = bind(&mut params, SqlValue::Integer(query.page as i64 * page_size as i64));
+        let mut stmt = self.conn.prepare(&format!(
+            r#"
+            SELECT id, event, site, date, red, black, result, initial_fen, flip_mode, ply_count, source
+            FROM games {}
+            ORDER BY date DESC, id DESC
+            LIMIT {} OFFSET {}
+            "#,
+            where_clause, limit, offset
+        ))?;
+        let games = stmt
+            .query_map(rusqlite::params_from_iter(params.iter()), |row| {
+                Ok(GameSummary {
+                    id: row.get(0)?,
+                    event: row.get(1)?,
+                    site: row.get(2)?,
+                    date: row.get(3)?,
+                    red: row.get(4)?,
+                    black: row.get(5)?,
+                    result: row.get(6)?,
+                    initial_fen: row.get(7)?,
+                    flip_mode: row.get(8)?,
+                    ply_count: row.get(9)?,
+                    source: row.get(10)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(GamePage { total, page: query.page, page_size, games })
+    }
+
+    pub fn get_stats(&self) -> Result<GameDbStats> {
+        let total_games: i64 = self.conn.query_row("SELECT COUNT(*) FROM games", [], |row| row.get(0))?;
+        let total_positions: i64 =
+            self.conn.query_row("SELECT COUNT(DISTINCT key) FROM game_positions", [], |row| row.get(0))?;
+        Ok(GameDbStats { total_games, total_positions })
+    }
+}
+
+// Substring pattern matching `text` literally, for `LIKE ... ESCAPE '\'`
+fn like_pattern(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+// Adds a parameter and returns its placeholder
+fn bind(params: &mut Vec<SqlValue>, value: SqlValue) -> String {
+    params.push(value);
+    format!("?{}", params.len())
+}
+
+// Dates are compared as text, so `2024/5/1` and `2024.05.01` become `2024-05-01`
+fn normalize_date(date: &str) -> String {
+    let parts: Vec<&str> = date.trim().split(['-', '/', '.']).collect();
+    match parts.as_slice() {
+        [year, month, day] if [year, month, day].iter().all(|p| p.chars().all(|c| c.is_ascii_digit())) => {
+            format!("{:0>4}-{:0>2}-{:0>2}", year, month, day)
+        }
+        _ => date.trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::START_FEN;
+
+    fn game(red: &str, moves: &[&str]) -> GameNotation {
+        let moves: Vec<String> = moves.iter().map(|m| format!(r#"{{"type":"move","data":"{}"}}"#, m)).collect();
+        let red = serde_json::to_string(red).unwrap();
+        let json = format!(r#"{{"metadata":{{"white":{},"black":"Someone"}},"moves":[{}]}}"#, red, moves.join(","));
+        GameNotation::from_json(&json).unwrap()
+    }
+
+    fn ids(db: &GameDatabase, query: GameQuery) -> Vec<i64> {
+        db.search(&query).unwrap().games.iter().map(|g| g.id).collect()
+    }
+
+    #[test]
+    fn move_filter_ignores_reveal_letters() {
+        let mut db = GameDatabase::new(":memory:").unwrap();
+        let id = db.import_game(&game("A", &["a3a4P", "a6a5p"]), None).unwrap().unwrap();
+        db.import_game(&game("B", &["c3c4P"]), None).unwrap();
+
+        for uci in ["a3a4", "a3a4P", "a3a4R"] {
+            let query = GameQuery {
+                position_fen: Some(START_FEN.to_string()),
+                move_uci: Some(uci.to_string()),
+                ..Default::default()
+            };
+            assert_eq!(ids(&db, query), vec![id], "{}", uci);
+        }
+    }
+
+    #[test]
+    fn name_filters_match_wildcards_literally() {
+        let mut db = GameDatabase::new(":memory:").unwrap();
+        let percent = db.import_game(&game("100%_Red", &["a3a4P"]), None).unwrap().unwrap();
+        let plain = db.import_game(&game("1000 Red", &["c3c4P"]), None).unwrap().unwrap();
+        let slash = db.import_game(&game(r"Back\Slash", &["g3g4P"]), None).unwrap().unwrap();
+
+        let player = |p: &str| GameQuery { player: Some(p.to_string()), ..Default::default() };
+        assert_eq!(ids(&db, player("0%")), vec![percent]);
+        assert_eq!(ids(&db, player("%_")), vec![percent]);
+        assert_eq!(ids(&db, player(r"k\s")), vec![slash]);
+        let mut all = ids(&db, player("red"));
+        all.sort();
+        assert_eq!(all, vec![percent, plain]);
+        assert_eq!(ids(&db, GameQuery { red: Some("0_R".into()), ..Default::default() }), vec![] as Vec<i64>);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::notation::{apply_entry, GameNotation, NotationMetadata, NotationMove};
+use crate::position::{Position, START_FEN};
+use serde::{Deserialize, Serialize};
+
+/// One history entry. The first child continues the line the node is on; the others are
+/// variations branching off before that continuation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameNode {
+    // `variations` is always empty here; the tree structure replaces it
+    pub entry: NotationMove,
+    pub children: Vec<GameNode>,
+}
+
+/// Game with variations. Nodes are addressed by the child index taken at each step from the
+/// initial position, so the mainline is all zeros and `[]` is the initial position.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GameTree {
+    pub metadata: NotationMetadata,
+    pub children: Vec<GameNode>,
+}
+
+// Nodes for a flat line plus the variations hanging off its entries, as the children of the
+// position before the line's first entry
+fn line_to_nodes(line: &[NotationMove]) -> Vec<GameNode> {
+    let Some((first, rest)) = line.split_first() else { return Vec::new() };
+    let mut entry = first.clone();
+    let variations = std::mem::take(&mut entry.variations);
+    let mut nodes = vec![GameNode { entry, children: line_to_nodes(rest) }];
+    for variation in &variations {
+        nodes.extend(line_to_nodes(variation));
+    }
+    nodes
+}
+
+// Inverse of line_to_nodes: follows first children, storing the others as variations
+fn nodes_to_line(mut children: &[GameNode]) -> Vec<NotationMove> {
+    let mut line = Vec::new();
+    while let Some((main, alternatives)) = children.split_first() {
+        let mut entry = main.entry.clone();
+        entry.variations = alternatives.iter().map(|alt| nodes_to_line(std::slice::from_ref(alt))).collect();
+        line.push(entry);
+        children = &main.children;
+    }
+    line
+}
+
+impl GameTree {
+    pub fn from_notation(notation: &GameNotation) -> GameTree {
+        GameTree { metadata: notation.metadata.clone(), children: line_to_nodes(&notation.moves) }
+    }
+
+    pub fn to_notation(&self) -> GameNotation {
+        GameNotation { metadata: self.metadata.clone(), moves: nodes_to_line(&self.children) }
+    }
+
+    pub fn node(&self, path: &[usize]) -> Option<&GameNode> {
+        let (last, parents) = path.split_last()?;
+        self.children_at(parents)?.get(*last)
+    }
+
+    pub fn node_mut(&mut self, path: &[usize]) -> Option<&mut GameNode> {
+        let (last, parents) = path.split_last()?;
+        self.children_at_mut(parents)?.get_mut(*last)
+    }
+
+    // Children of the node at `path`, or the first moves for the empty path
+    fn children_at(&self, path: &[usize]) -> Option<&Vec<GameNode>> {
+        let mut children = &self.children;
+        for &index in path {
+            children = &children.get(index)?.children;
+        }
+        Some(children)
+    }
+
+    fn children_at_mut(&mut self, path: &[usize]) -> Option<&mut Vec<GameNode>> {
+        let mut children = &mut self.children;
+        for &index in path {
+            children = &mut children.get_mut(index)?.children;
+        }
+        Some(children)
+    }
+
+    pub fn mainline(&self) -> Vec<&NotationMove> {
+        let mut line = Vec::new();
+        let mut children = &self.children;
+        while let Some(main) = children.first() {
+            line.push(&main.entry);
+            children = &main.children;
+        }
+        line
+    }
+
+    // Position after the node at `path`
+    pub fn position_at(&self, path: &[usize]) -> Result<Position, String> {
+        let initial_fen = self.metadata.initial_fen.as_deref().filter(|f| !f.is_empty()).unwrap_or(START_FEN);
+        let mut position = Position::from_fen(initial_fen)?;
+        let mut children = &self.children;
+        for (depth, &index) in path.iter().enumerate() {
+            let node = children.get(index).ok_or_else(|| format!("No node at {:?}", &path[..=depth]))?;
+            apply_entry(&mut position, &node.entry).map_err(|e| format!("{:?}: {}", &path[..=depth], e))?;
+            children = &node.children;
+        }
+        Ok(position)
+    }
+
+    /// Plays `entry` after the node at `parent`, as the continuation if there is none yet and
+    /// as a new variation otherwise. An existing child with the same data is reused. Returns
+    /// the path of the played node.
+    pub fn add_entry(&mut self, parent: &[usize], mut entry: NotationMove) -> Result<Vec<usize>, String> {
+        let mut position = self.position_at(parent)?;
+        apply_entry(&mut position, &entry)?;
+        entry.fen = position.to_fen();
+        entry.variations.clear();
+
+        let children = self.children_at_mut(parent).ok_or_else(|| format!("No node at {:?}", parent))?;
+        let index = match children.iter().position(|c| c.entry.kind == entry.kind && c.entry.data == entry.data) {
+            Some(index) => index,
+            None => {
+                children.push(GameNode { entry, children: Vec::new() });
+                children.len() - 1
+            }
+        };
+        let mut path = parent.to_vec();
+        path.push(index);
+        Ok(path)
+    }
+
+    pub fn add_move(&mut self, parent: &[usize], uci: &str) -> Result<Vec<usize>, String> {
+        self.add_entry(parent, NotationMove { kind: "move".to_string(), data: uci.to_string(), ..Default::default() })
+    }
+
+    /// Makes the node at `path` the main continuation at its branch point. Returns its new path.
+    pub fn promote(&mut self, path: &[usize]) -> Result<Vec<usize>, String> {
+        let (&index, parent) = path.split_last().ok_or("The initial position cannot be promoted")?;
+        let siblings = self.children_at_mut(parent).filter(|c| index < c.len()).ok_or_else(|| format!("No node at {:?}", path))?;
+        let node = siblings.remove(index);
+        siblings.insert(0, node);
+        let mut promoted = parent.to_vec();
+        promoted.push(0);
+        Ok(promoted)
+    }
+
+    /// Promotes the node and every branch above it, so its line becomes the mainline.
+    pub fn make_mainline(&mut self, path: &[usize]) -> Result<Vec<usize>, String> {
+        if self.node(path).is_none() {
+            return Err(format!("No node at {:?}", path));
+        }
+        // Top-down, so the ancestors of each level are already at index 0
+        for (depth, &index) in path.iter().enumerate() {
+            let siblings = self.children_at_mut(&vec![0; depth]).expect("path was checked");
+            let node = siblings.remove(index);
+            siblings.insert(0, node);
+        }
+        Ok(vec![0; path.len()])
+    }
+
+    /// Removes the node at `path` with everything after it. Deleting the main continuation
+    /// makes its first variation the main one.
+    pub fn delete(&mut self, path: &[usize]) -> Result<GameNode, String> {
+        let (&index, parent) = path.split_last().ok_or("The initial position cannot be deleted")?;
+        let siblings = self.children_at_mut(parent).filter(|c| index < c.len()).ok_or_else(|| format!("No node at {:?}", path))?;
+        Ok(siblings.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(line: &[NotationMove]) -> Vec<&str> {
+        line.iter().map(|m| m.data.as_str()).collect()
+    }
+
+    fn mainline(tree: &GameTree) -> Vec<&str> {
+        tree.mainline().iter().map(|m| m.data.as_str()).collect()
+    }
+
+    // Mainline e1e2 d9d8 e2e3, with a9a8 e2e3 as black's alternative and e1f1 d9d8 (or a9a7) as red's
+    fn tree() -> GameTree {
+        let initial_fen = Some("r2k5/9/9/9/9/9/9/9/4R4/4K4 w - - 0 1".to_string());
+        let metadata = NotationMetadata { initial_fen, ..Default::default() };
+        let mut tree = GameTree { metadata, children: Vec::new() };
+        for (parent, uci, path) in [
+            (vec![], "e1e2", vec![0]),
+            (vec![0], "d9d8", vec![0, 0]),
+            (vec![0, 0], "e2e3", vec![0, 0, 0]),
+            (vec![0], "a9a8", vec![0, 1]),
+            (vec![0, 1], "e2e3", vec![0, 1, 0]),
+            (vec![], "e1f1", vec![1]),
+            (vec![1], "d9d8", vec![1, 0]),
+            (vec![1], "a9a7", vec![1, 1]),
+        ] {
+            assert_eq!(tree.add_move(&parent, uci).unwrap(), path);
+        }
+        tree
+    }
+
+    #[test]
+    fn variations_round_trip_through_the_notation() {
+        let mut tree = tree();
+        // Playing a known move follows it instead of adding a variation
+        assert_eq!(tree.add_move(&[0], "a9a8").unwrap(), [0, 1]);
+        assert!(tree.add_move(&[0, 0], "a1a2").is_err());
+
+        let notation = tree.to_notation();
+        assert_eq!(data(&notation.moves), ["e1e2", "d9d8", "e2e3"]);
+        let red = &notation.moves[0].variations;
+        assert_eq!(red.len(), 1);
+        assert_eq!(data(&red[0]), ["e1f1", "d9d8"]);
+        assert_eq!(data(&red[0][1].variations[0]), ["a9a7"]);
+        let black = &notation.moves[1].variations;
+        assert_eq!(black.iter().map(|v| data(v)).collect::<Vec<_>>(), [["a9a8", "e2e3"]]);
+        assert!(notation.check().is_empty());
+
+        let reloaded = GameTree::from_notation(&notation);
+        assert_eq!(reloaded.to_notation().to_json().unwrap(), notation.to_json().unwrap());
+        assert_eq!(reloaded.node(&[1, 1]).unwrap().entry.data, "a9a7");
+        assert!(reloaded.node(&[1, 1]).unwrap().entry.variations.is_empty());
+        assert_eq!(reloaded.position_at(&[0, 1, 0]).unwrap().to_fen(), tree.position_at(&[0, 1, 0]).unwrap().to_fen());
+    }
+
+    #[test]
+    fn promoting_reorders_one_branch_point() {
+        let mut tree = tree();
+        assert_eq!(tree.promote(&[0, 1]).unwrap(), [0, 0]);
+        assert_eq!(mainline(&tree), ["e1e2", "a9a8", "e2e3"]);
+        assert_eq!(tree.node(&[0, 1]).unwrap().entry.data, "d9d8");
+        assert!(tree.promote(&[]).is_err());
+        assert!(tree.promote(&[0, 2]).is_err());
+    }
+
+    #[test]
+    fn make_mainline_promotes_every_ancestor() {
+        let mut tree = tree();
+        assert_eq!(tree.make_mainline(&[1, 1]).unwrap(), [0, 0]);
+        assert_eq!(mainline(&tree), ["e1f1", "a9a7"]);
+        assert_eq!(tree.node(&[0, 1]).unwrap().entry.data, "d9d8");
+        // The old mainline is now red's first alternative
+        assert_eq!(tree.node(&[1]).unwrap().entry.data, "e1e2");
+        assert_eq!(data(&tree.to_notation().moves[0].variations[0]), ["e1e2", "d9d8", "e2e3"]);
+        assert!(tree.make_mainline(&[1, 5]).is_err());
+    }
+
+    #[test]
+    fn deleting_the_main_continuation_promotes_the_first_variation() {
+        let mut tree = tree();
+        let removed = tree.delete(&[0, 0]).unwrap();
+        assert_eq!((removed.entry.data.as_str(), removed.children.len()), ("d9d8", 1));
+        assert_eq!(mainline(&tree), ["e1e2", "a9a8", "e2e3"]);
+
+        assert_eq!(tree.delete(&[0]).unwrap().entry.data, "e1e2");
+        assert_eq!(mainline(&tree), ["e1f1", "d9d8"]);
+        assert_eq!(tree.to_notation().moves[1].variations.len(), 1);
+        assert!(tree.delete(&[]).is_err());
+        assert!(tree.delete(&[3]).is_err());
+    }
+}
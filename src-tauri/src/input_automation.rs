@@ -0,0 +1,372 @@
+use crate::calibration::ScreenPoint;
+use crate::position::Rng;
+#[cfg(feature = "gui")]
+use enigo::{Button, Coordinate, Direction, Enigo, Mouse, Settings};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Mouse input, so the real one can be swapped for a recording in tests.
+pub trait InputDevice {
+    fn move_to(&mut self, point: ScreenPoint) -> Result<(), String>;
+    fn press(&mut self) -> Result<(), String>;
+    fn release(&mut self) -> Result<(), String>;
+}
+
+#[cfg(feature = "gui")]
+pub struct EnigoDevice(Enigo);
+
+#[cfg(feature = "gui")]
+impl EnigoDevice {
+    pub fn new() -> Result<Self, String> {
+        Enigo::new(&Settings::default()).map(EnigoDevice).map_err(|e| format!("Init Enigo failed: {}", e))
+    }
+}
+
+#[cfg(feature = "gui")]
+impl InputDevice for EnigoDevice {
+    fn move_to(&mut self, (x, y): ScreenPoint) -> Result<(), String> {
+        self.0.move_mouse(x, y, Coordinate::Abs).map_err(|e| e.to_string())
+    }
+
+    fn press(&mut self) -> Result<(), String> {
+        self.0.button(Button::Left, Direction::Press).map_err(|e| e.to_string())
+    }
+
+    fn release(&mut self) -> Result<(), String> {
+        self.0.button(Button::Left, Direction::Release).map_err(|e| e.to_string())
+    }
+}
+
+/// Keeps every action with the time since the device was created instead of touching the mouse.
+pub struct RecordingDevice {
+    started: Instant,
+    pub actions: Vec<(Duration, InputAction)>,
+}
+
+impl Default for RecordingDevice {
+    fn default() -> Self {
+        RecordingDevice { started: Instant::now(), actions: Vec::new() }
+    }
+}
+
+impl RecordingDevice {
+    fn record(&mut self, action: InputAction) -> Result<(), String> {
+        self.actions.push((self.started.elapsed(), action));
+        Ok(())
+    }
+}
+
+impl InputDevice for RecordingDevice {
+    fn move_to(&mut self, point: ScreenPoint) -> Result<(), String> {
+        self.record(InputAction::MoveTo(point))
+    }
+
+    fn press(&mut self) -> Result<(), String> {
+        self.record(InputAction::Press)
+    }
+
+    fn release(&mut self) -> Result<(), String> {
+        self.record(InputAction::Release)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum InputAction {
+    MoveTo(ScreenPoint),
+    Press,
+    Release,
+    // Milliseconds
+    Wait(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseMode {
+    // Press on the piece, drag it and release on the destination
+    #[default]
+    Drag,
+    // Click the piece, then click the destination
+    ClickClick,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutomationConfig {
+    pub mode: MouseMode,
+    // Pause before each press
+    pub press_delay_ms: u64,
+    // How long the button is held before releasing or dragging
+    pub hold_ms: u64,
+    // Pause on the destination before a drag is released, or between the two clicks
+    pub settle_ms: u64,
+    // Intermediate points between the squares; 0 jumps straight to the destination
+    pub path_steps: u32,
+    pub step_delay_ms: u64,
+    // Random offset from the square centers, in pixels
+    pub jitter: f32,
+    // Largest sideways bend of the path as a fraction of its length
+    pub curve: f32,
+    pub seed: Option<u64>,
+}
+
+impl Default for AutomationConfig {
+    fn default() -> Self {
+        AutomationConfig {
+            mode: MouseMode::Drag,
+            press_delay_ms: 50,
+            hold_ms: 50,
+            settle_ms: 100,
+            path_steps: 0,
+            step_delay_ms: 10,
+            jitter: 0.0,
+            curve: 0.0,
+            seed: None,
+        }
+    }
+}
+
+// Uniform in [-1, 1]
+fn unit(rng: &mut Rng) -> f32 {
+    (rng.next_u64() >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+}
+
+fn jittered((x, y): ScreenPoint, jitter: f32, rng: &mut Rng) -> ScreenPoint {
+    if jitter <= 0.0 {
+        return (x, y);
+    }
+    (x + (unit(rng) * jitter).round() as i32, y + (unit(rng) * jitter).round() as i32)
+}
+
+// Points after `from` up to and including `to`, along a quadratic curve with eased speed
+fn path(from: ScreenPoint, to: ScreenPoint, config: &AutomationConfig, rng: &mut Rng) -> Vec<ScreenPoint> {
+    let (fx, fy) = (from.0 as f32, from.1 as f32);
+    let (dx, dy) = (to.0 as f32 - fx, to.1 as f32 - fy);
+    let bend = config.curve * unit(rng);
+    // Control point: the midpoint pushed sideways
+    let (cx, cy) = (fx + dx / 2.0 - dy * bend, fy + dy / 2.0 + dx * bend);
+    let count = config.path_steps + 1;
+    (1..=count)
+        .map(|i| {
+            if i == count {
+                return to;
+            }
+            let t = i as f32 / count as f32;
+            let t = t * t * (3.0 - 2.0 * t);
+            let x = (1.0 - t) * (1.0 - t) * fx + 2.0 * (1.0 - t) * t * cx + t * t * to.0 as f32;
+            let y = (1.0 - t) * (1.0 - t) * fy + 2.0 * (1.0 - t) * t * cy + t * t * to.1 as f32;
+            (x.round() as i32, y.round() as i32)
+        })
+        .collect()
+}
+
+/// The actions that move a piece from one point to another in the configured mode.
+pub fn plan_move(from: ScreenPoint, to: ScreenPoint, config: &AutomationConfig, rng: &mut Rng) -> Vec<InputAction> {
+    use InputAction::{MoveTo, Press, Release, Wait};
+    let from = jittered(from, config.jitter, rng);
+    let to = jittered(to, config.jitter, rng);
+    let mut actions = vec![MoveTo(from), Wait(config.press_delay_ms), Press, Wait(config.hold_ms)];
+    if config.mode == MouseMode::ClickClick {
+        actions.extend([Release, Wait(config.settle_ms)]);
+    }
+    for (i, point) in path(from, to, config, rng).into_iter().enumerate() {
+        if i > 0 {
+            actions.push(Wait(config.step_delay_ms));
+        }
+        actions.push(MoveTo(point));
+    }
+    match config.mode {
+        MouseMode::Drag => actions.extend([Wait(config.settle_ms), Release]),
+        MouseMode::ClickClick => actions.extend([Wait(config.press_delay_ms), Press, Wait(config.hold_ms), Release]),
+    }
+    actions.retain(|a| *a != Wait(0));
+    actions
+}
+
+/// Runs the actions, sleeping through the waits, until done or `cancel` is set. A held
+/// button is released when cancelled or when the device fails.
+pub fn execute(device: &mut dyn InputDevice, actions: &[InputAction], cancel: &AtomicBool) -> Result<(), String> {
+    let mut pressed = false;
+    let result = actions.iter().try_for_each(|action| {
+        if cancel.load(Ordering::SeqCst) {
+            return Err("Mouse action cancelled".to_string());
+        }
+        match *action {
+            InputAction::MoveTo(point) => device.move_to(point),
+            InputAction::Press => device.press().map(|_| pressed = true),
+            InputAction::Release => device.release().map(|_| pressed = false),
+            InputAction::Wait(ms) => {
+                let end = Instant::now() + Duration::from_millis(ms);
+                while !cancel.load(Ordering::SeqCst) && Instant::now() < end {
+                    thread::sleep(end.saturating_duration_since(Instant::now()).min(Duration::from_millis(10)));
+                }
+                Ok(())
+            }
+        }
+    });
+    if result.is_err() && pressed {
+        let _ = device.release();
+    }
+    result
+}
+
+/// Plans and performs a move, returning what was done.
+pub fn perform_move(
+    device: &mut dyn InputDevice,
+    from: ScreenPoint,
+    to: ScreenPoint,
+    config: &AutomationConfig,
+    cancel: &AtomicBool,
+) -> Result<Vec<InputAction>, String> {
+    let mut rng = config.seed.map(Rng::new).unwrap_or_else(Rng::from_time);
+    let actions = plan_move(from, to, config, &mut rng);
+    execute(device, &actions, cancel)?;
+    Ok(actions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use InputAction::{MoveTo, Press, Release, Wait};
+
+    fn plan(config: &AutomationConfig) -> Vec<InputAction> {
+        plan_move((100, 100), (200, 150), config, &mut Rng::new(7))
+    }
+
+    #[test]
+    fn drag_presses_once_and_releases_on_the_destination() {
+        let actions = plan(&AutomationConfig::default());
+        assert_eq!(
+            actions,
+            [MoveTo((100, 100)), Wait(50), Press, Wait(50), MoveTo((200, 150)), Wait(100), Release]
+        );
+    }
+
+    #[test]
+    fn click_click_releases_before_moving() {
+        let config = AutomationConfig { mode: MouseMode::ClickClick, ..Default::default() };
+        let actions = plan(&config);
+        assert_eq!(
+            actions,
+            [
+                MoveTo((100, 100)),
+                Wait(50),
+                Press,
+                Wait(50),
+                Release,
+                Wait(100),
+                MoveTo((200, 150)),
+                Wait(50),
+                Press,
+                Wait(50),
+                Release
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_waits_are_dropped() {
+        let config = AutomationConfig {
+            press_delay_ms: 0,
+            hold_ms: 0,
+            settle_ms: 0,
+            path_steps: 2,
+            step_delay_ms: 0,
+            ..Default::default()
+        };
+        let actions = plan(&config);
+        assert!(!actions.iter().any(|a| matches!(a, Wait(_))), "{:?}", actions);
+        assert_eq!(actions.len(), 1 + 1 + 3 + 1);
+    }
+
+    #[test]
+    fn paths_are_repeatable_with_a_seed() {
+        let config = AutomationConfig { path_steps: 5, jitter: 3.0, curve: 0.2, ..Default::default() };
+        let actions = plan(&config);
+        assert_eq!(actions, plan(&config));
+
+        let points: Vec<ScreenPoint> =
+            actions.iter().filter_map(|a| if let MoveTo(p) = a { Some(*p) } else { None }).collect();
+        assert_eq!(points.len(), 1 + 6);
+        let near = |(x, y): ScreenPoint, (tx, ty): ScreenPoint| (x - tx).abs() <= 3 && (y - ty).abs() <= 3;
+        assert!(near(points[0], (100, 100)) && near(points[6], (200, 150)), "{:?}", points);
+        // Eased along the way: every step goes towards the destination
+        assert!(points.windows(2).all(|w| w[1].0 >= w[0].0), "{:?}", points);
+
+        // Without jitter or curve the path is the straight line
+        let straight = AutomationConfig { path_steps: 3, ..Default::default() };
+        for action in plan(&straight) {
+            if let MoveTo((x, y)) = action {
+                assert!(((y - 100) * 2 - (x - 100)).abs() <= 1, "({}, {}) is off the line", x, y);
+            }
+        }
+    }
+
+    // Records like RecordingDevice and cancels at the given call to `move_to`
+    struct CancellingDevice<'a> {
+        inner: RecordingDevice,
+        cancel: &'a AtomicBool,
+        cancel_at: usize,
+        moves: usize,
+        fail_moves: bool,
+    }
+
+    impl InputDevice for CancellingDevice<'_> {
+        fn move_to(&mut self, point: ScreenPoint) -> Result<(), String> {
+            self.moves += 1;
+            if self.moves == self.cancel_at {
+                self.cancel.store(true, Ordering::SeqCst);
+            }
+            if self.fail_moves && self.moves > 1 {
+                return Err("device gone".to_string());
+            }
+            self.inner.move_to(point)
+        }
+
+        fn press(&mut self) -> Result<(), String> {
+            self.inner.press()
+        }
+
+        fn release(&mut self) -> Result<(), String> {
+            self.inner.release()
+        }
+    }
+
+    impl<'a> CancellingDevice<'a> {
+        fn new(cancel: &'a AtomicBool, cancel_at: usize, fail_moves: bool) -> Self {
+            CancellingDevice { inner: RecordingDevice::default(), cancel, cancel_at, moves: 0, fail_moves }
+        }
+    }
+
+    fn recorded(device: &CancellingDevice) -> Vec<InputAction> {
+        device.inner.actions.iter().map(|(_, action)| *action).collect()
+    }
+
+    #[test]
+    fn cancelling_a_drag_releases_the_button() {
+        let config = AutomationConfig { path_steps: 4, ..Default::default() };
+        let actions = plan(&config);
+        let cancel = AtomicBool::new(false);
+        let mut device = CancellingDevice::new(&cancel, 3, false);
+
+        assert_eq!(execute(&mut device, &actions, &cancel), Err("Mouse action cancelled".to_string()));
+        let done = recorded(&device);
+        assert_eq!(done.iter().filter(|a| matches!(a, MoveTo(_))).count(), 3);
+        assert_eq!(done.first(), Some(&MoveTo((100, 100))));
+        assert_eq!(done.last(), Some(&Release));
+
+        // A device error mid-drag releases too
+        let cancel = AtomicBool::new(false);
+        let mut device = CancellingDevice::new(&cancel, 0, true);
+        assert_eq!(execute(&mut device, &actions, &cancel), Err("device gone".to_string()));
+        assert_eq!(recorded(&device), [MoveTo((100, 100)), Press, Release]);
+
+        // Nothing is pressed when cancelled before starting
+        let cancel = AtomicBool::new(true);
+        let mut device = RecordingDevice::default();
+        assert!(execute(&mut device, &actions, &cancel).is_err());
+        assert!(device.actions.is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+use crate::board_recognition::{recognize, PieceSet, Recognition, RecognitionConfig};
+use crate::board_watcher::FrameSource;
+use crate::calibration::ScreenPoint;
+use crate::input_automation::{perform_move, AutomationConfig, InputAction, InputDevice};
+use crate::position::{JieqiMove, Piece, Position};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VerifyConfig {
+    // Mouse moves tried before giving up
+    pub attempts: u32,
+    // Captures after each attempt, `check_interval_ms` apart, to let animations finish
+    pub checks: u32,
+    pub check_interval_ms: u64,
+}
+
+impl Default for VerifyConfig {
+    fn default() -> Self {
+        VerifyConfig { attempts: 2, checks: 4, check_interval_ms: 250 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifiedMove {
+    pub uci: String,
+    pub attempts: u32,
+    // Board recognized once the move showed up
+    pub fen: String,
+    pub actions: Vec<InputAction>,
+}
+
+/// Payload of `automation-desync`: the board before the move and the last one seen after it.
+#[derive(Debug, Clone, Serialize)]
+pub struct MoveDesync {
+    pub uci: String,
+    pub attempts: u32,
+    pub before: String,
+    pub after: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum VerifyOutcome {
+    Verified(VerifiedMove),
+    Desync(MoveDesync),
+}
+
+/// Whether `after` shows the move: the from square emptied and the to square now holds a
+/// piece of the mover, revealed or not.
+pub fn move_shown(before: &Position, after: &[Option<Piece>; 90], mv: &JieqiMove) -> bool {
+    let Some(mover) = before.board[mv.from] else { return false };
+    after[mv.from].is_none()
+        && after[mv.to].is_some_and(|p| p.side() == mover.side())
+        && after[mv.to] != before.board[mv.to]
+}
+
+fn capture(source: &mut dyn FrameSource, pieces: &PieceSet, config: &RecognitionConfig) -> Result<Recognition, String> {
+    let frame = source.next_frame()?.ok_or("No frame to verify the move with")?;
+    recognize(&frame, pieces, config)
+}
+
+fn board_part(fen: &str) -> &str {
+    fen.split_whitespace().next().unwrap_or_default()
+}
+
+/// Plays `mv` with the mouse and re-captures the board until the move shows. While the board
+/// still looks as before the move is retried; a board that changed some other way, or no
+/// change after the last attempt, is a desync.
+#[allow(clippy::too_many_arguments)]
+pub fn play_verified(
+    device: &mut dyn InputDevice,
+    source: &mut dyn FrameSource,
+    pieces: &PieceSet,
+    recognition: &RecognitionConfig,
+    position: &Position,
+    mv: &JieqiMove,
+    (from, to): (ScreenPoint, ScreenPoint),
+    automation: &AutomationConfig,
+    config: &VerifyConfig,
+    cancel: &AtomicBool,
+) -> Result<VerifyOutcome, String> {
+    let before = capture(source, pieces, recognition)?.fen;
+    let mut actions = Vec::new();
+    let mut after = before.clone();
+    for attempt in 1..=config.attempts.max(1) {
+        actions.extend(perform_move(device, from, to, automation, cancel)?);
+        for check in 0..config.checks.max(1) {
+            if check > 0 {
+                thread::sleep(Duration::from_millis(config.check_interval_ms));
+            }
+            if cancel.load(Ordering::SeqCst) {
+                return Err("Mouse action cancelled".to_string());
+            }
+            let Ok(seen) = capture(source, pieces, recognition) else { continue };
+            after = seen.fen;
+            let board = Position::from_fen(&after)?.board;
+            if move_shown(position, &board, mv) {
+                let verified = VerifiedMove { uci: mv.to_string(), attempts: attempt, fen: after, actions };
+                return Ok(VerifyOutcome::Verified(verified));
+            }
+        }
+        if board_part(&after) != board_part(&before) {
+            return Ok(VerifyOutcome::Desync(MoveDesync {
+                uci: mv.to_string(),
+                attempts: attempt,
+                before,
+                after,
+                reason: "The board changed but does not show the move".to_string(),
+            }));
+        }
+    }
+    Ok(VerifyOutcome::Desync(MoveDesync {
+        uci: mv.to_string(),
+        attempts: config.attempts.max(1),
+        before,
+        after,
+        reason: "The board did not change".to_string(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board_recognition::BoardOrientation;
+    use crate::board_watcher::RecordedFrames;
+    use crate::input_automation::RecordingDevice;
+    use crate::test_boards::{piece_set, render, GRID};
+
+    const FEN: &str = "3k5/9/9/9/9/9/9/9/4R4/4K4 w - - 0 1";
+
+    fn after(position: &Position, uci: &str) -> Position {
+        let mut next = position.clone();
+        next.apply(&JieqiMove::parse(uci, position.side_to_move).unwrap()).unwrap();
+        next
+    }
+
+    // Plays e1e2 against the given frames, the first of which is the board before the move
+    fn play(frames: &[&Position], cancel: bool) -> (Result<VerifyOutcome, String>, RecordingDevice) {
+        let position = Position::from_fen(FEN).unwrap();
+        let frames = frames.iter().map(|p| render(&p.to_fen(), BoardOrientation::RedBottom)).collect();
+        let recognition = RecognitionConfig { board_rect: Some(GRID), ..Default::default() };
+        let automation = AutomationConfig { press_delay_ms: 0, hold_ms: 0, settle_ms: 0, ..Default::default() };
+        let config = VerifyConfig { attempts: 2, checks: 2, check_interval_ms: 0 };
+        let mut device = RecordingDevice::default();
+        let outcome = play_verified(
+            &mut device,
+            &mut RecordedFrames::new(frames),
+            &piece_set(),
+            &recognition,
+            &position,
+            &JieqiMove::parse("e1e2", position.side_to_move).unwrap(),
+            ((100, 100), (100, 50)),
+            &automation,
+            &config,
+            &AtomicBool::new(cancel),
+        );
+        (outcome, device)
+    }
+
+    fn presses(device: &RecordingDevice) -> usize {
+        device.actions.iter().filter(|(_, a)| *a == InputAction::Press).count()
+    }
+
+    #[test]
+    fn revealed_or_dark_pieces_show_the_move() {
+        let start = Position::startpos();
+        let mv = JieqiMove::parse("h2e2", start.side_to_move).unwrap();
+        let mut board = after(&start, "h2e2C").board;
+        assert!(move_shown(&start, &board, &mv));
+        // Recognized before the reveal was drawn
+        board[mv.to] = Some(Piece::Dark(start.side_to_move));
+        assert!(move_shown(&start, &board, &mv));
+        board[mv.to] = Some(Piece::Known('c'));
+        assert!(!move_shown(&start, &board, &mv));
+        assert!(!move_shown(&start, &start.board, &mv));
+    }
+
+    #[test]
+    fn unchanged_board_is_retried() {
+        let before = Position::from_fen(FEN).unwrap();
+        let moved = after(&before, "e1e2");
+        // Both checks of the first attempt still see the old board
+        let (outcome, device) = play(&[&before, &before, &before, &moved], false);
+        let Ok(VerifyOutcome::Verified(verified)) = outcome else { panic!("{:?}", outcome) };
+        assert_eq!((verified.uci.as_str(), verified.attempts), ("e1e2", 2));
+        assert_eq!(board_part(&verified.fen), board_part(&moved.to_fen()));
+        assert_eq!(presses(&device), 2);
+    }
+
+    #[test]
+    fn board_changed_another_way_is_a_desync() {
+        let before = Position::from_fen(FEN).unwrap();
+        let other = after(&before, "e1f1");
+        let (outcome, device) = play(&[&before, &before, &other], false);
+        let Ok(VerifyOutcome::Desync(desync)) = outcome else { panic!("{:?}", outcome) };
+        assert_eq!(desync.attempts, 1);
+        assert_eq!(desync.reason, "The board changed but does not show the move");
+        assert_eq!(board_part(&desync.after), board_part(&other.to_fen()));
+        assert_eq!(presses(&device), 1);
+    }
+
+    #[test]
+    fn no_change_after_every_attempt_is_a_desync() {
+        let before = Position::from_fen(FEN).unwrap();
+        // The frames run out during the second attempt; missing captures are skipped
+        let (outcome, device) = play(&[&before, &before, &before, &before], false);
+        let Ok(VerifyOutcome::Desync(desync)) = outcome else { panic!("{:?}", outcome) };
+        assert_eq!((desync.attempts, desync.reason.as_str()), (2, "The board did not change"));
+        assert_eq!(desync.before, desync.after);
+        assert_eq!(presses(&device), 2);
+
+        assert!(play(&[&before, &before], true).0.is_err());
+    }
+}
@@ -0,0 +1,412 @@
+use crate::position::{JieqiMove, Position, START_FEN};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fs;
+use std::path::Path;
+
+// Integral scores are written back as integers, as the frontend does
+fn serialize_number<S: Serializer>(value: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(v) if v.fract() == 0.0 && v.abs() < i64::MAX as f64 => serializer.serialize_i64(*v as i64),
+        Some(v) => serializer.serialize_f64(*v),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotationMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub site: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub round: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub white: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub black: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_fen: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flip_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_fen: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opening_comment: Option<String>,
+    // Fields written by newer versions are kept as-is
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotationMove {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub data: String,
+    #[serde(default)]
+    pub fen: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "serialize_number")]
+    pub engine_score: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none", serialize_with = "serialize_number")]
+    pub engine_time: Option<f64>,
+    // Alternative lines starting from the position before this entry. Readers that do not
+    // know the field just see the mainline.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variations: Vec<Vec<NotationMove>>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+impl NotationMove {
+    pub fn is_move(&self) -> bool {
+        self.kind == "move"
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GameNotation {
+    #[serde(default)]
+    pub metadata: NotationMetadata,
+    #[serde(default)]
+    pub moves: Vec<NotationMove>,
+}
+
+impl GameNotation {
+    pub fn from_json(content: &str) -> Result<Self, String> {
+        serde_json::from_str(content).map_err(|e| format!("Invalid notation JSON: {}", e))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let content = fs::read_to_string(path.as_ref())
+            .map_err(|e| format!("Failed to read {}: {}", path.as_ref().display(), e))?;
+        GameNotation::from_json(&content)
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.to_string())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        fs::write(path.as_ref(), self.to_json()?)
+            .map_err(|e| format!("Failed to write {}: {}", path.as_ref().display(), e))
+    }
+
+    pub fn initial_fen(&self) -> &str {
+        self.metadata.initial_fen.as_deref().filter(|f| !f.is_empty()).unwrap_or(START_FEN)
+    }
+
+    // Replays the game from the initial FEN and returns the position before every entry
+    // plus the final position
+    pub fn replay(&self) -> Result<Vec<Position>, String> {
+        let mut position = Position::from_fen(self.initial_fen())?;
+        let mut positions = vec![position.clone()];
+        for (index, entry) in self.moves.iter().enumerate() {
+            apply_entry(&mut position, entry).map_err(|e| format!("Entry {}: {}", index + 1, e))?;
+            positions.push(position.clone());
+        }
+        Ok(positions)
+    }
+}
+
+// Pieces that can be dark; the kings never are
+const HIDDEN_PIECES: &str = "RNBACP";
+
+// Annotations the frontend knows how to display
+const ANNOTATIONS: [&str; 6] = ["!!", "!", "!?", "?!", "?", "??"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemKind {
+    InvalidInitialFen,
+    // An entry that cannot be applied; later entries are not checked
+    IllegalEntry,
+    InvalidFen,
+    FenMismatch,
+    CurrentFenMismatch,
+    UnknownAnnotation,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotationProblem {
+    // 1-based index into `moves`, None for metadata. Problems in a variation carry the entry it
+    // branches from.
+    pub entry: Option<usize>,
+    pub kind: ProblemKind,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepairReport {
+    pub fens_fixed: usize,
+    pub annotations_removed: usize,
+    // Entries from the first illegal one on, which cannot be replayed
+    pub entries_removed: usize,
+}
+
+// Stored FENs may use either layout, so both sides are compared in the new one
+fn same_position(stored: &str, position: &Position) -> Result<bool, String> {
+    Ok(Position::from_fen(stored)?.to_fen() == position.to_fen())
+}
+
+impl GameNotation {
+    /// Replays the game, variations included, and lists every entry that is illegal, carries an
+    /// unknown annotation or whose stored `fen` differs from the replayed position.
+    pub fn check(&self) -> Vec<NotationProblem> {
+        let mut problems = Vec::new();
+        let mut position = match Position::from_fen(self.initial_fen()) {
+            Ok(position) => position,
+            Err(e) => {
+                problems.push(NotationProblem { entry: None, kind: ProblemKind::InvalidInitialFen, message: e });
+                return problems;
+            }
+        };
+        if !check_line(&mut position, &self.moves, None, &mut problems) {
+            return problems;
+        }
+
+        if let Some(current) = self.metadata.current_fen.as_deref() {
+            if !same_position(current, &position).unwrap_or(false) {
+                problems.push(NotationProblem {
+                    entry: None,
+                    kind: ProblemKind::CurrentFenMismatch,
+                    message: format!("currentFen differs from the final position {}", position.to_fen()),
+                });
+            }
+        }
+        problems
+    }
+
+    /// Recomputes every `fen` and `currentFen` from the moves and drops unknown annotations.
+    /// Entries from the first illegal one on are removed, since nothing after it can be replayed;
+    /// the same goes for each variation.
+    pub fn repair(&mut self) -> Result<RepairReport, String> {
+        let mut report = RepairReport::default();
+        let mut position = Position::from_fen(self.initial_fen())?;
+        repair_line(&mut position, &mut self.moves, &mut report);
+        if !self.metadata.current_fen.as_deref().is_some_and(|f| same_position(f, &position).unwrap_or(false)) {
+            self.metadata.current_fen = Some(position.to_fen());
+        }
+        Ok(report)
+    }
+}
+
+// Checks `line` from `position`, leaving the final position in it. Problems in a variation are
+// reported on the mainline entry it branches from, with `branch` naming the variation. Returns
+// false when the line stops at an illegal entry.
+fn check_line(
+    position: &mut Position,
+    line: &[NotationMove],
+    branch: Option<(usize, &str)>,
+    problems: &mut Vec<NotationProblem>,
+) -> bool {
+    for (index, entry) in line.iter().enumerate() {
+        let (entry_no, location) = match branch {
+            Some((entry_no, name)) => (entry_no, Some(format!("{}, move {}", name, index + 1))),
+            None => (index + 1, None),
+        };
+        // Variations start from the position before this entry
+        for (number, variation) in entry.variations.iter().enumerate() {
+            let name = match &location {
+                Some(location) => format!("{}, variation {}", location, number + 1),
+                None => format!("Variation {}", number + 1),
+            };
+            check_line(&mut position.clone(), variation, Some((entry_no, &name)), problems);
+        }
+
+        let prefix = location.map(|l| format!("{}: ", l)).unwrap_or_default();
+        let mut report = |kind, message: String| {
+            problems.push(NotationProblem { entry: Some(entry_no), kind, message: format!("{}{}", prefix, message) })
+        };
+        if let Some(annotation) = entry.annotation.as_deref().filter(|a| !a.is_empty() && !ANNOTATIONS.contains(a)) {
+            report(ProblemKind::UnknownAnnotation, format!("Unknown annotation '{}'", annotation));
+        }
+        if let Err(e) = apply_entry(position, entry) {
+            report(ProblemKind::IllegalEntry, e);
+            return false;
+        }
+        match same_position(&entry.fen, position) {
+            Ok(true) => {}
+            Ok(false) => {
+                report(ProblemKind::FenMismatch, format!("Stored FEN differs from the replayed {}", position.to_fen()))
+            }
+            Err(e) => report(ProblemKind::InvalidFen, format!("Invalid stored FEN: {}", e)),
+        }
+    }
+    true
+}
+
+// Repairs `line` from `position` as GameNotation::repair does, leaving the final position in it.
+// Variations left empty are dropped.
+fn repair_line(position: &mut Position, line: &mut Vec<NotationMove>, report: &mut RepairReport) {
+    let mut keep = line.len();
+    for (index, entry) in line.iter_mut().enumerate() {
+        for variation in entry.variations.iter_mut() {
+            repair_line(&mut position.clone(), variation, report);
+        }
+        entry.variations.retain(|variation| !variation.is_empty());
+
+        if apply_entry(position, entry).is_err() {
+            keep = index;
+            break;
+        }
+        if !same_position(&entry.fen, position).unwrap_or(false) {
+            entry.fen = position.to_fen();
+            report.fens_fixed += 1;
+        }
+        if entry.annotation.as_deref().is_some_and(|a| !a.is_empty() && !ANNOTATIONS.contains(&a)) {
+            entry.annotation = None;
+            report.annotations_removed += 1;
+        }
+    }
+    report.entries_removed += line.len() - keep;
+    line.truncate(keep);
+}
+
+// Applies one history entry: a move in extended UCI, or a pool adjustment such as `R+` or `captured_p-`
+pub fn apply_entry(position: &mut Position, entry: &NotationMove) -> Result<(), String> {
+    match entry.kind.as_str() {
+        "move" => {
+            let mv = JieqiMove::parse(&entry.data, position.side_to_move)?;
+            position.apply(&mv)
+        }
+        "adjust" => apply_adjust(position, &entry.data),
+        other => Err(format!("Unknown entry type '{}'", other)),
+    }
+}
+
+// Mirrors adjustUnrevealedCount / adjustCapturedUnrevealedCount
+fn apply_adjust(position: &mut Position, data: &str) -> Result<(), String> {
+    let (captured, spec) = match data.strip_prefix("captured_") {
+        Some(rest) => (true, rest),
+        None => (false, data),
+    };
+    let mut chars = spec.chars();
+    let (Some(piece), Some(sign), None) = (chars.next(), chars.next(), chars.next()) else {
+        return Err(format!("Invalid adjustment '{}'", data));
+    };
+    if !HIDDEN_PIECES.contains(piece.to_ascii_uppercase()) {
+        return Err(format!("Invalid piece letter '{}' in adjustment '{}'", piece, data));
+    }
+    let delta = match sign {
+        '+' => 1,
+        '-' => -1,
+        _ => return Err(format!("Invalid adjustment '{}'", data)),
+    };
+    if captured {
+        *position.hidden.entry(piece).or_insert(0) -= delta;
+        *position.captured_hidden.entry(piece).or_insert(0) += delta;
+    } else {
+        *position.hidden.entry(piece).or_insert(0) += delta;
+    }
+    if position.hidden.get(&piece).copied().unwrap_or(0) < 0
+        || position.captured_hidden.get(&piece).copied().unwrap_or(0) < 0
+    {
+        return Err(format!("Adjustment '{}' makes a pool negative", data));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(kind: &str, data: &str) -> NotationMove {
+        NotationMove { kind: kind.to_string(), data: data.to_string(), ..Default::default() }
+    }
+
+    // A consistent game: checked clean once repair has filled in the FENs
+    fn game(moves: &[&str]) -> GameNotation {
+        let initial_fen = Some("3k5/9/9/9/9/9/9/9/4R4/4K4 w - - 0 1".to_string());
+        let metadata = NotationMetadata { initial_fen, ..Default::default() };
+        let mut notation = GameNotation { metadata, moves: moves.iter().map(|uci| entry("move", uci)).collect() };
+        notation.repair().unwrap();
+        assert!(notation.check().is_empty());
+        notation
+    }
+
+    fn kinds(notation: &GameNotation) -> Vec<(Option<usize>, ProblemKind)> {
+        notation.check().into_iter().map(|p| (p.entry, p.kind)).collect()
+    }
+
+    #[test]
+    fn invalid_initial_fen_stops_the_check() {
+        let mut notation = game(&["e1e2"]);
+        notation.metadata.initial_fen = Some("not a fen".to_string());
+        assert_eq!(kinds(&notation), [(None, ProblemKind::InvalidInitialFen)]);
+        assert!(notation.repair().is_err());
+    }
+
+    #[test]
+    fn illegal_entries_are_cut_off() {
+        let mut notation = game(&["e1e2", "d9d8", "e2e3"]);
+        notation.moves[1] = entry("move", "a1a2");
+        assert_eq!(kinds(&notation), [(Some(2), ProblemKind::IllegalEntry)]);
+
+        let report = notation.repair().unwrap();
+        assert_eq!((report.entries_removed, report.fens_fixed), (2, 0));
+        assert_eq!(notation.moves.len(), 1);
+        assert_eq!(notation.metadata.current_fen.as_deref(), Some(notation.moves[0].fen.as_str()));
+        assert!(notation.check().is_empty());
+    }
+
+    #[test]
+    fn annotations_and_fens_are_fixed() {
+        let mut notation = game(&["e1e2", "d9d8"]);
+        notation.moves[0].annotation = Some("!!!".to_string());
+        notation.moves[1].annotation = Some("?!".to_string());
+        notation.moves[1].fen = notation.moves[0].fen.clone();
+        notation.metadata.current_fen = Some("3k5/9/9/9/9/9/9/9/4R4/4K4 w - - 0 1".to_string());
+        assert_eq!(
+            kinds(&notation),
+            [
+                (Some(1), ProblemKind::UnknownAnnotation),
+                (Some(2), ProblemKind::FenMismatch),
+                (None, ProblemKind::CurrentFenMismatch)
+            ]
+        );
+
+        let report = notation.repair().unwrap();
+        assert_eq!((report.fens_fixed, report.annotations_removed, report.entries_removed), (1, 1, 0));
+        assert_eq!(notation.moves[1].annotation.as_deref(), Some("?!"));
+        assert!(notation.check().is_empty());
+    }
+
+    #[test]
+    fn variations_are_checked_from_their_branch_point() {
+        let mut notation = game(&["e1e2", "d9d8"]);
+        // Red's alternative first move, then an illegal third one
+        let mut variation = game(&["e1f1", "d9d8", "f1f2"]).moves;
+        variation[2] = entry("move", "a1a2");
+        notation.moves[0].variations.push(variation);
+
+        let problems = notation.check();
+        assert_eq!(problems.len(), 1);
+        assert_eq!((problems[0].entry, problems[0].kind), (Some(1), ProblemKind::IllegalEntry));
+        assert!(problems[0].message.starts_with("Variation 1, move 3: "), "{}", problems[0].message);
+
+        let report = notation.repair().unwrap();
+        assert_eq!(report.entries_removed, 1);
+        assert_eq!(notation.moves[0].variations[0].len(), 2);
+        assert!(notation.check().is_empty());
+    }
+
+    #[test]
+    fn adjustments_need_a_piece_that_can_be_dark() {
+        let mut position = Position::from_fen("3k5/9/9/9/9/9/9/9/4R4/4K4 w - - 0 1").unwrap();
+        apply_entry(&mut position, &entry("adjust", "r+")).unwrap();
+        apply_entry(&mut position, &entry("adjust", "captured_r+")).unwrap();
+        assert_eq!((position.hidden[&'r'], position.captured_hidden[&'r']), (0, 1));
+        for data in ["K+", "z+", "1-", "r-", "R"] {
+            assert!(apply_entry(&mut position, &entry("adjust", data)).is_err(), "{}", data);
+        }
+    }
+}
@@ -0,0 +1,1014 @@
+use crate::notation::{apply_entry, GameNotation, NotationMetadata, NotationMove};
+use crate::position::{initial_role, square, square_to_uci, JieqiMove, Piece, Position, Side};
+use encoding_rs::GBK;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotationFormat {
+    Json,
+    Xqf,
+    Pgn,
+    // Move lists only; these cannot be read back
+    Wxf,
+    Chinese,
+    ChineseTraditional,
+}
+
+impl NotationFormat {
+    // Guessed from the file extension; anything unknown is treated as the JSON format
+    pub fn from_path<P: AsRef<Path>>(path: P) -> NotationFormat {
+        let extension = path.as_ref().extension().map(|e| e.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("xqf") => NotationFormat::Xqf,
+            Some("pgn") => NotationFormat::Pgn,
+            Some("wxf") => NotationFormat::Wxf,
+            _ => NotationFormat::Json,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            NotationFormat::Json => "json",
+            NotationFormat::Xqf => "xqf",
+            NotationFormat::Pgn => "pgn",
+            NotationFormat::Wxf => "wxf",
+            NotationFormat::Chinese | NotationFormat::ChineseTraditional => "txt",
+        }
+    }
+}
+
+/// Reads every game in a JSON, XQF or PGN file.
+pub fn read_games<P: AsRef<Path>>(path: P) -> Result<Vec<GameNotation>, String> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    match NotationFormat::from_path(path) {
+        NotationFormat::Xqf => Ok(vec![read_xqf(&bytes, None)?]),
+        NotationFormat::Pgn => from_pgn_games(&decode_text(&bytes)),
+        NotationFormat::Json => Ok(vec![GameNotation::from_json(&decode_text(&bytes))?]),
+        other => Err(format!("{:?} files cannot be imported", other)),
+    }
+}
+
+// Reads the first game of a file
+pub fn read_notation<P: AsRef<Path>>(path: P) -> Result<GameNotation, String> {
+    let path = path.as_ref();
+    read_games(path)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("No game found in {}", path.display()))
+}
+
+/// Writes a game in a text format. XQF is read-only.
+pub fn export_notation(notation: &GameNotation, format: NotationFormat) -> Result<String, String> {
+    match format {
+        NotationFormat::Json => notation.to_json(),
+        NotationFormat::Pgn => to_pgn(notation),
+        NotationFormat::Wxf => move_list_text(notation, &render_wxf),
+        NotationFormat::Chinese => move_list_text(notation, &|d| render_chinese(d, false)),
+        NotationFormat::ChineseTraditional => move_list_text(notation, &|d| render_chinese(d, true)),
+        NotationFormat::Xqf => Err("Writing XQF files is not supported".to_string()),
+    }
+}
+
+// Files from Chinese tools are often GBK rather than UTF-8
+fn decode_text(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => GBK.decode(bytes).0.into_owned(),
+    }
+}
+
+fn format_number(value: f64) -> String {
+    if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+        (value as i64).to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+// Replays a line from `position` and stores the position after each entry, as the frontend
+// does. Variations are filled from the position before the entry they replace.
+fn fill_line_fens(line: &mut [NotationMove], mut position: Position) -> Result<Position, String> {
+    for (index, entry) in line.iter_mut().enumerate() {
+        for variation in &mut entry.variations {
+            fill_line_fens(variation, position.clone()).map_err(|e| format!("Variation at entry {}: {}", index + 1, e))?;
+        }
+        apply_entry(&mut position, entry).map_err(|e| format!("Entry {}: {}", index + 1, e))?;
+        entry.fen = position.to_fen();
+    }
+    Ok(position)
+}
+
+fn fill_fens(notation: &mut GameNotation) -> Result<(), String> {
+    let position = Position::from_fen(notation.initial_fen())?;
+    let last = fill_line_fens(&mut notation.moves, position)?;
+    notation.metadata.current_fen = Some(last.to_fen());
+    Ok(())
+}
+
+// ----- XQF -----
+
+const XQF_HEADER_LEN: usize = 1024;
+// Piece order of the 32 position bytes in the header
+const XQF_PIECES: &[u8; 32] = b"RNBAKABNRCCPPPPPrnbakabnrccppppp";
+const XQF_WATERMARK: &[u8; 32] = b"[(C) Copyright Mr. Dong Shiwei.]";
+
+// Decryption keys; all zero for unencrypted files (version 15 and below)
+#[derive(Default)]
+struct XqfKeys {
+    f32: [u8; 32],
+    xyp: u8,
+    xyf: u8,
+    xyt: u8,
+    rmk: i64,
+}
+
+impl XqfKeys {
+    fn from_header(header: &[u8]) -> XqfKeys {
+        let mut keys = XqfKeys::default();
+        if header[2] <= 15 {
+            return keys;
+        }
+        let (mask, sum, key_xyp, key_xyf, key_xyt) = (header[3], header[12], header[13], header[14], header[15]);
+        let f_key = [
+            (sum & mask) | header[8],
+            (key_xyp & mask) | header[9],
+            (key_xyf & mask) | header[10],
+            (key_xyt & mask) | header[11],
+        ];
+        for (i, f) in keys.f32.iter_mut().enumerate() {
+            *f = f_key[i % 4] & XQF_WATERMARK[i];
+        }
+        let mix = |k: u8, by: u8| (((k as u32 * k as u32 * 54 + 221) * by as u32) & 0xff) as u8;
+        keys.xyp = mix(key_xyp, key_xyp);
+        keys.xyf = mix(key_xyf, keys.xyp);
+        keys.xyt = mix(key_xyt, keys.xyf);
+        keys.rmk = ((((sum as i64) << 8) + key_xyp as i64) % 32000 + 767) & 0xffff;
+        keys
+    }
+}
+
+// XQF squares are `file * 10 + rank` with rank 0 at the bottom
+fn xqf_square(code: u8) -> Option<usize> {
+    let (col, rank) = ((code / 10) as usize, (code % 10) as usize);
+    (col < 9).then(|| square(9 - rank, col))
+}
+
+// Header strings are length-prefixed GBK
+fn xqf_string(header: &[u8], length_offset: usize) -> Option<String> {
+    let length = header[length_offset] as usize;
+    let bytes = &header[length_offset + 1..(length_offset + 1 + length).min(XQF_HEADER_LEN)];
+    let bytes = bytes.split(|b| *b == 0).next().unwrap_or_default();
+    let text = GBK.decode(bytes).0.trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+struct XqfRecord {
+    from: usize,
+    to: usize,
+    comment: Option<String>,
+}
+
+// The move block is a depth-first dump of the variation tree; the main line is the first
+// record (the game comment) followed by the chain of first children
+fn xqf_main_line(data: &[u8], version: u8, keys: &XqfKeys) -> (Option<String>, Vec<XqfRecord>) {
+    // Flag marking a record that has a following move
+    let has_next = if version > 10 { 0x80 } else { 0xf0 };
+    let mut game_comment = None;
+    let mut records = Vec::new();
+    let mut pos = 0;
+    while pos + 4 <= data.len() {
+        let (from_byte, to_byte, flags) = (data[pos], data[pos + 1], data[pos + 2]);
+        let mut next = 4;
+        let mut comment = None;
+        // Newer files only store a length when the comment flag is set
+        if version <= 10 || flags & 0x20 != 0 {
+            if pos + 8 > data.len() {
+                break;
+            }
+            let raw = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]]) as i64;
+            let length = if version > 10 { raw - keys.rmk } else { raw };
+            let length = if (0..=100_000).contains(&length) { length as usize } else { 0 };
+            if length > 0 && pos + 8 + length <= data.len() {
+                let bytes = &data[pos + 8..pos + 8 + length];
+                let bytes = bytes.split(|b| *b == 0).next().unwrap_or_default();
+                comment = Some(GBK.decode(bytes).0.trim().to_string()).filter(|c| !c.is_empty());
+            }
+            next = 8 + length;
+        }
+
+        if pos == 0 {
+            game_comment = comment;
+        } else {
+            let from = xqf_square(from_byte.wrapping_sub(24).wrapping_sub(keys.xyf));
+            let to = xqf_square(to_byte.wrapping_sub(32).wrapping_sub(keys.xyt));
+            if let (Some(from), Some(to)) = (from, to) {
+                records.push(XqfRecord { from, to, comment });
+            }
+        }
+        if flags & has_next == 0 {
+            break;
+        }
+        pos += next;
+    }
+    (game_comment, records)
+}
+
+// Engine data written by some tools into move comments as `s:<score> t:<ms>`
+fn comment_number(comment: &str, prefix: &str) -> Option<f64> {
+    comment
+        .split(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | '(' | ')'))
+        .find_map(|word| word.strip_prefix(prefix)?.parse::<i64>().ok())
+        .map(|n| n as f64)
+}
+
+/// Converts an XQF file into a Jieqi game, mirroring convertXQFToJieqiNotation: kings and
+/// pieces off the standard start squares are revealed, every other piece starts dark and is
+/// revealed as the piece the file has on that square when it first moves. In `random` flip
+/// mode (the default) captured dark pieces are taken out of the pool as well.
+pub fn read_xqf(bytes: &[u8], flip_mode: Option<&str>) -> Result<GameNotation, String> {
+    if bytes.len() < XQF_HEADER_LEN || &bytes[0..2] != b"XQ" {
+        return Err("Not an XQF file".to_string());
+    }
+    let header = &bytes[..XQF_HEADER_LEN];
+    let version = header[2];
+    let keys = XqfKeys::from_header(header);
+
+    // The real pieces, as recorded by the file
+    let mut actual: [Option<char>; 90] = [None; 90];
+    for (i, &code) in header[16..48].iter().enumerate() {
+        let (piece_index, code) = if version > 11 {
+            ((keys.xyp as usize + i + 1) & 31, code.wrapping_sub(keys.xyp))
+        } else {
+            (i, code)
+        };
+        if code < 90 {
+            if let Some(sq) = xqf_square(code) {
+                actual[sq] = Some(XQF_PIECES[piece_index] as char);
+            }
+        }
+    }
+
+    let mut data = bytes[XQF_HEADER_LEN..].to_vec();
+    if version > 15 {
+        for (i, b) in data.iter_mut().enumerate() {
+            *b = b.wrapping_sub(keys.f32[i % 32]);
+        }
+    }
+    let (game_comment, records) = xqf_main_line(&data, version, &keys);
+
+    let mut position = Position {
+        board: [None; 90],
+        // The side is taken from the first move when there is one, as WhoPlay is often wrong
+        side_to_move: match records.first().and_then(|r| actual[r.from]) {
+            Some(c) => Side::of_char(c),
+            None if header[50] == 1 => Side::Black,
+            None => Side::Red,
+        },
+        hidden: HashMap::new(),
+        captured_hidden: HashMap::new(),
+        halfmove: 0,
+        fullmove: (u16::from_le_bytes([header[48], header[49]]) as u32 / 2).max(1),
+    };
+    for (sq, piece) in actual.iter().enumerate() {
+        let Some(c) = *piece else { continue };
+        position.board[sq] = Some(if c.eq_ignore_ascii_case(&'K') || initial_role(sq).is_none() {
+            Piece::Known(c)
+        } else {
+            *position.hidden.entry(c).or_insert(0) += 1;
+            Piece::Dark(Side::of_char(c))
+        });
+    }
+    let initial_fen = position.to_fen();
+
+    let flip_mode = flip_mode.unwrap_or("random").to_string();
+    let mut moves = Vec::with_capacity(records.len());
+    for (index, record) in records.iter().enumerate() {
+        let mut mv = JieqiMove::new(record.from, record.to);
+        // Dark pieces have not moved yet, so the file's piece on their square is their identity
+        if position.needs_reveal(&mv) {
+            mv.reveal = actual[record.from];
+        }
+        if flip_mode == "random" && position.captures_dark(&mv) {
+            mv.captured_hidden = actual[record.to];
+        }
+        position.apply(&mv).map_err(|e| format!("XQF move {}: {}", index + 1, e))?;
+        let comment = record.comment.clone();
+        moves.push(NotationMove {
+            kind: "move".to_string(),
+            data: mv.to_string(),
+            fen: position.to_fen(),
+            engine_score: comment.as_deref().and_then(|c| comment_number(c, "s:")),
+            engine_time: comment.as_deref().and_then(|c| comment_number(c, "t:")),
+            comment,
+            ..Default::default()
+        });
+    }
+
+    let result = match header[51] {
+        1 => "1-0",
+        2 => "0-1",
+        3 => "1/2-1/2",
+        _ => "*",
+    };
+    Ok(GameNotation {
+        metadata: NotationMetadata {
+            event: xqf_string(header, 208).or_else(|| xqf_string(header, 80)).or_else(|| Some("揭棋对局".to_string())),
+            site: xqf_string(header, 288).or_else(|| Some("jieqibox".to_string())),
+            date: xqf_string(header, 272),
+            white: xqf_string(header, 304).or_else(|| Some("红方".to_string())),
+            black: xqf_string(header, 320).or_else(|| Some("黑方".to_string())),
+            result: Some(result.to_string()),
+            initial_fen: Some(initial_fen),
+            flip_mode: Some(flip_mode),
+            current_fen: Some(position.to_fen()),
+            opening_comment: game_comment,
+            ..Default::default()
+        },
+        moves,
+    })
+}
+
+// ----- PGN-style text -----
+
+const RESULTS: [&str; 4] = ["1-0", "0-1", "1/2-1/2", "*"];
+// Standard PGN numeric annotation glyphs for the move annotations the app uses
+const NAGS: [(&str, &str); 6] = [("1", "!"), ("2", "?"), ("3", "!!"), ("4", "??"), ("5", "!?"), ("6", "?!")];
+
+fn escape_tag(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Comments cannot contain a closing brace
+fn escape_comment(text: &str) -> String {
+    text.replace('}', ")")
+}
+
+// Appends the tokens of a line; variations follow the move they replace in parentheses
+fn pgn_line(tokens: &mut Vec<String>, line: &[NotationMove], mut side: Side, mut fullmove: u32) {
+    let mut need_number = true;
+    for entry in line {
+        if !entry.is_move() {
+            tokens.push(format!("{{[%adjust {}]}}", entry.data));
+            need_number = true;
+            continue;
+        }
+        if side == Side::Red {
+            tokens.push(format!("{}.", fullmove));
+        } else if need_number {
+            tokens.push(format!("{}...", fullmove));
+        }
+        tokens.push(format!("{}{}", entry.data, entry.annotation.as_deref().unwrap_or("")));
+        let mut comment = Vec::new();
+        if let Some(score) = entry.engine_score {
+            comment.push(format!("[%eval {}]", format_number(score)));
+        }
+        if let Some(time) = entry.engine_time {
+            comment.push(format!("[%time {}]", format_number(time)));
+        }
+        if let Some(text) = entry.comment.as_deref().filter(|c| !c.is_empty()) {
+            comment.push(escape_comment(text));
+        }
+        need_number = !comment.is_empty();
+        if need_number {
+            tokens.push(format!("{{{}}}", comment.join(" ")));
+        }
+        for variation in &entry.variations {
+            let mut variation_tokens = Vec::new();
+            pgn_line(&mut variation_tokens, variation, side, fullmove);
+            if let (Some(first), Some(_)) = (variation_tokens.first_mut(), variation.first()) {
+                first.insert(0, '(');
+                variation_tokens.last_mut().expect("not empty").push(')');
+                tokens.extend(variation_tokens);
+                need_number = true;
+            }
+        }
+        if side == Side::Black {
+            fullmove += 1;
+        }
+        side = side.opponent();
+    }
+}
+
+/// Writes the PGN-like text format: tag pairs for the metadata, then the moves in extended
+/// UCI with `{}` comments and variations in parentheses. Engine data is kept as `[%eval N]`
+/// and `[%time MS]` inside the comment of its move, and pool adjustments as `{[%adjust R+]}`.
+pub fn to_pgn(notation: &GameNotation) -> Result<String, String> {
+    let metadata = &notation.metadata;
+    let result = metadata.result.clone().unwrap_or_else(|| "*".to_string());
+    let mut out = String::new();
+    let mut tag = |name: &str, value: Option<&str>| {
+        if let Some(value) = value {
+            out.push_str(&format!("[{} \"{}\"]\n", name, escape_tag(value)));
+        }
+    };
+    tag("Event", Some(metadata.event.as_deref().unwrap_or("?")));
+    tag("Site", Some(metadata.site.as_deref().unwrap_or("?")));
+    tag("Date", Some(metadata.date.as_deref().unwrap_or("????.??.??")));
+    tag("Round", metadata.round.as_deref());
+    tag("Red", Some(metadata.white.as_deref().unwrap_or("?")));
+    tag("Black", Some(metadata.black.as_deref().unwrap_or("?")));
+    tag("Result", Some(&result));
+    tag("FEN", metadata.initial_fen.as_deref());
+    tag("FlipMode", metadata.flip_mode.as_deref());
+    for (name, value) in &metadata.extra {
+        if let Some(value) = value.as_str() {
+            tag(name, Some(value));
+        }
+    }
+    out.push('\n');
+
+    let start = Position::from_fen(notation.initial_fen())?;
+    let mut tokens: Vec<String> = Vec::new();
+    if let Some(comment) = &metadata.opening_comment {
+        tokens.push(format!("{{{}}}", escape_comment(comment)));
+    }
+    pgn_line(&mut tokens, &notation.moves, start.side_to_move, start.fullmove);
+    tokens.push(result);
+
+    // Wrapped at 80 columns like most PGN writers
+    let mut line = String::new();
+    for token in tokens {
+        if !line.is_empty() && line.len() + 1 + token.len() > 80 {
+            out.push_str(&line);
+            out.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    out.push_str(&line);
+    out.push('\n');
+    Ok(out)
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.trim().strip_prefix('[')?.strip_suffix(']')?;
+    let (name, rest) = inner.split_once(char::is_whitespace)?;
+    let quoted = rest.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut value = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.extend(chars.next()),
+            c => value.push(c),
+        }
+    }
+    Some((name.to_string(), value))
+}
+
+enum Token {
+    Word(String),
+    Comment(String),
+    Nag(String),
+    VariationStart,
+    VariationEnd,
+}
+
+fn tokenize_movetext(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '{' => {
+                chars.next();
+                let comment: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                tokens.push(Token::Comment(comment));
+            }
+            ';' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                tokens.push(Token::Comment(comment[1..].to_string()));
+            }
+            '(' | ')' => {
+                chars.next();
+                tokens.push(if c == '(' { Token::VariationStart } else { Token::VariationEnd });
+            }
+            '$' => {
+                chars.next();
+                let mut nag = String::new();
+                while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    nag.push(d);
+                    chars.next();
+                }
+                tokens.push(Token::Nag(nag));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek().filter(|c| !c.is_whitespace() && !matches!(c, '{' | '(' | ')' | ';' | '$')) {
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// Splits `[%name value]` commands out of a comment; returns them with the remaining text
+fn comment_commands(comment: &str) -> (Vec<(String, String)>, String) {
+    let mut commands = Vec::new();
+    let mut text = String::new();
+    let mut rest = comment;
+    while let Some(start) = rest.find("[%") {
+        let Some(end) = rest[start..].find(']') else { break };
+        text.push_str(&rest[..start]);
+        let body = &rest[start + 2..start + end];
+        let (name, value) = body.split_once(char::is_whitespace).unwrap_or((body, ""));
+        commands.push((name.to_string(), value.trim().to_string()));
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+    (commands, text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+fn parse_pgn_game(tags: Vec<(String, String)>, movetext: &str) -> Result<GameNotation, String> {
+    let mut metadata = NotationMetadata::default();
+    for (name, value) in tags {
+        let unknown = |value: &str| if value == "?" || value.starts_with("????") { None } else { Some(value.to_string()) };
+        match name.as_str() {
+            "Event" => metadata.event = unknown(&value),
+            "Site" => metadata.site = unknown(&value),
+            "Date" => metadata.date = unknown(&value),
+            "Round" => metadata.round = unknown(&value),
+            "Red" | "White" => metadata.white = unknown(&value),
+            "Black" => metadata.black = unknown(&value),
+            "Result" => metadata.result = Some(value),
+            "FEN" => metadata.initial_fen = Some(value),
+            "FlipMode" => metadata.flip_mode = Some(value),
+            _ => {
+                metadata.extra.insert(name, serde_json::Value::String(value));
+            }
+        }
+    }
+
+    // The line being read, above it the lines its variations branch off from
+    let mut lines: Vec<Vec<NotationMove>> = vec![Vec::new()];
+    for token in tokenize_movetext(movetext)? {
+        let depth = lines.len();
+        let moves = lines.last_mut().expect("the mainline is never popped");
+        match token {
+            Token::VariationStart => {
+                if moves.last().is_none() {
+                    return Err("Variation before the first move".to_string());
+                }
+                lines.push(Vec::new());
+            }
+            Token::VariationEnd => {
+                if depth == 1 {
+                    return Err("Unbalanced ')'".to_string());
+                }
+                let variation = lines.pop().expect("depth is above 1");
+                if let Some(replaced) = lines.last_mut().and_then(|line| line.last_mut()) {
+                    replaced.variations.push(variation);
+                }
+            }
+            Token::Comment(comment) => {
+                let (commands, text) = comment_commands(&comment);
+                if let Some((_, adjustment)) = commands.iter().find(|(name, _)| name == "adjust") {
+                    moves.push(NotationMove { kind: "adjust".to_string(), data: adjustment.clone(), ..Default::default() });
+                    continue;
+                }
+                let Some(last) = moves.last_mut().filter(|m| m.is_move()) else {
+                    if depth == 1 && !text.is_empty() {
+                        metadata.opening_comment = Some(text);
+                    }
+                    continue;
+                };
+                for (name, value) in commands {
+                    let number = value.parse::<f64>().ok();
+                    match name.as_str() {
+                        "eval" => last.engine_score = number,
+                        "time" => last.engine_time = number,
+                        _ => {}
+                    }
+                }
+                if !text.is_empty() {
+                    last.comment = Some(text);
+                }
+            }
+            Token::Nag(nag) => {
+                if let (Some(last), Some((_, annotation))) = (moves.last_mut(), NAGS.iter().find(|(n, _)| *n == nag)) {
+                    last.annotation = Some(annotation.to_string());
+                }
+            }
+            Token::Word(word) => {
+                if RESULTS.contains(&word.as_str()) {
+                    metadata.result.get_or_insert(word);
+                    continue;
+                }
+                // Move numbers may be attached to the move, as in `1.h2e2`
+                let word = word.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                if word.is_empty() {
+                    continue;
+                }
+                let split = word.find(['!', '?']).unwrap_or(word.len());
+                let (data, annotation) = word.split_at(split);
+                moves.push(NotationMove {
+                    kind: "move".to_string(),
+                    data: data.to_string(),
+                    annotation: (!annotation.is_empty()).then(|| annotation.to_string()),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    if lines.len() > 1 {
+        return Err("Unterminated variation".to_string());
+    }
+    let mut notation = GameNotation { metadata, moves: lines.pop().expect("the mainline is never popped") };
+    fill_fens(&mut notation)?;
+    Ok(notation)
+}
+
+/// Parses every game of a PGN-style file. Games are separated by their tag sections.
+pub fn from_pgn_games(text: &str) -> Result<Vec<GameNotation>, String> {
+    let mut games = Vec::new();
+    let mut tags = Vec::new();
+    let mut movetext = String::new();
+    for line in text.lines() {
+        if let Some(tag) = parse_tag(line) {
+            if !movetext.trim().is_empty() {
+                games.push(parse_pgn_game(std::mem::take(&mut tags), &movetext).map_err(|e| format!("Game {}: {}", games.len() + 1, e))?);
+                movetext.clear();
+            }
+            tags.push(tag);
+        } else {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+    if !tags.is_empty() || !movetext.trim().is_empty() {
+        games.push(parse_pgn_game(tags, &movetext).map_err(|e| format!("Game {}: {}", games.len() + 1, e))?);
+    }
+    Ok(games)
+}
+
+pub fn from_pgn(text: &str) -> Result<GameNotation, String> {
+    from_pgn_games(text)?.into_iter().next().ok_or_else(|| "No game found".to_string())
+}
+
+// ----- WXF and Chinese move notation -----
+
+enum Direction {
+    Forward,
+    Backward,
+    Sideways,
+}
+
+// Second part of a move: the destination file, or the number of ranks moved
+enum Target {
+    File(usize),
+    Steps(usize),
+}
+
+// Everything both notations need to name a move, taken from the position before it
+struct MoveDescription {
+    // Revealed type, or for a dark piece the piece starting on its square; case gives the side
+    letter: char,
+    from_col: usize,
+    // Index counted from the mover's front among same-type pieces on the file, and their count
+    tandem: Option<(usize, usize)>,
+    direction: Direction,
+    target: Target,
+    reveal: Option<char>,
+    captured: Option<char>,
+}
+
+fn notation_letter(position: &Position, sq: usize) -> Option<char> {
+    match position.piece_at(sq)? {
+        Piece::Known(c) => Some(c),
+        Piece::Dark(Side::Red) => initial_role(sq),
+        Piece::Dark(Side::Black) => initial_role(sq).map(|r| r.to_ascii_lowercase()),
+    }
+}
+
+fn describe_move(position: &Position, mv: &JieqiMove) -> Result<MoveDescription, String> {
+    let letter = notation_letter(position, mv.from)
+        .ok_or_else(|| format!("No nameable piece on {}", square_to_uci(mv.from)))?;
+    let side = Side::of_char(letter);
+    let (from_row, from_col) = (mv.from / 9, mv.from % 9);
+    let (to_row, to_col) = (mv.to / 9, mv.to % 9);
+
+    // Rows grow towards Red's side, so Red's front piece has the smallest row
+    let mut same_file: Vec<usize> =
+        (0..10).filter(|&row| notation_letter(position, square(row, from_col)) == Some(letter)).collect();
+    if side == Side::Black {
+        same_file.reverse();
+    }
+    let tandem = (same_file.len() > 1)
+        .then(|| same_file.iter().position(|&row| row == from_row).map(|i| (i, same_file.len())))
+        .flatten();
+
+    let direction = match (side, to_row.cmp(&from_row)) {
+        (_, std::cmp::Ordering::Equal) => Direction::Sideways,
+        (Side::Red, std::cmp::Ordering::Less) | (Side::Black, std::cmp::Ordering::Greater) => Direction::Forward,
+        _ => Direction::Backward,
+    };
+    // Pieces moving along a line count ranks; knights, elephants and advisors name the file
+    let target = match direction {
+        Direction::Sideways => Target::File(to_col),
+        _ if "RCPK".contains(letter.to_ascii_uppercase()) => Target::Steps(from_row.abs_diff(to_row)),
+        _ => Target::File(to_col),
+    };
+    Ok(MoveDescription { letter, from_col, tandem, direction, target, reveal: mv.reveal, captured: mv.captured_hidden })
+}
+
+// Applies `render` to every move of the game, describing each from the position before it
+fn describe_moves(notation: &GameNotation, render: impl Fn(&MoveDescription) -> String) -> Result<Vec<String>, String> {
+    let positions = notation.replay()?;
+    let mut out = Vec::new();
+    for (index, (entry, position)) in notation.moves.iter().zip(positions.iter()).enumerate() {
+        if !entry.is_move() {
+            continue;
+        }
+        let mv = JieqiMove::parse(&entry.data, position.side_to_move)?;
+        let description = describe_move(position, &mv).map_err(|e| format!("Entry {}: {}", index + 1, e))?;
+        out.push(render(&description));
+    }
+    Ok(out)
+}
+
+// Files are numbered 1-9 from each player's right
+fn file_number(side: Side, col: usize) -> usize {
+    if side == Side::Red { 9 - col } else { col + 1 }
+}
+
+fn wxf_letter(letter: char) -> char {
+    match letter.to_ascii_uppercase() {
+        'N' => 'H',
+        'B' => 'E',
+        other => other,
+    }
+}
+
+fn render_wxf(d: &MoveDescription) -> String {
+    let side = Side::of_char(d.letter);
+    let piece = wxf_letter(d.letter);
+    // Two or three pieces on a file are `+`, `=`, `-` from the front; more are numbered
+    let mut out = match d.tandem {
+        Some((index, count)) if count > 3 => format!("{}{}", index + 1, piece),
+        Some((0, _)) => format!("{}+", piece),
+        Some((index, count)) if index + 1 == count => format!("{}-", piece),
+        Some(_) => format!("{}=", piece),
+        None => format!("{}{}", piece, file_number(side, d.from_col)),
+    };
+    out.push(match d.direction {
+        Direction::Forward => '+',
+        Direction::Backward => '-',
+        Direction::Sideways => '.',
+    });
+    match d.target {
+        Target::File(col) => out.push_str(&file_number(side, col).to_string()),
+        Target::Steps(steps) => out.push_str(&steps.to_string()),
+    }
+    if let Some(reveal) = d.reveal {
+        out.push_str(&format!("({})", wxf_letter(reveal)));
+    }
+    if let Some(captured) = d.captured {
+        out.push_str(&format!("x{}", wxf_letter(captured)));
+    }
+    out
+}
+
+/// WXF move notation. Jieqi extras follow the move: `(R)` for the piece a dark piece
+/// revealed and `xC` for the identity of a captured dark piece.
+pub fn to_wxf_moves(notation: &GameNotation) -> Result<Vec<String>, String> {
+    describe_moves(notation, render_wxf)
+}
+
+const RED_NUMERALS: [&str; 10] = ["零", "一", "二", "三", "四", "五", "六", "七", "八", "九"];
+const FULL_WIDTH_DIGITS: [&str; 10] = ["０", "１", "２", "３", "４", "５", "６", "７", "８", "９"];
+const TRADITIONAL: [(char, char); 6] = [('马', '馬'), ('车', '車'), ('帅', '帥'), ('将', '將'), ('进', '進'), ('后', '後')];
+
+fn chinese_name(letter: char) -> &'static str {
+    match letter {
+        'R' | 'r' => "车",
+        'N' | 'n' => "马",
+        'B' => "相",
+        'b' => "象",
+        'A' => "仕",
+        'a' => "士",
+        'C' | 'c' => "炮",
+        'P' => "兵",
+        'p' => "卒",
+        'K' => "帅",
+        'k' => "将",
+        _ => "?",
+    }
+}
+
+// Red writes numbers as Chinese numerals, Black as full-width digits
+fn chinese_number(side: Side, n: usize) -> &'static str {
+    if side == Side::Red { RED_NUMERALS[n] } else { FULL_WIDTH_DIGITS[n] }
+}
+
+fn render_chinese(d: &MoveDescription, traditional: bool) -> String {
+    let side = Side::of_char(d.letter);
+    let name = chinese_name(d.letter);
+    let direction = match d.direction {
+        Direction::Forward => "进",
+        Direction::Backward => "退",
+        Direction::Sideways => "平",
+    };
+    let target = match d.target {
+        Target::File(col) => chinese_number(side, file_number(side, col)),
+        Target::Steps(steps) => chinese_number(side, steps),
+    };
+    let mut out = match d.tandem {
+        Some((index, count)) => {
+            // Jieqi can stack up to six pawns on a file: 前二三四五后
+            let label = match (index, count) {
+                (0, _) => "前",
+                (i, c) if i + 1 == c => "后",
+                (i, c) if c > 3 && c <= 6 && d.letter.eq_ignore_ascii_case(&'P') => RED_NUMERALS[i + 1],
+                _ => "中",
+            };
+            format!("{}{}{}{}", label, name, direction, target)
+        }
+        None => format!("{}{}{}{}", name, chinese_number(side, file_number(side, d.from_col)), direction, target),
+    };
+    if let Some(reveal) = d.reveal {
+        out.push_str(&format!("翻{}", chinese_name(reveal)));
+    }
+    if let Some(captured) = d.captured {
+        out.push_str(&format!("吃{}", chinese_name(captured)));
+    }
+    if traditional {
+        out = out.chars().map(|c| TRADITIONAL.iter().find(|(s, _)| *s == c).map(|(_, t)| *t).unwrap_or(c)).collect();
+    }
+    out
+}
+
+/// Chinese move notation as produced by chineseNotation.ts, e.g. `炮二平五翻车`.
+pub fn to_chinese_moves(notation: &GameNotation, traditional: bool) -> Result<Vec<String>, String> {
+    describe_moves(notation, |d| render_chinese(d, traditional))
+}
+
+// Appends a line of move-list text starting from `position`, with variations in parentheses
+fn move_list_line(
+    out: &mut String,
+    line: &[NotationMove],
+    mut position: Position,
+    render: &dyn Fn(&MoveDescription) -> String,
+    mainline: bool,
+) -> Result<(), String> {
+    let mut need_number = true;
+    for (index, entry) in line.iter().enumerate() {
+        if entry.is_move() {
+            let mv = JieqiMove::parse(&entry.data, position.side_to_move)?;
+            let text = render(&describe_move(&position, &mv).map_err(|e| format!("Entry {}: {}", index + 1, e))?);
+            // The mainline gets one line per move pair
+            let separator = if mainline && position.side_to_move == Side::Red { "\n" } else { " " };
+            if !out.is_empty() && !out.ends_with('(') {
+                out.push_str(separator);
+            }
+            if position.side_to_move == Side::Red {
+                out.push_str(&format!("{}. ", position.fullmove));
+            } else if need_number {
+                out.push_str(&format!("{}. ... ", position.fullmove));
+            }
+            out.push_str(&text);
+            need_number = false;
+            for variation in &entry.variations {
+                out.push_str(" (");
+                move_list_line(out, variation, position.clone(), render, false)?;
+                out.push(')');
+                need_number = true;
+            }
+        }
+        apply_entry(&mut position, entry).map_err(|e| format!("Entry {}: {}", index + 1, e))?;
+    }
+    Ok(())
+}
+
+// Numbered move list followed by the result
+fn move_list_text(notation: &GameNotation, render: &dyn Fn(&MoveDescription) -> String) -> Result<String, String> {
+    let mut out = String::new();
+    move_list_line(&mut out, &notation.moves, Position::from_fen(notation.initial_fen())?, render, true)?;
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(notation.metadata.result.as_deref().unwrap_or("*"));
+    out.push('\n');
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Written by XQStudio-style tools: a version 10 file and an encrypted version 18 file of the
+    // same game, 1. h2e2 h9g7 2. h0g2 b7b0 from the standard start position
+    const PLAIN_XQF: &[u8] = include_bytes!("../tests/fixtures/opening.xqf");
+    const ENCRYPTED_XQF: &[u8] = include_bytes!("../tests/fixtures/opening_encrypted.xqf");
+
+    fn notation(initial_fen: Option<&str>, moves: &[&str]) -> GameNotation {
+        let moves: Vec<NotationMove> = moves
+            .iter()
+            .map(|m| NotationMove { kind: "move".to_string(), data: m.to_string(), ..Default::default() })
+            .collect();
+        let metadata = NotationMetadata { initial_fen: initial_fen.map(str::to_string), ..Default::default() };
+        GameNotation { metadata, moves }
+    }
+
+    #[test]
+    fn pgn_round_trips_comments_adjustments_and_variations() {
+        let json = r#"{
+            "metadata": {
+                "event": "Club \"Open\"", "site": "Online", "date": "2024-05-01", "white": "Red Player",
+                "black": "Black Player", "result": "1-0", "flipMode": "random", "openingComment": "Main line"
+            },
+            "moves": [
+                {"type": "move", "data": "h2e2C", "comment": "centre cannon", "annotation": "!",
+                 "engineScore": 35, "engineTime": 1200},
+                {"type": "move", "data": "h9g7n", "variations": [
+                    [{"type": "move", "data": "b9c7n", "comment": "other knight"},
+                     {"type": "adjust", "data": "R-"},
+                     {"type": "move", "data": "b0c2N"}],
+                    [{"type": "move", "data": "a6a5p"}]
+                ]},
+                {"type": "adjust", "data": "captured_p+"},
+                {"type": "move", "data": "h0g2N", "annotation": "?!"},
+                {"type": "move", "data": "b7b0cN", "engineScore": -120.5}
+            ]
+        }"#;
+        let mut original = GameNotation::from_json(json).unwrap();
+        fill_fens(&mut original).unwrap();
+
+        let pgn = to_pgn(&original).unwrap();
+        assert!(pgn.contains("[Event \"Club \\\"Open\\\"\"]"), "{}", pgn);
+        assert!(pgn.contains("{[%adjust captured_p+]}"), "{}", pgn);
+        let movetext = pgn.replace('\n', " ");
+        assert!(movetext.contains("(1... b9c7n {other knight} {[%adjust R-]} 2. b0c2N) (1... a6a5p)"), "{}", pgn);
+
+        let parsed = from_pgn(&pgn).unwrap();
+        let value = |n: &GameNotation| serde_json::to_value(n).unwrap();
+        assert_eq!(value(&parsed), value(&original), "{}", pgn);
+    }
+
+    #[test]
+    fn pgn_rejects_unbalanced_variations() {
+        assert!(from_pgn("1. h2e2C (1. b2e2C").is_err());
+        assert!(from_pgn("1. h2e2C )").is_err());
+        assert!(from_pgn("(1. h2e2C)").is_err());
+    }
+
+    #[test]
+    fn wxf_and_chinese_name_moves() {
+        let game = notation(None, &["h2e2C", "h9g7n", "h0g2N", "b7b0cN"]);
+        assert_eq!(to_wxf_moves(&game).unwrap(), ["C2.5(C)", "H8+7(H)", "H2+3(H)", "C2+7(C)xH"]);
+        assert_eq!(to_chinese_moves(&game, false).unwrap(), ["炮二平五翻炮", "马８进７翻马", "马二进三翻马", "炮２进７翻炮吃马"]);
+        assert_eq!(to_chinese_moves(&game, true).unwrap()[3], "炮２進７翻炮吃馬");
+
+        // Two rooks on a file are told apart by front and rear
+        let fen = "3k5/9/9/9/9/4R4/9/4R4/9/4K4 w - - 0 1";
+        assert_eq!(to_wxf_moves(&notation(Some(fen), &["e4e7"])).unwrap(), ["R++3"]);
+        assert_eq!(to_wxf_moves(&notation(Some(fen), &["e2a2"])).unwrap(), ["R-.9"]);
+        assert_eq!(to_chinese_moves(&notation(Some(fen), &["e4e7"]), false).unwrap(), ["前车进三"]);
+        assert_eq!(to_chinese_moves(&notation(Some(fen), &["e2a2"]), true).unwrap(), ["後車平九"]);
+
+        let text = export_notation(&game, NotationFormat::Wxf).unwrap();
+        assert_eq!(text, "1. C2.5(C) H8+7(H)\n2. H2+3(H) C2+7(C)xH\n*\n");
+    }
+
+    #[test]
+    fn reads_plain_and_encrypted_xqf() {
+        for bytes in [PLAIN_XQF, ENCRYPTED_XQF] {
+            let game = read_xqf(bytes, None).unwrap();
+            let metadata = &game.metadata;
+            assert_eq!(metadata.event.as_deref(), Some("测试赛"));
+            assert_eq!(metadata.site.as_deref(), Some("北京"));
+            assert_eq!(metadata.date.as_deref(), Some("2024-05-01"));
+            assert_eq!(metadata.white.as_deref(), Some("红方甲"));
+            assert_eq!(metadata.black.as_deref(), Some("黑方乙"));
+            assert_eq!(metadata.result.as_deref(), Some("1-0"));
+            assert_eq!(metadata.opening_comment.as_deref(), Some("测试对局"));
+            assert_eq!(metadata.initial_fen, Some(Position::startpos().to_fen()));
+
+            let moves: Vec<&str> = game.moves.iter().map(|m| m.data.as_str()).collect();
+            assert_eq!(moves, ["h2e2C", "h9g7n", "h0g2N", "b7b0cN"]);
+            assert_eq!(game.moves[0].comment.as_deref(), Some("s:35 t:1200 中炮"));
+            assert_eq!((game.moves[0].engine_score, game.moves[0].engine_time), (Some(35.0), Some(1200.0)));
+            assert_eq!(game.moves[3].comment.as_deref(), Some("打马"));
+            assert_eq!(metadata.current_fen.as_deref(), Some(game.moves[3].fen.as_str()));
+            assert_eq!(game.replay().unwrap().last().unwrap().to_fen(), game.moves[3].fen);
+        }
+
+        // Without random flips a captured dark piece stays unknown
+        let game = read_xqf(PLAIN_XQF, Some("free")).unwrap();
+        assert_eq!(game.moves[3].data, "b7b0c");
+        assert!(read_xqf(&PLAIN_XQF[..512], None).is_err());
+    }
+}
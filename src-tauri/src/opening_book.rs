@@ -1,3 +1,5 @@
+use crate::notation::GameNotation;
+use crate::position::{JieqiMove, Side};
 use rusqlite::{Connection, Result};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -173,6 +175,48 @@ impl JieqiOpeningBook {
         Ok(())
     }
 
+    // Adds game statistics to a move, creating it with default priority when missing
+    pub fn add_result(&self, fen: &str, uci_move: &str, wins: i32, draws: i32, losses: i32) -> Result<()> {
+        let (key_blob, transform_idx) = compute_key_and_transform(fen);
+        let transformed_uci = transform_uci_move(uci_move, transform_idx);
+        let move_int = uci_to_int(&transformed_uci) as i64;
+
+        self.conn.execute(
+            r#"
+            INSERT INTO openings (key, move, priority, wins, draws, losses, allowed, comment)
+            VALUES (?1, ?2, 0, ?3, ?4, ?5, 1, '')
+            ON CONFLICT(key, move) DO UPDATE SET
+                wins=wins + excluded.wins,
+                draws=draws + excluded.draws,
+                losses=losses + excluded.losses;
+            "#,
+            rusqlite::params![ &key_blob, move_int, wins, draws, losses ],
+        )?;
+        Ok(())
+    }
+
+    // Merges another book into this one: statistics are summed, the higher priority wins
+    // and existing comments are kept. Returns the number of rows inserted or updated.
+    pub fn merge_from<P: AsRef<Path>>(&self, other_path: P) -> Result<usize> {
+        let other = other_path.as_ref().to_string_lossy().to_string();
+        self.conn.execute("ATTACH DATABASE ?1 AS other", rusqlite::params![ other ])?;
+        let merged = self.conn.execute(
+            r#"
+            INSERT INTO openings (key, move, priority, wins, draws, losses, allowed, comment)
+            SELECT key, move, priority, wins, draws, losses, allowed, comment FROM other.openings WHERE true
+            ON CONFLICT(key, move) DO UPDATE SET
+                priority=MAX(priority, excluded.priority),
+                wins=wins + excluded.wins,
+                draws=draws + excluded.draws,
+                losses=losses + excluded.losses,
+                comment=COALESCE(NULLIF(comment, ''), excluded.comment);
+            "#,
+            [],
+        );
+        self.conn.execute("DETACH DATABASE other", [])?;
+        merged
+    }
+
     pub fn export_all(&self) -> Result<Vec<OpeningBookEntry>> {
         let mut entries: HashMap<String, OpeningBookEntry> = HashMap::new();
 
@@ -204,13 +248,42 @@ impl JieqiOpeningBook {
 
         // Sort moves by priority for each entry
         for entry in entries.values_mut() {
-            entry.moves.sort_by(|a, b| b.priority.cmp(&a.priority));
+            entry.moves.sort_by_key(|m| std::cmp::Reverse(m.priority));
         }
 
         Ok(entries.into_values().collect())
     }
 }
 
+// Adds every move of a finished game (up to `max_plies`) with the result credited to the mover.
+// Returns the number of moves recorded; unfinished games are skipped.
+pub fn import_game(book: &JieqiOpeningBook, notation: &GameNotation, max_plies: usize) -> std::result::Result<usize, String> {
+    let red_score = match notation.metadata.result.as_deref() {
+        Some("1-0") => 1,
+        Some("0-1") => -1,
+        Some("1/2-1/2") => 0,
+        _ => return Ok(0),
+    };
+    let positions = notation.replay()?;
+    let mut recorded = 0;
+    for (entry, position) in notation.moves.iter().zip(positions.iter()).take(max_plies) {
+        if !entry.is_move() {
+            continue;
+        }
+        let mv = JieqiMove::parse(&entry.data, position.side_to_move)?;
+        let mover_score = if position.side_to_move == Side::Red { red_score } else { -red_score };
+        let (wins, draws, losses) = match mover_score {
+            1 => (1, 0, 0),
+            0 => (0, 1, 0),
+            _ => (0, 0, 1),
+        };
+        book.add_result(&position.to_fen(), &mv.base_uci(), wins, draws, losses)
+            .map_err(|e| e.to_string())?;
+        recorded += 1;
+    }
+    Ok(recorded)
+}
+
 // FEN processing functions
 fn parse_pool_string(pool_str: &str) -> (HashMap<char, i32>, HashMap<char, i32>) {
     let mut red_pool = HashMap::new();
@@ -411,7 +484,7 @@ fn swap_colors_fen(normalized_fen: &str) -> String {
 // Compute key value, also return the transformation index used
 // Transformation index definitions:
 // 0 = original normalized FEN; 1 = horizontal mirror; 2 = color swap (with vertical flip); 3 = color swap then horizontal mirror
-fn compute_key_and_transform(fen: &str) -> (Vec<u8>, usize) {
+pub(crate) fn compute_key_and_transform(fen: &str) -> (Vec<u8>, usize) {
     let norm_fen = normalize_fen(fen);
     let swapped_fen = swap_colors_fen(&norm_fen);
 
@@ -449,7 +522,7 @@ fn compute_key_and_transform(fen: &str) -> (Vec<u8>, usize) {
 }
 
 // Transform UCI move coordinates according to transformation index. This function is its own inverse (repeated calls with same index restore original).
-fn transform_uci_move(uci: &str, transform_idx: usize) -> String {
+pub(crate) fn transform_uci_move(uci: &str, transform_idx: usize) -> String {
     if uci.len() != 4 {
         return uci.to_string();
     }
@@ -489,7 +562,7 @@ fn transform_uci_move(uci: &str, transform_idx: usize) -> String {
     format!("{}{}{}{}", fx, fy, tx, ty)
 }
 
-fn uci_to_int(uci: &str) -> u16 {
+pub(crate) fn uci_to_int(uci: &str) -> u16 {
     if uci.len() != 4 {
         return 0;
     }
@@ -0,0 +1,169 @@
+use crate::engine::{PlayingEngine, SearchLimits, SearchResult};
+use crate::position::{JieqiMove, Piece, Position, Side};
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PonderConfig {
+    pub enabled: bool,
+    // Also ponder predicted moves of or onto a dark piece. What it turns out to be is unknown
+    // until the move is made, so the engine searches a guess; only the same reveal is a hit.
+    pub dark_moves: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PonderOutcome {
+    // The engine was not pondering
+    #[default]
+    None,
+    Hit,
+    // The opponent played something else; the ponder search was thrown away
+    Miss,
+}
+
+/// The predicted reply to ponder on in `position` (after our move), if it is legal and
+/// `config` allows it.
+pub fn ponder_move(position: &Position, ponder: &str, config: &PonderConfig) -> Option<JieqiMove> {
+    if !config.enabled {
+        return None;
+    }
+    let mv = JieqiMove::parse(ponder, position.side_to_move).ok().filter(|mv| position.is_legal(mv.from, mv.to))?;
+    let dark = [mv.from, mv.to].iter().any(|&sq| matches!(position.board[sq], Some(Piece::Dark(_))));
+    if dark && !config.dark_moves {
+        return None;
+    }
+    Some(mv)
+}
+
+/// Starts pondering after our move when the engine predicted a reply worth pondering.
+/// `position` is the position after our move. Returns whether the engine is pondering.
+pub fn start_pondering(
+    engine: &mut dyn PlayingEngine,
+    position: &Position,
+    search: &SearchResult,
+    limits: &SearchLimits,
+    config: &PonderConfig,
+) -> Result<bool, String> {
+    let Some(mv) = search.ponder.as_deref().and_then(|p| ponder_move(position, p, config)) else {
+        return Ok(false);
+    };
+    // With the guessed reveal, if the engine gave one, to tell a hit later
+    engine.start_ponder(&position.to_fen(), &mv.to_string(), limits)?;
+    Ok(true)
+}
+
+/// Our search once the opponent has played `played`, leading to `position`. A predicted move
+/// turns the ponder search into the real one with `ponderhit`; otherwise it is stopped and
+/// `position` searched from scratch. A dark piece must also reveal the predicted type, since
+/// the ponder search assumed it. `elapsed_ms` is the time to charge to our clock: from
+/// the ponderhit, or including the stop on a miss.
+pub fn search_after(
+    engine: &mut dyn PlayingEngine,
+    position: &Position,
+    played: Option<&JieqiMove>,
+    limits: &SearchLimits,
+) -> Result<(SearchResult, PonderOutcome), String> {
+    let Some(predicted) = engine.pondering().map(|p| p.to_string()) else {
+        return Ok((engine.search(&position.to_fen(), limits, limits.timeout())?, PonderOutcome::None));
+    };
+    let mover = position.side_to_move.opponent();
+    let hit = played.is_some_and(|mv| {
+        JieqiMove::parse(&predicted, mover).is_ok_and(|p| p.base_uci() == mv.base_uci() && p.reveal == mv.reveal)
+    });
+    if hit {
+        return Ok((engine.ponder_hit(limits.timeout())?, PonderOutcome::Hit));
+    }
+    let started = Instant::now();
+    engine.stop_ponder()?;
+    // The stop came out of our time as well
+    let spent = started.elapsed().as_millis() as u64;
+    let mut limits = limits.clone();
+    let clock = if position.side_to_move == Side::Red { &mut limits.wtime } else { &mut limits.btime };
+    *clock = clock.map(|ms| ms.saturating_sub(spent));
+    let mut result = engine.search(&position.to_fen(), &limits, limits.timeout())?;
+    result.elapsed_ms += spent;
+    Ok((result, PonderOutcome::Miss))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // Pretends to search and keeps the calls it got
+    #[derive(Default)]
+    struct StubEngine {
+        pondering: Option<String>,
+        calls: Vec<String>,
+    }
+
+    impl PlayingEngine for StubEngine {
+        fn search(&mut self, _: &str, _: &SearchLimits, _: Option<Duration>) -> Result<SearchResult, String> {
+            self.calls.push("search".to_string());
+            Ok(SearchResult::default())
+        }
+
+        fn start_ponder(&mut self, _: &str, ponder_move: &str, _: &SearchLimits) -> Result<(), String> {
+            self.calls.push(format!("ponder {}", ponder_move));
+            self.pondering = Some(ponder_move.to_string());
+            Ok(())
+        }
+
+        fn pondering(&self) -> Option<&str> {
+            self.pondering.as_deref()
+        }
+
+        fn ponder_hit(&mut self, _: Option<Duration>) -> Result<SearchResult, String> {
+            self.calls.push("ponderhit".to_string());
+            self.pondering = None;
+            Ok(SearchResult::default())
+        }
+
+        fn stop_ponder(&mut self) -> Result<(), String> {
+            self.calls.push("stop".to_string());
+            self.pondering = None;
+            Ok(())
+        }
+    }
+
+    // Ponders `predicted` after our h2e2C, then sees `played`
+    fn outcome(predicted: &str, played: &str) -> (PonderOutcome, Vec<String>) {
+        let mut position = Position::startpos();
+        position.apply(&JieqiMove::parse("h2e2C", Side::Red).unwrap()).unwrap();
+        let search = SearchResult { ponder: Some(predicted.to_string()), ..Default::default() };
+        let config = PonderConfig { enabled: true, dark_moves: true };
+        let limits = SearchLimits::default();
+        let mut engine = StubEngine::default();
+        assert!(start_pondering(&mut engine, &position, &search, &limits, &config).unwrap());
+
+        let mv = JieqiMove::parse(played, Side::Black).unwrap();
+        position.apply(&mv).unwrap();
+        let (_, outcome) = search_after(&mut engine, &position, Some(&mv), &limits).unwrap();
+        (outcome, engine.calls)
+    }
+
+    #[test]
+    fn dark_moves_hit_only_with_the_predicted_reveal() {
+        assert_eq!(outcome("h9g7n", "h9g7n"), (PonderOutcome::Hit, vec!["ponder h9g7n".into(), "ponderhit".into()]));
+        assert_eq!(outcome("h9g7n", "h9g7r").0, PonderOutcome::Miss);
+        // Without a guess there is nothing to match the reveal against
+        let (result, calls) = outcome("h9g7", "h9g7n");
+        assert_eq!(result, PonderOutcome::Miss);
+        assert_eq!(calls, ["ponder h9g7", "stop", "search"]);
+        assert_eq!(outcome("h9g7n", "b9c7n").0, PonderOutcome::Miss);
+    }
+
+    #[test]
+    fn revealed_pieces_hit_on_the_move_alone() {
+        let mut position = Position::from_fen("3k5/9/9/9/9/9/9/9/4R4/4K4 w - - 0 1").unwrap();
+        let mut engine = StubEngine::default();
+        let limits = SearchLimits::default();
+        engine.start_ponder(&position.to_fen(), "d9d8", &limits).unwrap();
+        position.side_to_move = Side::Black;
+        let mv = JieqiMove::parse("d9d8", Side::Black).unwrap();
+        position.apply(&mv).unwrap();
+        assert_eq!(search_after(&mut engine, &position, Some(&mv), &limits).unwrap().1, PonderOutcome::Hit);
+    }
+}
@@ -0,0 +1,822 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const START_FEN: &str =
+    "xxxxkxxxx/9/1x5x1/x1x1x1x1x/9/9/X1X1X1X1X/1X5X1/9/XXXXKXXXX w A2B2N2R2C2P5a2b2n2r2c2p5 - 0 1";
+
+// Order used when writing dark piece pools, same as the frontend's generateFen
+const POOL_ORDER: &str = "RNBAKCP";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Red,
+    Black,
+}
+
+impl Side {
+    pub fn opponent(self) -> Side {
+        match self {
+            Side::Red => Side::Black,
+            Side::Black => Side::Red,
+        }
+    }
+
+    pub fn of_char(c: char) -> Side {
+        if c.is_ascii_uppercase() { Side::Red } else { Side::Black }
+    }
+
+    pub fn fen_char(self) -> char {
+        match self {
+            Side::Red => 'w',
+            Side::Black => 'b',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Piece {
+    // Revealed piece, stored as its FEN letter (uppercase for red)
+    Known(char),
+    // Unrevealed piece, moves like the piece that starts on its square
+    Dark(Side),
+}
+
+impl Piece {
+    pub fn side(self) -> Side {
+        match self {
+            Piece::Known(c) => Side::of_char(c),
+            Piece::Dark(side) => side,
+        }
+    }
+
+    pub fn fen_char(self) -> char {
+        match self {
+            Piece::Known(c) => c,
+            Piece::Dark(Side::Red) => 'X',
+            Piece::Dark(Side::Black) => 'x',
+        }
+    }
+
+    pub fn is_dark(self) -> bool {
+        matches!(self, Piece::Dark(_))
+    }
+}
+
+pub fn square(row: usize, col: usize) -> usize {
+    row * 9 + col
+}
+
+pub fn square_to_uci(sq: usize) -> String {
+    let (row, col) = (sq / 9, sq % 9);
+    format!("{}{}", (b'a' + col as u8) as char, 9 - row)
+}
+
+pub fn square_from_uci(s: &str) -> Option<usize> {
+    let bytes = s.as_bytes();
+    if bytes.len() != 2 || !(b'a'..=b'i').contains(&bytes[0]) || !bytes[1].is_ascii_digit() {
+        return None;
+    }
+    let col = (bytes[0] - b'a') as usize;
+    let row = 9 - (bytes[1] - b'0') as usize;
+    Some(square(row, col))
+}
+
+// Role (uppercase letter) of the piece that starts on a square in the standard setup
+pub fn initial_role(sq: usize) -> Option<char> {
+    let (row, col) = (sq / 9, sq % 9);
+    match (row, col) {
+        (0 | 9, 0 | 8) => Some('R'),
+        (0 | 9, 1 | 7) => Some('N'),
+        (0 | 9, 2 | 6) => Some('B'),
+        (0 | 9, 3 | 5) => Some('A'),
+        (0 | 9, 4) => Some('K'),
+        (2 | 7, 1 | 7) => Some('C'),
+        (3 | 6, 0 | 2 | 4 | 6 | 8) => Some('P'),
+        _ => None,
+    }
+}
+
+/// A move in the extended UCI notation used by the notation files, e.g. `a3a4R` or `b2b9Rn`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JieqiMove {
+    pub from: usize,
+    pub to: usize,
+    // Piece type revealed when a dark piece moves
+    pub reveal: Option<char>,
+    // Piece type removed from the opponent's pool when a dark piece is captured
+    pub captured_hidden: Option<char>,
+}
+
+impl JieqiMove {
+    pub fn new(from: usize, to: usize) -> Self {
+        JieqiMove { from, to, reveal: None, captured_hidden: None }
+    }
+
+    // Mirrors parseUciExtended: a single suffix letter of the mover's color is a reveal,
+    // of the opponent's color a capture; two letters are reveal then capture.
+    pub fn parse(uci: &str, mover: Side) -> Result<Self, String> {
+        let uci = uci.trim();
+        if uci.len() < 4 || !uci.is_ascii() {
+            return Err(format!("Invalid move: {}", uci));
+        }
+        let from = square_from_uci(&uci[0..2]).ok_or_else(|| format!("Invalid move: {}", uci))?;
+        let to = square_from_uci(&uci[2..4]).ok_or_else(|| format!("Invalid move: {}", uci))?;
+        let extension: Vec<char> = uci[4..].chars().collect();
+        let mut mv = JieqiMove::new(from, to);
+        match extension.as_slice() {
+            [] => {}
+            [c] if Side::of_char(*c) == mover => mv.reveal = Some(*c),
+            [c] => mv.captured_hidden = Some(*c),
+            [r, c] => {
+                mv.reveal = Some(*r);
+                mv.captured_hidden = Some(*c);
+            }
+            _ => return Err(format!("Invalid move: {}", uci)),
+        }
+        for c in mv.reveal.iter().chain(mv.captured_hidden.iter()) {
+            if !POOL_ORDER.contains(c.to_ascii_uppercase()) {
+                return Err(format!("Invalid piece letter '{}' in move {}", c, uci));
+            }
+        }
+        Ok(mv)
+    }
+
+    pub fn base_uci(&self) -> String {
+        format!("{}{}", square_to_uci(self.from), square_to_uci(self.to))
+    }
+}
+
+impl fmt::Display for JieqiMove {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.base_uci())?;
+        if let Some(c) = self.reveal {
+            write!(f, "{}", c)?;
+        }
+        if let Some(c) = self.captured_hidden {
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub board: [Option<Piece>; 90],
+    pub side_to_move: Side,
+    pub hidden: HashMap<char, i32>,
+    pub captured_hidden: HashMap<char, i32>,
+    pub halfmove: u32,
+    pub fullmove: u32,
+}
+
+fn parse_pool(pool_str: &str) -> Result<HashMap<char, i32>, String> {
+    let mut pool = HashMap::new();
+    if pool_str == "-" || pool_str.is_empty() {
+        return Ok(pool);
+    }
+    let chars: Vec<char> = pool_str.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let piece = chars[i];
+        if !POOL_ORDER.contains(piece.to_ascii_uppercase()) {
+            return Err(format!("Invalid piece '{}' in pool {}", piece, pool_str));
+        }
+        i += 1;
+        let start = i;
+        while i < chars.len() && chars[i].is_ascii_digit() {
+            i += 1;
+        }
+        let count = if start == i {
+            1
+        } else {
+            chars[start..i].iter().collect::<String>().parse::<i32>().map_err(|e| e.to_string())?
+        };
+        if pool.insert(piece, count).is_some() {
+            return Err(format!("Duplicate piece '{}' in pool {}", piece, pool_str));
+        }
+    }
+    Ok(pool)
+}
+
+fn format_pool(pool: &HashMap<char, i32>) -> String {
+    let mut result = String::new();
+    for red in POOL_ORDER.chars() {
+        for c in [red, red.to_ascii_lowercase()] {
+            let count = pool.get(&c).copied().unwrap_or(0);
+            if count > 0 {
+                result.push(c);
+                result.push_str(&count.to_string());
+            }
+        }
+    }
+    if result.is_empty() { "-".to_string() } else { result }
+}
+
+fn strip_position_prefix(fen: &str) -> String {
+    let mut fen = fen.trim();
+    for prefix in ["position fen ", "fen ", "position "] {
+        if let Some(rest) = fen.strip_prefix(prefix) {
+            fen = rest;
+            break;
+        }
+    }
+    let fen = fen.split(" moves ").next().unwrap_or(fen);
+    if let Some(rest) = fen.strip_prefix("startpos") {
+        return format!("{}{}", START_FEN, rest);
+    }
+    fen.to_string()
+}
+
+impl Position {
+    pub fn startpos() -> Self {
+        Position::from_fen(START_FEN).expect("start FEN is valid")
+    }
+
+    // Accepts both the new and the legacy FEN layouts, like loadFen in the frontend
+    pub fn from_fen(fen: &str) -> Result<Self, String> {
+        let fen = strip_position_prefix(fen);
+        let parts: Vec<&str> = fen.split_whitespace().collect();
+        if parts.len() < 2 {
+            return Err(format!("FEN needs at least a board and a side to move: {}", fen));
+        }
+
+        let is_new_format = parts[1] == "w" || parts[1] == "b";
+        let (board_part, side_part, hidden_part, captured_part, halfmove, fullmove) = if is_new_format {
+            match parts.len() {
+                2 => (parts[0], parts[1], "-", "-", "0", "1"),
+                3 => (parts[0], parts[1], parts[2], "-", "0", "1"),
+                4 => (parts[0], parts[1], parts[2], parts[3], "0", "1"),
+                5 => (parts[0], parts[1], parts[2], "-", parts[3], parts[4]),
+                _ => (parts[0], parts[1], parts[2], parts[3], parts[4], parts[5]),
+            }
+        } else {
+            match parts.len() {
+                2 => return Err(format!("Invalid side to move '{}'", parts[1])),
+                3..=5 => (parts[0], parts[2], parts[1], "-", "0", "1"),
+                6 => (parts[0], parts[2], parts[1], "-", parts[5], "1"),
+                _ => (parts[0], parts[2], parts[1], "-", parts[5], parts[6]),
+            }
+        };
+
+        let side_to_move = match side_part {
+            "w" => Side::Red,
+            "b" => Side::Black,
+            other => return Err(format!("Invalid side to move '{}'", other)),
+        };
+
+        let rows: Vec<&str> = board_part.split('/').collect();
+        if rows.len() != 10 {
+            return Err(format!("Board must have 10 rows, found {}", rows.len()));
+        }
+        let mut board = [None; 90];
+        for (row, row_str) in rows.iter().enumerate() {
+            let mut col = 0usize;
+            for c in row_str.chars() {
+                if let Some(empty) = c.to_digit(10) {
+                    if empty == 0 {
+                        return Err(format!("Invalid empty count in row {}", row_str));
+                    }
+                    col += empty as usize;
+                    continue;
+                }
+                if col >= 9 {
+                    return Err(format!("Row '{}' has more than 9 columns", row_str));
+                }
+                board[square(row, col)] = Some(match c {
+                    'X' => Piece::Dark(Side::Red),
+                    'x' => Piece::Dark(Side::Black),
+                    _ if "RNBAKCPrnbakcp".contains(c) => Piece::Known(c),
+                    _ => return Err(format!("Invalid piece character '{}'", c)),
+                });
+                col += 1;
+            }
+            if col != 9 {
+                return Err(format!("Row '{}' does not have exactly 9 columns", row_str));
+            }
+        }
+
+        Ok(Position {
+            board,
+            side_to_move,
+            hidden: parse_pool(hidden_part)?,
+            captured_hidden: parse_pool(captured_part)?,
+            halfmove: halfmove.parse().map_err(|_| format!("Invalid halfmove clock '{}'", halfmove))?,
+            fullmove: fullmove.parse().map_err(|_| format!("Invalid fullmove number '{}'", fullmove))?,
+        })
+    }
+
+    pub fn board_fen(&self) -> String {
+        let mut rows = Vec::with_capacity(10);
+        for row in 0..10 {
+            let mut row_str = String::new();
+            let mut empty = 0;
+            for col in 0..9 {
+                match self.board[square(row, col)] {
+                    Some(piece) => {
+                        if empty > 0 {
+                            row_str.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        row_str.push(piece.fen_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                row_str.push_str(&empty.to_string());
+            }
+            rows.push(row_str);
+        }
+        rows.join("/")
+    }
+
+    // New FEN format: board side hidden captured-hidden halfmove fullmove
+    pub fn to_fen(&self) -> String {
+        format!(
+            "{} {} {} {} {} {}",
+            self.board_fen(),
+            self.side_to_move.fen_char(),
+            format_pool(&self.hidden),
+            format_pool(&self.captured_hidden),
+            self.halfmove,
+            self.fullmove
+        )
+    }
+
+    // Legacy FEN format: board hidden side castling en-passant halfmove fullmove
+    pub fn to_old_fen(&self) -> String {
+        format!(
+            "{} {} {} - - {} {}",
+            self.board_fen(),
+            format_pool(&self.hidden),
+            self.side_to_move.fen_char(),
+            self.halfmove,
+            self.fullmove
+        )
+    }
+
+    pub fn piece_at(&self, sq: usize) -> Option<Piece> {
+        self.board.get(sq).copied().flatten()
+    }
+
+    fn king_square(&self, side: Side) -> Option<usize> {
+        let king = if side == Side::Red { 'K' } else { 'k' };
+        self.board.iter().position(|p| *p == Some(Piece::Known(king)))
+    }
+
+    fn pieces_between(&self, from: usize, to: usize) -> usize {
+        let (r1, c1, r2, c2) = (from / 9, from % 9, to / 9, to % 9);
+        if r1 == r2 {
+            (c1.min(c2) + 1..c1.max(c2)).filter(|&c| self.board[square(r1, c)].is_some()).count()
+        } else if c1 == c2 {
+            (r1.min(r2) + 1..r1.max(r2)).filter(|&r| self.board[square(r, c1)].is_some()).count()
+        } else {
+            0
+        }
+    }
+
+    // Follows isMoveMechanicallyValid in useChessGame.ts; does not look at checks
+    fn is_pseudo_legal(&self, from: usize, to: usize) -> bool {
+        let Some(piece) = self.piece_at(from) else { return false };
+        if from == to || to >= 90 {
+            return false;
+        }
+        let side = piece.side();
+        let target = self.piece_at(to);
+        if target.map(|t| t.side()) == Some(side) {
+            return false;
+        }
+        let role = match piece {
+            Piece::Known(c) => c.to_ascii_uppercase(),
+            Piece::Dark(_) => match initial_role(from) {
+                Some(role) => role,
+                None => return false,
+            },
+        };
+
+        let (row, col) = ((from / 9) as i32, (from % 9) as i32);
+        let (target_row, target_col) = ((to / 9) as i32, (to % 9) as i32);
+        let (d_row, d_col) = ((target_row - row).abs(), (target_col - col).abs());
+
+        match role {
+            'K' => {
+                let (min_row, max_row) = if side == Side::Red { (7, 9) } else { (0, 2) };
+                d_row + d_col == 1 && (3..=5).contains(&target_col) && (min_row..=max_row).contains(&target_row)
+            }
+            'A' => {
+                // Unrevealed advisors may only step towards the palace center
+                if piece.is_dark() && (target_col == 2 || target_col == 6) {
+                    return false;
+                }
+                d_row == 1 && d_col == 1
+            }
+            'B' => {
+                d_row == 2 && d_col == 2
+                    && self.board[square(((row + target_row) / 2) as usize, ((col + target_col) / 2) as usize)].is_none()
+            }
+            'N' => {
+                if !((d_row == 2 && d_col == 1) || (d_row == 1 && d_col == 2)) {
+                    return false;
+                }
+                let leg_row = if d_row == 2 { (row + target_row) / 2 } else { row };
+                let leg_col = if d_col == 2 { (col + target_col) / 2 } else { col };
+                self.board[square(leg_row as usize, leg_col as usize)].is_none()
+            }
+            'R' => (d_row == 0 || d_col == 0) && self.pieces_between(from, to) == 0,
+            'C' => {
+                if d_row != 0 && d_col != 0 {
+                    return false;
+                }
+                let between = self.pieces_between(from, to);
+                if target.is_some() { between == 1 } else { between == 0 }
+            }
+            'P' => {
+                let forward = if side == Side::Red { -1 } else { 1 };
+                let over_river = if side == Side::Red { row <= 4 } else { row >= 5 };
+                let is_forward = target_row - row == forward && d_col == 0;
+                is_forward || (over_river && d_row == 0 && d_col == 1)
+            }
+            _ => false,
+        }
+    }
+
+    // Unrevealed pieces never give check, as in isInCheck on the frontend
+    pub fn in_check(&self, side: Side) -> bool {
+        let Some(king_sq) = self.king_square(side) else { return false };
+        for sq in 0..90 {
+            let Some(Piece::Known(c)) = self.board[sq] else { continue };
+            if Side::of_char(c) == side {
+                continue;
+            }
+            if c.eq_ignore_ascii_case(&'K') {
+                if sq % 9 == king_sq % 9 && self.pieces_between(sq, king_sq) == 0 {
+                    return true;
+                }
+                continue;
+            }
+            if self.is_pseudo_legal(sq, king_sq) {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn is_legal(&self, from: usize, to: usize) -> bool {
+        let Some(piece) = self.piece_at(from) else { return false };
+        if piece.side() != self.side_to_move || !self.is_pseudo_legal(from, to) {
+            return false;
+        }
+        let mut next = self.clone();
+        next.board[to] = next.board[from].take();
+        !next.in_check(piece.side())
+    }
+
+    pub fn legal_moves(&self) -> Vec<JieqiMove> {
+        let mut moves = Vec::new();
+        for from in 0..90 {
+            if self.board[from].map(|p| p.side()) != Some(self.side_to_move) {
+                continue;
+            }
+            for to in 0..90 {
+                if self.is_legal(from, to) {
+                    moves.push(JieqiMove::new(from, to));
+                }
+            }
+        }
+        moves
+    }
+
+    fn side_pool_count(&self, side: Side) -> i32 {
+        self.hidden.iter().filter(|(c, _)| Side::of_char(**c) == side).map(|(_, n)| *n).sum()
+    }
+
+    pub fn needs_reveal(&self, mv: &JieqiMove) -> bool {
+        self.piece_at(mv.from).map(|p| p.is_dark()).unwrap_or(false)
+    }
+
+    pub fn captures_dark(&self, mv: &JieqiMove) -> bool {
+        self.piece_at(mv.to).map(|p| p.is_dark()).unwrap_or(false)
+    }
+
+    // Applies a move; dark pieces must carry the revealed type in `mv.reveal`
+    pub fn apply(&mut self, mv: &JieqiMove) -> Result<(), String> {
+        let piece = self.piece_at(mv.from).ok_or_else(|| format!("No piece on {}", square_to_uci(mv.from)))?;
+        if piece.side() != self.side_to_move {
+            return Err(format!("{} moves a piece of the side not to move", mv));
+        }
+        if !self.is_legal(mv.from, mv.to) {
+            return Err(format!("Illegal move {}", mv));
+        }
+        let mover = piece.side();
+        let target = self.piece_at(mv.to);
+
+        // Everything is checked before the pools change
+        let moved = match (piece, mv.reveal) {
+            (Piece::Dark(_), Some(c)) => {
+                if Side::of_char(c) != mover || self.hidden.get(&c).copied().unwrap_or(0) <= 0 {
+                    return Err(format!("Revealed piece '{}' is not in the dark pool", c));
+                }
+                Piece::Known(c)
+            }
+            (Piece::Dark(_), None) => return Err(format!("Move {} reveals a dark piece but has no piece type", mv)),
+            (Piece::Known(_), Some(_)) => return Err(format!("Move {} reveals a piece that is already known", mv)),
+            (known, None) => known,
+        };
+        if let Some(c) = mv.captured_hidden {
+            if !matches!(target, Some(Piece::Dark(_))) {
+                return Err(format!("Move {} does not capture a dark piece", mv));
+            }
+            if Side::of_char(c) == mover || self.hidden.get(&c).copied().unwrap_or(0) <= 0 {
+                return Err(format!("Captured piece '{}' is not in the dark pool", c));
+            }
+        }
+
+        if let Some(c) = mv.reveal {
+            *self.hidden.entry(c).or_insert(0) -= 1;
+        }
+        if let Some(c) = mv.captured_hidden {
+            *self.hidden.entry(c).or_insert(0) -= 1;
+            *self.captured_hidden.entry(c).or_insert(0) += 1;
+        }
+
+        self.board[mv.from] = None;
+        self.board[mv.to] = Some(moved);
+        self.halfmove = if target.is_some() { 0 } else { self.halfmove + 1 };
+        if mover == Side::Black {
+            self.fullmove += 1;
+        }
+        self.side_to_move = mover.opponent();
+        Ok(())
+    }
+
+    // Fills in the reveal/capture letters of a bare engine move by drawing from the pools,
+    // the same way random flip mode does
+    pub fn complete_random(&self, mv: &JieqiMove, rng: &mut Rng) -> Result<JieqiMove, String> {
+        let mut completed = mv.clone();
+        if self.needs_reveal(mv) && completed.reveal.is_none() {
+            let side = self.side_to_move;
+            if self.side_pool_count(side) <= 0 {
+                return Err(format!("Dark pool of {:?} is empty", side));
+            }
+            completed.reveal = Some(self.draw_from_pool(side, rng));
+        }
+        if self.captures_dark(mv) && completed.captured_hidden.is_none() {
+            let side = self.side_to_move.opponent();
+            if self.side_pool_count(side) > 0 {
+                let mut after_reveal = self.clone();
+                if let Some(c) = completed.reveal {
+                    *after_reveal.hidden.entry(c).or_insert(0) -= 1;
+                }
+                completed.captured_hidden = Some(after_reveal.draw_from_pool(side, rng));
+            }
+        }
+        Ok(completed)
+    }
+
+    fn draw_from_pool(&self, side: Side, rng: &mut Rng) -> char {
+        let mut pool = Vec::new();
+        for c in POOL_ORDER.chars() {
+            let c = if side == Side::Red { c } else { c.to_ascii_lowercase() };
+            for _ in 0..self.hidden.get(&c).copied().unwrap_or(0).max(0) {
+                pool.push(c);
+            }
+        }
+        pool[rng.below(pool.len())]
+    }
+
+    // Result string from Red's point of view, or None while the game continues
+    pub fn game_result(&self) -> Option<&'static str> {
+        let loser_result = |side: Side| if side == Side::Red { "0-1" } else { "1-0" };
+        if self.king_square(self.side_to_move).is_none() {
+            return Some(loser_result(self.side_to_move));
+        }
+        if self.legal_moves().is_empty() {
+            return Some(loser_result(self.side_to_move));
+        }
+        None
+    }
+
+    // Sanity checks on piece counts beyond what from_fen enforces
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let initial: HashMap<char, i32> =
+            [('R', 2), ('N', 2), ('B', 2), ('A', 2), ('K', 1), ('C', 2), ('P', 5)].into_iter().collect();
+
+        for side in [Side::Red, Side::Black] {
+            let king = Piece::Known(if side == Side::Red { 'K' } else { 'k' });
+            let kings = self.board.iter().filter(|p| **p == Some(king)).count();
+            if kings != 1 {
+                problems.push(format!("{:?} has {} kings on the board", side, kings));
+            }
+            let dark_on_board = self.board.iter().filter(|p| **p == Some(Piece::Dark(side))).count() as i32;
+            if dark_on_board > self.side_pool_count(side) {
+                problems.push(format!(
+                    "{:?} has {} dark pieces on the board but only {} in the pool",
+                    side,
+                    dark_on_board,
+                    self.side_pool_count(side)
+                ));
+            }
+        }
+
+        for (&red, &limit) in &initial {
+            for c in [red, red.to_ascii_lowercase()] {
+                let on_board = self.board.iter().filter(|p| **p == Some(Piece::Known(c))).count() as i32;
+                let total = on_board
+                    + self.hidden.get(&c).copied().unwrap_or(0)
+                    + self.captured_hidden.get(&c).copied().unwrap_or(0);
+                if total > limit {
+                    problems.push(format!("Too many '{}' pieces: {} (max {})", c, total, limit));
+                }
+            }
+        }
+
+        for sq in 0..90 {
+            if let Some(Piece::Dark(side)) = self.board[sq] {
+                let on_home_half = if side == Side::Red { sq / 9 >= 5 } else { sq / 9 <= 4 };
+                if initial_role(sq).is_none() || !on_home_half {
+                    problems.push(format!("Dark piece on {} is not on a starting square", square_to_uci(sq)));
+                }
+            }
+        }
+
+        if self.in_check(self.side_to_move.opponent()) {
+            problems.push("The side not to move is in check".to_string());
+        }
+
+        problems.sort();
+        problems
+    }
+}
+
+/// Small xorshift generator used for random reveals; not for anything security related.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.max(1))
+    }
+
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+        Rng::new(nanos ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        if n == 0 { 0 } else { (self.next_u64() % n as u64) as usize }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sq(uci: &str) -> usize {
+        square_from_uci(uci).unwrap()
+    }
+
+    fn mv(uci: &str, pos: &Position) -> JieqiMove {
+        JieqiMove::parse(uci, pos.side_to_move).unwrap()
+    }
+
+    #[test]
+    fn new_fen_round_trips() {
+        let start = Position::startpos();
+        // Pools are written in RNBAKCP order, whatever order they were read in
+        assert_eq!(
+            start.to_fen(),
+            "xxxxkxxxx/9/1x5x1/x1x1x1x1x/9/9/X1X1X1X1X/1X5X1/9/XXXXKXXXX w R2r2N2n2B2b2A2a2C2c2P5p5 - 0 1"
+        );
+        assert_eq!(Position::from_fen(&start.to_fen()).unwrap(), start);
+
+        let fen = "3k5/4a4/9/9/2X6/9/9/9/4A4/4K4 b R1N2 n1 7 31";
+        let pos = Position::from_fen(fen).unwrap();
+        assert_eq!(pos.side_to_move, Side::Black);
+        assert_eq!(pos.captured_hidden.get(&'n'), Some(&1));
+        assert_eq!((pos.halfmove, pos.fullmove), (7, 31));
+        assert_eq!(pos.to_fen(), fen);
+    }
+
+    #[test]
+    fn legacy_fen_round_trips() {
+        let legacy = "xxxxkxxxx/9/1x5x1/x1x1x1x1x/9/9/X1X1X1X1X/1X5X1/9/XXXXKXXXX A2B2N2R2C2P5a2b2n2r2c2p5 w - - 0 1";
+        let pos = Position::from_fen(legacy).unwrap();
+        assert_eq!(pos, Position::startpos());
+        assert_eq!(Position::from_fen(&pos.to_old_fen()).unwrap(), pos);
+        assert_eq!(Position::from_fen("startpos moves a3a4R").unwrap(), pos);
+        assert_eq!(Position::from_fen(&format!("position fen {} moves a3a4R", START_FEN)).unwrap(), pos);
+    }
+
+    #[test]
+    fn rejects_malformed_fens() {
+        assert!(Position::from_fen("9/9/9 w").is_err());
+        assert!(Position::from_fen("xxxxkxxxx/9/1x5x1/x1x1x1x1x/9/9/X1X1X1X1X/1X5X1/9/XXXXKXXXX r - - 0 1").is_err());
+        assert!(Position::from_fen("xxxxkxxxx/9/1x5x1/x1x1x1x1x/9/9/X1X1X1X1X/1X5X1/9/XXXXKXXXQ w - - 0 1").is_err());
+        assert!(Position::from_fen("xxxxkxxxx/9/1x5x1/x1x1x1x1x/9/9/X1X1X1X1X/1X5X1/9/XXXXKXXX w - - 0 1").is_err());
+    }
+
+    #[test]
+    fn dark_pieces_move_like_their_start_square() {
+        let pos = Position::startpos();
+        // Rook corner
+        assert!(pos.is_legal(sq("a0"), sq("a2")));
+        assert!(!pos.is_legal(sq("a0"), sq("a3")));
+        // Horse
+        assert!(pos.is_legal(sq("b0"), sq("c2")));
+        assert!(!pos.is_legal(sq("b0"), sq("b1")));
+        // Cannon jumps the opposing cannon to capture the dark horse
+        assert!(pos.is_legal(sq("b2"), sq("b9")));
+        assert!(!pos.is_legal(sq("b2"), sq("b8")));
+        // Elephant
+        assert!(pos.is_legal(sq("c0"), sq("e2")));
+        // An unrevealed advisor only steps towards the palace center
+        assert!(pos.is_legal(sq("d0"), sq("e1")));
+        assert!(!pos.is_legal(sq("f0"), sq("g1")));
+        // Pawn
+        assert!(pos.is_legal(sq("a3"), sq("a4")));
+        assert!(!pos.is_legal(sq("a3"), sq("b3")));
+        assert_eq!(pos.legal_moves().len(), 44);
+
+        // A dark piece off the starting squares has no role and cannot move
+        let pos = Position::from_fen("4k4/9/9/9/9/4X4/9/9/9/4K4 w R1 - 0 1").unwrap();
+        assert!(pos.legal_moves().iter().all(|m| m.from != sq("e4")));
+    }
+
+    #[test]
+    fn reveals_update_the_pools() {
+        let mut pos = Position::startpos();
+        assert!(pos.apply(&JieqiMove::new(sq("a3"), sq("a4"))).is_err(), "dark move without a reveal");
+        assert!(pos.apply(&mv("a3a4K", &pos)).is_err(), "king is not in the pool");
+
+        pos.apply(&mv("b2b9Rn", &pos)).unwrap();
+        assert_eq!(pos.board[sq("b9")], Some(Piece::Known('R')));
+        assert_eq!(pos.board[sq("b2")], None);
+        assert_eq!(pos.hidden.get(&'R'), Some(&1));
+        assert_eq!(pos.hidden.get(&'n'), Some(&1));
+        assert_eq!(pos.captured_hidden.get(&'n'), Some(&1));
+        assert_eq!((pos.side_to_move, pos.halfmove), (Side::Black, 0));
+        assert!(pos.to_fen().ends_with(" b R1r2N2n1B2b2A2a2C2c2P5p5 n1 0 1"));
+
+        // Black reveals its last two horses; a third is not in the pool
+        pos.apply(&mv("h9g7n", &pos)).unwrap();
+        assert_eq!(pos.fullmove, 2);
+        pos.apply(&mv("a0a1N", &pos)).unwrap();
+        assert_eq!(pos.hidden.get(&'n'), Some(&0));
+        assert!(pos.apply(&mv("c9e7n", &pos)).is_err());
+        let before = pos.clone();
+        assert!(pos.apply(&mv("a9a8rP", &pos)).is_err(), "a8 holds no dark piece");
+        assert_eq!(pos, before, "a rejected move changes nothing");
+    }
+
+    #[test]
+    fn kings_may_not_face_each_other() {
+        let pos = Position::from_fen("4k4/9/9/9/4R4/9/9/9/9/4K4 w - - 0 1").unwrap();
+        assert!(!pos.in_check(Side::Red));
+        assert!(!pos.is_legal(sq("e5"), sq("a5")), "the rook is pinned by the flying general");
+        assert!(pos.is_legal(sq("e5"), sq("e8")));
+        assert!(pos.is_legal(sq("e0"), sq("d0")));
+
+        let facing = Position::from_fen("4k4/9/9/9/9/9/9/9/9/4K4 w - - 0 1").unwrap();
+        assert!(facing.in_check(Side::Red));
+        assert!(facing.in_check(Side::Black));
+    }
+
+    #[test]
+    fn moves_must_not_leave_the_king_in_check() {
+        let pos = Position::from_fen("3k5/9/9/9/9/9/9/9/9/r3K4 w - - 0 1").unwrap();
+        assert!(pos.in_check(Side::Red));
+        assert!(pos.is_legal(sq("e0"), sq("e1")));
+        assert!(!pos.is_legal(sq("e0"), sq("f0")), "still on the rook's rank");
+        assert!(!pos.is_legal(sq("e0"), sq("d0")), "faces the black king");
+
+        // Unrevealed pieces do not give check
+        let pos = Position::from_fen("3k5/9/9/x8/9/9/9/9/9/4K4 w - - 0 1").unwrap();
+        assert!(!pos.in_check(Side::Red));
+    }
+
+    #[test]
+    fn game_result_on_mate_and_stalemate() {
+        assert_eq!(Position::startpos().game_result(), None);
+
+        let mate = Position::from_fen("3k5/9/9/9/9/9/9/9/1r7/r3K4 w - - 0 1").unwrap();
+        assert!(mate.in_check(Side::Red));
+        assert_eq!(mate.game_result(), Some("0-1"));
+
+        // No legal move without being in check loses as well
+        let stalemate = Position::from_fen("3k5/9/9/9/5r3/9/9/9/r8/4K4 w - - 0 1").unwrap();
+        assert!(!stalemate.in_check(Side::Red));
+        assert!(stalemate.legal_moves().is_empty());
+        assert_eq!(stalemate.game_result(), Some("0-1"));
+
+        let black_stalemated = Position::from_fen("3k5/R8/9/9/9/9/9/9/9/4K4 b - - 0 1").unwrap();
+        assert_eq!(black_stalemated.game_result(), Some("1-0"));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::engine::{EngineClient, SearchLimits, MATE_SCORE_BASE};
+use crate::notation::GameNotation;
+use crate::position::{Position, Side};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Scores this close to MATE_SCORE_BASE are mate scores, as in ReviewAnalysisDialog.vue
+const MATE_THRESHOLD: i32 = MATE_SCORE_BASE - 100;
+// Mate scores are clamped to this when computing centipawn loss
+const MAX_CP_LOSS_SCORE: i32 = 1000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlayerReview {
+    pub moves: u32,
+    pub average_cp_loss: f64,
+    // 0-100, averaged over moves from win-probability drops
+    pub accuracy: f64,
+    pub brilliant: u32,
+    pub good: u32,
+    pub interesting: u32,
+    pub dubious: u32,
+    pub mistakes: u32,
+    pub blunders: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReviewReport {
+    pub red: PlayerReview,
+    pub black: PlayerReview,
+}
+
+/// Same thresholds as pickAnnotation in ReviewAnalysisDialog.vue. `delta` is the change in the
+/// mover's evaluation, `before` and `after` are both from the mover's point of view.
+pub fn pick_annotation(delta: i32, before: Option<i32>, after: Option<i32>) -> Option<&'static str> {
+    let is_mate_before = before.map(|s| s.abs() > MATE_THRESHOLD).unwrap_or(false);
+    let is_mate_after = after.map(|s| s.abs() > MATE_THRESHOLD).unwrap_or(false);
+
+    if is_mate_before || is_mate_after {
+        let before = before.unwrap_or(0);
+        let after = after.unwrap_or(0);
+        // Threw away a winning mate
+        if is_mate_before && before > MATE_THRESHOLD && (!is_mate_after || after < MATE_THRESHOLD) {
+            return Some("??");
+        }
+        // Was getting mated but the opponent gave a mate back
+        if is_mate_before && before < -MATE_THRESHOLD && is_mate_after && after > MATE_THRESHOLD {
+            return Some("!!");
+        }
+        // Still mating, only flag clearly longer routes
+        if is_mate_before && before > MATE_THRESHOLD && is_mate_after && after > MATE_THRESHOLD {
+            let ply_before = MATE_SCORE_BASE - before;
+            let ply_after = MATE_SCORE_BASE - after;
+            return if ply_after > ply_before + 2 { Some("?") } else { None };
+        }
+        return if delta <= -500 {
+            Some("??")
+        } else if delta >= 500 {
+            Some("!!")
+        } else {
+            None
+        };
+    }
+
+    match delta {
+        d if d <= -300 => Some("??"),
+        d if d <= -150 => Some("?"),
+        d if d <= -60 => Some("?!"),
+        d if d >= 300 => Some("!!"),
+        d if d >= 150 => Some("!"),
+        d if d >= 60 => Some("!?"),
+        _ => None,
+    }
+}
+
+fn win_percent(cp: i32) -> f64 {
+    let cp = cp.clamp(-MAX_CP_LOSS_SCORE, MAX_CP_LOSS_SCORE) as f64;
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * cp).exp()) - 1.0)
+}
+
+fn move_accuracy(before: i32, after: i32) -> f64 {
+    let drop = (win_percent(before) - win_percent(after)).max(0.0);
+    (103.1668 * (-0.04354 * drop).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+/// Analyzes every position of the game once, then fills `engineScore`, `engineTime` and
+/// `annotation` of each move. `on_progress` receives (done, total) after each position.
+pub fn review_notation<F: FnMut(usize, usize)>(
+    engine: &mut EngineClient,
+    notation: &mut GameNotation,
+    limits: &SearchLimits,
+    timeout: Option<Duration>,
+    mut on_progress: F,
+) -> Result<ReviewReport, String> {
+    let positions = notation.replay()?;
+    let total = positions.len();
+
+    // Score of every position from the side to move's point of view
+    let mut scores: Vec<Option<(i32, u64)>> = Vec::with_capacity(total);
+    for (index, position) in positions.iter().enumerate() {
+        let score = if position.game_result().is_some() {
+            Some((-MATE_SCORE_BASE, 0))
+        } else {
+            let result = engine.search(&position.to_fen(), limits, timeout)?;
+            result
+                .info
+                .and_then(|info| info.score)
+                .map(|score| (score.to_engine_score(), result.elapsed_ms))
+        };
+        scores.push(score);
+        on_progress(index + 1, total);
+    }
+    Ok(annotate_moves(notation, &positions, &scores))
+}
+
+// Fills the moves from the (score, time) of every position in `positions`, each from the side to
+// move's point of view, and sums up both players
+fn annotate_moves(notation: &mut GameNotation, positions: &[Position], scores: &[Option<(i32, u64)>]) -> ReviewReport {
+    let mut report = ReviewReport::default();
+    let mut cp_loss_sum = [0i64; 2];
+    let mut accuracy_sum = [0f64; 2];
+
+    for (index, entry) in notation.moves.iter_mut().enumerate() {
+        if !entry.is_move() {
+            continue;
+        }
+        let (Some((before, time)), after) = (scores[index], scores[index + 1]) else {
+            continue;
+        };
+        entry.engine_score = Some(before as f64);
+        entry.engine_time = Some(time as f64);
+        // Without a score for the next position the move cannot be judged
+        let Some((after, _)) = after else { continue };
+        // The next position is scored for the opponent
+        let after = -after;
+        let annotation = pick_annotation(after - before, Some(before), Some(after));
+        entry.annotation = annotation.map(|a| a.to_string());
+
+        let mover = positions[index].side_to_move;
+        let (player, slot) = if mover == Side::Red { (&mut report.red, 0) } else { (&mut report.black, 1) };
+        let clamped_before = before.clamp(-MAX_CP_LOSS_SCORE, MAX_CP_LOSS_SCORE);
+        let clamped_after = after.clamp(-MAX_CP_LOSS_SCORE, MAX_CP_LOSS_SCORE);
+        player.moves += 1;
+        cp_loss_sum[slot] += (clamped_before - clamped_after).max(0) as i64;
+        accuracy_sum[slot] += move_accuracy(clamped_before, clamped_after);
+        match annotation {
+            Some("!!") => player.brilliant += 1,
+            Some("!") => player.good += 1,
+            Some("!?") => player.interesting += 1,
+            Some("?!") => player.dubious += 1,
+            Some("?") => player.mistakes += 1,
+            Some("??") => player.blunders += 1,
+            _ => {}
+        }
+    }
+
+    for (player, slot) in [(&mut report.red, 0), (&mut report.black, 1)] {
+        if player.moves > 0 {
+            player.average_cp_loss = cp_loss_sum[slot] as f64 / player.moves as f64;
+            player.accuracy = accuracy_sum[slot] / player.moves as f64;
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::{NotationMetadata, NotationMove};
+
+    #[test]
+    fn annotations_follow_the_score_change() {
+        let pick = |delta| pick_annotation(delta, Some(0), Some(delta));
+        let picked: Vec<_> = [-300, -150, -60, -59, 59, 60, 150, 300].into_iter().map(pick).collect();
+        assert_eq!(picked, [Some("??"), Some("?"), Some("?!"), None, None, Some("!?"), Some("!"), Some("!!")]);
+    }
+
+    #[test]
+    fn mate_scores_are_judged_by_the_mate() {
+        let mate_in = |ply| MATE_SCORE_BASE - ply;
+        // Threw a mate away, even for a winning score
+        assert_eq!(pick_annotation(500 - mate_in(5), Some(mate_in(5)), Some(500)), Some("??"));
+        assert_eq!(pick_annotation(2 * mate_in(8), Some(-mate_in(8)), Some(mate_in(8))), Some("!!"));
+        // A slightly longer mate is fine, a much longer one is not
+        assert_eq!(pick_annotation(-1, Some(mate_in(5)), Some(mate_in(6))), None);
+        assert_eq!(pick_annotation(-5, Some(mate_in(5)), Some(mate_in(10))), Some("?"));
+        // Walking into a mate
+        assert_eq!(pick_annotation(-mate_in(3) - 100, Some(100), Some(-mate_in(3))), Some("??"));
+    }
+
+    fn game(moves: &[&str]) -> (GameNotation, Vec<Position>) {
+        let initial_fen = Some("3k5/9/9/9/9/9/9/9/4R4/4K4 w - - 0 1".to_string());
+        let metadata = NotationMetadata { initial_fen, ..Default::default() };
+        let moves = moves
+            .iter()
+            .map(|uci| NotationMove { kind: "move".to_string(), data: uci.to_string(), ..Default::default() })
+            .collect();
+        let notation = GameNotation { metadata, moves };
+        let positions = notation.replay().unwrap();
+        (notation, positions)
+    }
+
+    #[test]
+    fn accuracy_is_kept_per_side() {
+        let (mut notation, positions) = game(&["e1e2", "d9d8", "e2e3", "d8d9"]);
+        // Red keeps the balance, black then drops 350, and the third position has no score
+        let scores = [Some((50, 10)), Some((-50, 20)), Some((400, 30)), None, Some((0, 50))];
+        let report = annotate_moves(&mut notation, &positions, &scores);
+
+        assert_eq!((report.red.moves, report.red.average_cp_loss, report.red.blunders), (1, 0.0, 0));
+        assert!(report.red.accuracy > 99.0);
+        assert_eq!((report.black.moves, report.black.average_cp_loss, report.black.blunders), (1, 350.0, 1));
+        assert!(report.black.accuracy < 50.0);
+
+        let annotations: Vec<_> = notation.moves.iter().map(|m| m.annotation.as_deref()).collect();
+        assert_eq!(annotations, [None, Some("??"), None, None]);
+        // Red's second move is not judged against a missing score
+        assert_eq!(notation.moves[2].engine_score, Some(400.0));
+        assert_eq!(notation.moves[3].engine_score, None);
+    }
+}
@@ -0,0 +1,103 @@
+use base64::Engine;
+use image::buffer::ConvertBuffer;
+use image::codecs::jpeg::JpegEncoder;
+use image::{ImageFormat, RgbImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureFormat {
+    #[default]
+    Png,
+    Jpeg,
+    // Unencoded RGBA rows, top to bottom; the cheapest to produce
+    Rgba,
+}
+
+impl CaptureFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            CaptureFormat::Png => "image/png",
+            CaptureFormat::Jpeg => "image/jpeg",
+            CaptureFormat::Rgba => "application/octet-stream",
+        }
+    }
+}
+
+/// Part of a monitor's capture, in its pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CaptureRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaptureOptions {
+    pub monitor: usize,
+    // The whole monitor when not given
+    pub region: Option<CaptureRegion>,
+    pub format: CaptureFormat,
+    pub jpeg_quality: u8,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        CaptureOptions { monitor: 0, region: None, format: CaptureFormat::Png, jpeg_quality: 85 }
+    }
+}
+
+/// A captured and encoded frame. `data` is a data URL unless the bytes were sent separately.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapturedFrame {
+    pub monitor: usize,
+    // The region actually captured, after clipping to the monitor
+    pub region: CaptureRegion,
+    pub format: CaptureFormat,
+    pub data: Option<String>,
+}
+
+/// Cuts the region out of a capture, clipped to the image. Returns the image with the region
+/// it covers.
+pub fn crop(image: RgbaImage, region: Option<CaptureRegion>) -> Result<(RgbaImage, CaptureRegion), String> {
+    let full = CaptureRegion { x: 0, y: 0, width: image.width(), height: image.height() };
+    let Some(region) = region else { return Ok((image, full)) };
+    let width = region.width.min(full.width.saturating_sub(region.x));
+    let height = region.height.min(full.height.saturating_sub(region.y));
+    if width == 0 || height == 0 {
+        return Err(format!(
+            "Capture region {}x{} at {},{} is outside the {}x{} monitor",
+            region.width, region.height, region.x, region.y, full.width, full.height
+        ));
+    }
+    let clipped = CaptureRegion { x: region.x, y: region.y, width, height };
+    if clipped == full {
+        return Ok((image, full));
+    }
+    Ok((image::imageops::crop_imm(&image, region.x, region.y, width, height).to_image(), clipped))
+}
+
+pub fn encode(image: RgbaImage, format: CaptureFormat, jpeg_quality: u8) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+    match format {
+        CaptureFormat::Png => {
+            image.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png).map_err(|e| e.to_string())?;
+        }
+        CaptureFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let rgb: RgbImage = image.convert();
+            JpegEncoder::new_with_quality(&mut buffer, jpeg_quality.clamp(1, 100))
+                .encode_image(&rgb)
+                .map_err(|e| e.to_string())?;
+        }
+        CaptureFormat::Rgba => buffer = image.into_raw(),
+    }
+    Ok(buffer)
+}
+
+pub fn data_url(bytes: &[u8], format: CaptureFormat) -> String {
+    format!("data:{};base64,{}", format.mime_type(), base64::engine::general_purpose::STANDARD.encode(bytes))
+}
@@ -0,0 +1,254 @@
+use crate::auto_play::AutoPlayEvent;
+use crate::board_watcher::{FrameSource, WatchEvent};
+use crate::calibration::ScreenPoint;
+use crate::input_automation::{InputAction, InputDevice};
+use crate::notation::{GameNotation, NotationMetadata, NotationMove};
+use image::RgbaImage;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+pub enum LogRecord {
+    Event { event: AutoPlayEvent },
+    Mouse { action: InputAction },
+    Screenshot { file: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    // Milliseconds since the session started
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub record: LogRecord,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionExport {
+    pub notation: String,
+    pub log: String,
+    // Directory of the key screenshots, when they were kept
+    pub screenshots: Option<String>,
+}
+
+/// Everything that happened in an auto-play session, for replay and debugging.
+pub struct SessionLog {
+    started: Instant,
+    started_at: chrono::DateTime<chrono::Local>,
+    entries: Vec<LogEntry>,
+    // Where key screenshots are written as they are taken; None keeps none
+    screenshot_dir: Option<PathBuf>,
+    last_frame: Option<RgbaImage>,
+    screenshots: usize,
+}
+
+impl SessionLog {
+    pub fn new(screenshot_dir: Option<PathBuf>) -> Self {
+        SessionLog {
+            started: Instant::now(),
+            started_at: chrono::Local::now(),
+            entries: Vec::new(),
+            screenshot_dir,
+            last_frame: None,
+            screenshots: 0,
+        }
+    }
+
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    fn push(&mut self, record: LogRecord) {
+        self.entries.push(LogEntry { at_ms: self.started.elapsed().as_millis() as u64, record });
+    }
+
+    pub fn record_action(&mut self, action: InputAction) {
+        self.push(LogRecord::Mouse { action });
+    }
+
+    pub fn record_frame(&mut self, frame: &RgbaImage) {
+        if self.screenshot_dir.is_some() {
+            self.last_frame = Some(frame.clone());
+        }
+    }
+
+    // Moves, desyncs and the end of the game keep the frame they were seen on. A screenshot
+    // that cannot be written is left out of the log.
+    pub fn record_event(&mut self, event: &AutoPlayEvent) {
+        let key = match event {
+            AutoPlayEvent::Board { event: WatchEvent::Move { uci, .. } } => Some(format!("opponent-{}", uci)),
+            AutoPlayEvent::Board { event: WatchEvent::Unexplained { .. } } => Some("unexplained".to_string()),
+            AutoPlayEvent::Played { uci, .. } => Some(format!("played-{}", uci)),
+            AutoPlayEvent::Desync(desync) => Some(format!("desync-{}", desync.uci)),
+            AutoPlayEvent::Finished { .. } => Some("finished".to_string()),
+            _ => None,
+        };
+        self.push(LogRecord::Event { event: event.clone() });
+        let (Some(key), Some(dir), Some(frame)) = (key, &self.screenshot_dir, &self.last_frame) else { return };
+        let file = format!("{:03}-{}.png", self.screenshots + 1, key);
+        if fs::create_dir_all(dir).is_ok() && frame.save(dir.join(&file)).is_ok() {
+            self.screenshots += 1;
+            self.push(LogRecord::Screenshot { file });
+        }
+    }
+
+    /// The game as notation: both sides' moves with the engine's score, time and PV on ours.
+    pub fn to_notation(&self) -> GameNotation {
+        let mut initial_fen = None;
+        let mut current_fen = None;
+        let mut result = None;
+        let mut decision = None;
+        let mut moves = Vec::new();
+        for entry in &self.entries {
+            let LogRecord::Event { event } = &entry.record else { continue };
+            match event {
+                AutoPlayEvent::State { fen: Some(fen), .. }
+                | AutoPlayEvent::Board { event: WatchEvent::Synced { fen } } => {
+                    initial_fen.get_or_insert_with(|| fen.clone());
+                }
+                AutoPlayEvent::EngineMove { score, depth, pv, time_ms, .. } => {
+                    let comment = format!("depth {} pv {}", depth, pv.join(" "));
+                    decision = Some((score.map(|s| s.to_engine_score() as f64), *time_ms as f64, comment));
+                }
+                AutoPlayEvent::Played { uci, fen, .. } => {
+                    let (engine_score, engine_time, comment) = decision.take().unwrap_or_default();
+                    moves.push(NotationMove {
+                        kind: "move".to_string(),
+                        data: uci.clone(),
+                        fen: fen.clone(),
+                        comment: Some(comment).filter(|c| !c.is_empty()),
+                        engine_score,
+                        engine_time: Some(engine_time).filter(|t| *t > 0.0),
+                        ..Default::default()
+                    });
+                    current_fen = Some(fen.clone());
+                }
+                AutoPlayEvent::Board { event: WatchEvent::Move { uci, fen, .. } } => {
+                    moves.push(NotationMove {
+                        kind: "move".to_string(),
+                        data: uci.clone(),
+                        fen: fen.clone(),
+                        ..Default::default()
+                    });
+                    current_fen = Some(fen.clone());
+                }
+                AutoPlayEvent::Finished { result: finished, .. } => result = Some(finished.clone()),
+                _ => {}
+            }
+        }
+        GameNotation {
+            metadata: NotationMetadata {
+                event: Some("Auto-play".to_string()),
+                date: Some(self.started_at.format("%Y-%m-%d").to_string()),
+                result,
+                current_fen: current_fen.or(initial_fen.clone()),
+                initial_fen,
+                ..Default::default()
+            },
+            moves,
+        }
+    }
+
+    /// Writes `<name>.json` (notation) and `<name>.log.json`. The screenshots are already in
+    /// their directory.
+    pub fn export<P: AsRef<Path>>(&self, dir: P, name: &str) -> Result<SessionExport, String> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let notation = dir.join(format!("{}.json", name));
+        self.to_notation().save(&notation)?;
+        let log = dir.join(format!("{}.log.json", name));
+        let content = serde_json::to_string_pretty(&self.entries).map_err(|e| e.to_string())?;
+        fs::write(&log, content).map_err(|e| format!("Failed to write {}: {}", log.display(), e))?;
+
+        let screenshots =
+            self.screenshot_dir.as_ref().filter(|_| self.screenshots > 0).map(|d| d.display().to_string());
+        Ok(SessionExport { notation: notation.display().to_string(), log: log.display().to_string(), screenshots })
+    }
+}
+
+/// Passes mouse input through to `device`, logging each action.
+pub struct LoggedDevice<'a> {
+    pub device: &'a mut dyn InputDevice,
+    pub log: &'a Mutex<SessionLog>,
+}
+
+impl InputDevice for LoggedDevice<'_> {
+    fn move_to(&mut self, point: ScreenPoint) -> Result<(), String> {
+        self.device.move_to(point)?;
+        self.log.lock().unwrap().record_action(InputAction::MoveTo(point));
+        Ok(())
+    }
+
+    fn press(&mut self) -> Result<(), String> {
+        self.device.press()?;
+        self.log.lock().unwrap().record_action(InputAction::Press);
+        Ok(())
+    }
+
+    fn release(&mut self) -> Result<(), String> {
+        self.device.release()?;
+        self.log.lock().unwrap().record_action(InputAction::Release);
+        Ok(())
+    }
+}
+
+/// Passes frames through from `source`, keeping the latest for key screenshots.
+pub struct LoggedFrames<'a> {
+    pub source: &'a mut dyn FrameSource,
+    pub log: &'a Mutex<SessionLog>,
+}
+
+impl FrameSource for LoggedFrames<'_> {
+    fn next_frame(&mut self) -> Result<Option<RgbaImage>, String> {
+        let frame = self.source.next_frame()?;
+        if let Some(frame) = &frame {
+            self.log.lock().unwrap().record_frame(frame);
+        }
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auto_play::AutoPlayState;
+
+    fn played(uci: &str) -> AutoPlayEvent {
+        AutoPlayEvent::Played { uci: uci.to_string(), fen: String::new(), attempts: 1, actions: Vec::new() }
+    }
+
+    #[test]
+    fn key_screenshots_are_written_as_they_happen() {
+        let dir = std::env::temp_dir().join(format!("jieqibox-session-{}", std::process::id()));
+        let shots = dir.join("game-screenshots");
+        let mut log = SessionLog::new(Some(shots.clone()));
+
+        // No frame seen yet
+        log.record_event(&played("h2e2C"));
+        log.record_frame(&RgbaImage::new(4, 4));
+        log.record_event(&AutoPlayEvent::State { state: AutoPlayState::Watching, fen: None });
+        log.record_event(&played("h0g2N"));
+        let files: Vec<&str> = log
+            .entries()
+            .iter()
+            .filter_map(|e| if let LogRecord::Screenshot { file } = &e.record { Some(file.as_str()) } else { None })
+            .collect();
+        assert_eq!(files, ["001-played-h0g2N.png"]);
+        assert!(shots.join("001-played-h0g2N.png").is_file());
+
+        let export = log.export(&dir, "game").unwrap();
+        assert_eq!(export.screenshots, Some(shots.display().to_string()));
+        assert!(Path::new(&export.log).is_file());
+        let _ = fs::remove_dir_all(&dir);
+
+        // Without a directory no frame is kept
+        let mut log = SessionLog::new(None);
+        log.record_frame(&RgbaImage::new(4, 4));
+        log.record_event(&played("h2e2C"));
+        assert!(log.last_frame.is_none());
+        assert_eq!(log.entries().len(), 1);
+    }
+}
@@ -0,0 +1,634 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use crate::atomic_file::write_atomic;
+use std::fs;
+use std::path::Path;
+
+// Defaults and field names follow defaultConfig in useConfigManager.ts
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InterfaceSettings {
+    pub show_coordinates: bool,
+    pub parse_uci_info: bool,
+    pub show_animations: bool,
+    pub show_position_chart: bool,
+    pub show_evaluation_bar: bool,
+    pub dark_mode: bool,
+    pub autosave: bool,
+    pub use_new_fen_format: bool,
+    pub engine_log_line_limit: u32,
+    pub validation_timeout: u32,
+    pub show_chinese_notation: bool,
+    pub show_luck_index: bool,
+    pub show_arrows: bool,
+    pub show_book_moves: bool,
+    pub opening_book_enable_in_game: bool,
+    pub opening_book_prefer_high_priority: bool,
+    pub enable_sound_effects: bool,
+    pub sound_volume: u32,
+}
+
+impl Default for InterfaceSettings {
+    fn default() -> Self {
+        InterfaceSettings {
+            show_coordinates: false,
+            parse_uci_info: true,
+            show_animations: true,
+            show_position_chart: false,
+            show_evaluation_bar: true,
+            dark_mode: false,
+            autosave: true,
+            use_new_fen_format: true,
+            engine_log_line_limit: 256,
+            validation_timeout: 5000,
+            show_chinese_notation: true,
+            show_luck_index: false,
+            show_arrows: true,
+            show_book_moves: true,
+            opening_book_enable_in_game: true,
+            opening_book_prefer_high_priority: true,
+            enable_sound_effects: true,
+            sound_volume: 70,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WindowSettings {
+    pub width: u32,
+    pub height: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_maximized: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale_factor: Option<f64>,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        WindowSettings { width: 800, height: 600, x: None, y: None, is_maximized: None, scale_factor: None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EvaluationChartSettings {
+    pub show_move_labels: bool,
+    pub use_linear_y_axis: bool,
+    pub show_only_lines: bool,
+    pub black_perspective: bool,
+    pub enable_y_axis_clamp: bool,
+    pub y_axis_clamp_value: u32,
+    pub color_scheme: String,
+    pub show_separate_lines: bool,
+    pub view_mode: String,
+}
+
+impl Default for EvaluationChartSettings {
+    fn default() -> Self {
+        EvaluationChartSettings {
+            show_move_labels: true,
+            use_linear_y_axis: false,
+            show_only_lines: false,
+            black_perspective: false,
+            enable_y_axis_clamp: false,
+            y_axis_clamp_value: 500,
+            color_scheme: "default".to_string(),
+            show_separate_lines: false,
+            view_mode: "evaluation".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AnalysisSettings {
+    pub movetime: u64,
+    pub max_think_time: u64,
+    pub max_depth: u32,
+    pub max_nodes: u64,
+    pub analysis_mode: String,
+    pub advanced_script: String,
+}
+
+impl Default for AnalysisSettings {
+    fn default() -> Self {
+        AnalysisSettings {
+            movetime: 1000,
+            max_think_time: 5000,
+            max_depth: 20,
+            max_nodes: 1000000,
+            analysis_mode: "movetime".to_string(),
+            advanced_script: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct GameSettings {
+    pub flip_mode: String,
+    pub enable_ponder: bool,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        GameSettings { flip_mode: "random".to_string(), enable_ponder: false }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MatchSettings {
+    pub is_match_mode: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HumanVsAiSettings {
+    pub is_human_vs_ai_mode: bool,
+    pub ai_side: String,
+    pub show_engine_analysis: bool,
+}
+
+impl Default for HumanVsAiSettings {
+    fn default() -> Self {
+        HumanVsAiSettings { is_human_vs_ai_mode: false, ai_side: "black".to_string(), show_engine_analysis: false }
+    }
+}
+
+// Engine options per engine ID, values as the option dialogs store them
+pub type EngineOptionValues = BTreeMap<String, BTreeMap<String, Value>>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    pub interface_settings: InterfaceSettings,
+    pub window_settings: WindowSettings,
+    pub evaluation_chart_settings: EvaluationChartSettings,
+    pub analysis_settings: AnalysisSettings,
+    pub game_settings: GameSettings,
+    pub match_settings: MatchSettings,
+    pub human_vs_ai_settings: HumanVsAiSettings,
+    pub uci_options: EngineOptionValues,
+    pub jai_options: EngineOptionValues,
+    pub locale: String,
+    // Sections the frontend adds on its own ([Engines], [Settings], ...)
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        AppSettings {
+            interface_settings: InterfaceSettings::default(),
+            window_settings: WindowSettings::default(),
+            evaluation_chart_settings: EvaluationChartSettings::default(),
+            analysis_settings: AnalysisSettings::default(),
+            game_settings: GameSettings::default(),
+            match_settings: MatchSettings::default(),
+            human_vs_ai_settings: HumanVsAiSettings::default(),
+            uci_options: BTreeMap::new(),
+            jai_options: BTreeMap::new(),
+            locale: "zh_cn".to_string(),
+            extra: Map::new(),
+        }
+    }
+}
+
+const ANALYSIS_MODES: [&str; 5] = ["movetime", "maxThinkTime", "depth", "nodes", "advanced"];
+const LOCALES: [&str; 5] = ["zh_cn", "zh_tw", "en", "vi", "ja"];
+
+impl AppSettings {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Failed to read settings: {}", e))?;
+        let mut settings: AppSettings =
+            serde_json::from_str(&content).map_err(|e| format!("Invalid settings file: {}", e))?;
+        settings.validate();
+        Ok(settings)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        write_atomic(path.as_ref(), content.as_bytes()).map_err(|e| format!("Failed to write settings: {}", e))
+    }
+
+    /// Replaces out-of-range values with their defaults and returns a note for each fix.
+    pub fn validate(&mut self) -> Vec<String> {
+        let defaults = AppSettings::default();
+        let mut fixes = Vec::new();
+        let mut fix = |ok: bool, what: &str| {
+            if !ok {
+                fixes.push(format!("{} was invalid and has been reset", what));
+            }
+            !ok
+        };
+
+        // Same checks as validateWindowSettings in useConfigManager.ts
+        let window = &self.window_settings;
+        let on_screen = |v: Option<i32>| v.map(|v| (-10000..=10000).contains(&v)).unwrap_or(true);
+        if fix(window.width > 0 && window.height > 0 && on_screen(window.x) && on_screen(window.y), "windowSettings") {
+            self.window_settings = defaults.window_settings.clone();
+        }
+
+        let interface = &mut self.interface_settings;
+        if fix(interface.sound_volume <= 100, "interfaceSettings.soundVolume") {
+            interface.sound_volume = defaults.interface_settings.sound_volume;
+        }
+        if fix(interface.engine_log_line_limit > 0, "interfaceSettings.engineLogLineLimit") {
+            interface.engine_log_line_limit = defaults.interface_settings.engine_log_line_limit;
+        }
+        if fix(interface.validation_timeout > 0, "interfaceSettings.validationTimeout") {
+            interface.validation_timeout = defaults.interface_settings.validation_timeout;
+        }
+
+        let analysis = &mut self.analysis_settings;
+        if fix(analysis.movetime > 0, "analysisSettings.movetime") {
+            analysis.movetime = defaults.analysis_settings.movetime;
+        }
+        if fix(analysis.max_think_time > 0, "analysisSettings.maxThinkTime") {
+            analysis.max_think_time = defaults.analysis_settings.max_think_time;
+        }
+        if fix(analysis.max_depth > 0, "analysisSettings.maxDepth") {
+            analysis.max_depth = defaults.analysis_settings.max_depth;
+        }
+        if fix(analysis.max_nodes > 0, "analysisSettings.maxNodes") {
+            analysis.max_nodes = defaults.analysis_settings.max_nodes;
+        }
+        if fix(ANALYSIS_MODES.contains(&analysis.analysis_mode.as_str()), "analysisSettings.analysisMode") {
+            analysis.analysis_mode = defaults.analysis_settings.analysis_mode.clone();
+        }
+
+        if fix(self.evaluation_chart_settings.y_axis_clamp_value > 0, "evaluationChartSettings.yAxisClampValue") {
+            self.evaluation_chart_settings.y_axis_clamp_value = defaults.evaluation_chart_settings.y_axis_clamp_value;
+        }
+        if fix(["random", "free"].contains(&self.game_settings.flip_mode.as_str()), "gameSettings.flipMode") {
+            self.game_settings.flip_mode = defaults.game_settings.flip_mode.clone();
+        }
+        if fix(["red", "black"].contains(&self.human_vs_ai_settings.ai_side.as_str()), "humanVsAiSettings.aiSide") {
+            self.human_vs_ai_settings.ai_side = defaults.human_vs_ai_settings.ai_side.clone();
+        }
+        if fix(LOCALES.contains(&self.locale.as_str()), "locale") {
+            self.locale = defaults.locale;
+        }
+        fixes
+    }
+
+    pub fn get_section(&self, section: &str) -> Result<Value, String> {
+        let value = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        value.get(section).cloned().ok_or_else(|| format!("Unknown settings section '{}'", section))
+    }
+
+    /// Merges `patch` into one section: object sections are updated key by key, other values
+    /// are replaced. The result is type-checked and validated before it is applied.
+    pub fn set_section(&mut self, section: &str, patch: Value) -> Result<Vec<String>, String> {
+        let mut value = serde_json::to_value(&*self).map_err(|e| e.to_string())?;
+        let root = value.as_object_mut().ok_or("Settings are not an object")?;
+        match (root.get_mut(section), patch) {
+            (Some(Value::Object(current)), Value::Object(patch)) => current.extend(patch),
+            (_, patch) => {
+                root.insert(section.to_string(), patch);
+            }
+        }
+        let mut updated: AppSettings =
+            serde_json::from_value(value).map_err(|e| format!("Invalid value for '{}': {}", section, e))?;
+        let fixes = updated.validate();
+        *self = updated;
+        Ok(fixes)
+    }
+
+    /// Reads the INI text written by the frontend (npm `ini` format). Values are converted to
+    /// the type of the matching default, since INI stores everything as text.
+    pub fn from_ini(text: &str) -> Result<Self, String> {
+        let mut value = parse_ini(text);
+        let mut shape = serde_json::to_value(AppSettings::default()).map_err(|e| e.to_string())?;
+        // Optional window fields are left out of the defaults but still numeric
+        for key in ["x", "y", "scaleFactor"] {
+            shape["windowSettings"][key] = Value::from(0);
+        }
+        coerce_to_shape(&mut value, Some(&shape));
+        let mut settings: AppSettings =
+            serde_json::from_value(value).map_err(|e| format!("Invalid config.ini: {}", e))?;
+        settings.validate();
+        Ok(settings)
+    }
+
+    // Same layout as Ini.stringify, for the frontend's load_config
+    pub fn to_ini(&self) -> Result<String, String> {
+        let value = serde_json::to_value(self).map_err(|e| e.to_string())?;
+        let Value::Object(map) = value else { return Ok(String::new()) };
+        Ok(encode_ini(&map, None))
+    }
+}
+
+/// Loads the settings file, migrating a legacy `config.ini` the first time. A corrupt
+/// settings file is kept as `.corrupt` and replaced by defaults.
+pub fn load_or_migrate(path: &Path, legacy_ini: &Path) -> Result<AppSettings, String> {
+    if path.exists() {
+        return match AppSettings::load(path) {
+            Ok(settings) => Ok(settings),
+            Err(_) => {
+                let _ = fs::rename(path, path.with_extension("json.corrupt"));
+                let settings = AppSettings::default();
+                settings.save(path)?;
+                Ok(settings)
+            }
+        };
+    }
+
+    let settings = if legacy_ini.exists() {
+        let text = fs::read_to_string(legacy_ini).map_err(|e| format!("Failed to read config.ini: {}", e))?;
+        AppSettings::from_ini(&text)?
+    } else {
+        AppSettings::default()
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+    settings.save(path)?;
+    if legacy_ini.exists() {
+        // Keep the old file around instead of deleting user data
+        let _ = fs::rename(legacy_ini, legacy_ini.with_extension("ini.migrated"));
+    }
+    Ok(settings)
+}
+
+fn coerce_to_shape(value: &mut Value, shape: Option<&Value>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map.iter_mut() {
+                coerce_to_shape(child, shape.and_then(|s| s.get(key)));
+            }
+        }
+        Value::String(text) => {
+            // Unknown keys (engine options, extra sections) stay text like in the frontend
+            if let Some(Value::Number(_)) = shape {
+                if let Ok(number) = serde_json::from_str::<serde_json::Number>(text.trim()) {
+                    *value = Value::Number(number);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_quoted(value: &str) -> bool {
+    value.len() > 1
+        && ((value.starts_with('"') && value.ends_with('"')) || (value.starts_with('\'') && value.ends_with('\'')))
+}
+
+// Inverse of `safe` in the ini package: strips quotes and escapes, drops inline comments
+fn unsafe_ini(value: &str) -> String {
+    let value = value.trim();
+    if is_quoted(value) {
+        let inner = if value.starts_with('\'') { &value[1..value.len() - 1] } else { value };
+        return match serde_json::from_str::<Value>(inner) {
+            Ok(Value::String(s)) => s,
+            Ok(other) => other.to_string(),
+            Err(_) => inner.to_string(),
+        };
+    }
+    let mut result = String::new();
+    let mut escaped = false;
+    for c in value.chars() {
+        if escaped {
+            if !"\\;#".contains(c) {
+                result.push('\\');
+            }
+            result.push(c);
+            escaped = false;
+        } else if c == ';' || c == '#' {
+            break;
+        } else if c == '\\' {
+            escaped = true;
+        } else {
+            result.push(c);
+        }
+    }
+    if escaped {
+        result.push('\\');
+    }
+    result.trim().to_string()
+}
+
+fn safe_ini(value: &Value) -> String {
+    match value {
+        Value::String(s) => {
+            if s.contains(['=', '\r', '\n']) || s.starts_with('[') || is_quoted(s) || s != s.trim() {
+                Value::String(s.clone()).to_string()
+            } else {
+                s.replace(';', "\\;").replace('#', "\\#")
+            }
+        }
+        other => other.to_string(),
+    }
+}
+
+fn split_sections(section: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = section.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.peek() == Some(&'.') {
+            parts.last_mut().unwrap().push('.');
+            chars.next();
+        } else if c == '.' {
+            parts.push(String::new());
+        } else {
+            parts.last_mut().unwrap().push(c);
+        }
+    }
+    parts
+}
+
+fn parse_ini(text: &str) -> Value {
+    let mut root = Map::new();
+    let mut section: Option<Vec<String>> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with(';') || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            section = Some(split_sections(&unsafe_ini(name)));
+            continue;
+        }
+        let (raw_key, raw_value) = match line.split_once('=') {
+            Some((key, value)) => (key, Some(value)),
+            None => (line, None),
+        };
+        let key = unsafe_ini(raw_key);
+        let value = match raw_value.map(unsafe_ini) {
+            None => Value::Bool(true),
+            Some(v) if v == "true" => Value::Bool(true),
+            Some(v) if v == "false" => Value::Bool(false),
+            Some(v) if v == "null" => Value::Null,
+            Some(v) => Value::String(v),
+        };
+
+        let mut target = &mut root;
+        for part in section.iter().flatten() {
+            let entry = target.entry(part.clone()).or_insert_with(|| Value::Object(Map::new()));
+            if !entry.is_object() {
+                *entry = Value::Object(Map::new());
+            }
+            target = entry.as_object_mut().unwrap();
+        }
+        match key.strip_suffix("[]") {
+            Some(array_key) => {
+                let entry = target.entry(array_key.to_string()).or_insert_with(|| Value::Array(Vec::new()));
+                if let Value::Array(items) = entry {
+                    items.push(value);
+                }
+            }
+            None => {
+                target.insert(key, value);
+            }
+        }
+    }
+    Value::Object(root)
+}
+
+fn encode_ini(map: &Map<String, Value>, section: Option<&str>) -> String {
+    let mut out = String::new();
+    let mut children = Vec::new();
+    for (key, value) in map {
+        match value {
+            Value::Array(items) => {
+                for item in items {
+                    out.push_str(&format!("{}[]={}\n", safe_ini(&Value::String(key.clone())), safe_ini(item)));
+                }
+            }
+            Value::Object(child) => children.push((key, child)),
+            other => out.push_str(&format!("{}={}\n", safe_ini(&Value::String(key.clone())), safe_ini(other))),
+        }
+    }
+    if let Some(section) = section.filter(|_| !out.is_empty()) {
+        out = format!("[{}]\n{}", safe_ini(&Value::String(section.to_string())), out);
+    }
+    for (key, child) in children {
+        let escaped = key.replace('.', "\\.");
+        let name = match section {
+            Some(section) => format!("{}.{}", section, escaped),
+            None => escaped,
+        };
+        let encoded = encode_ini(child, Some(&name));
+        if !out.is_empty() && !encoded.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&encoded);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("jieqibox-settings-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn defaults_round_trip_through_ini() {
+        let settings = AppSettings::default();
+        assert_eq!(AppSettings::from_ini(&settings.to_ini().unwrap()).unwrap(), settings);
+    }
+
+    #[test]
+    fn custom_values_round_trip_through_ini() {
+        let mut settings = AppSettings::default();
+        settings.interface_settings.dark_mode = true;
+        settings.interface_settings.sound_volume = 35;
+        settings.window_settings = WindowSettings {
+            width: 1280,
+            height: 720,
+            x: Some(-40),
+            y: Some(12),
+            is_maximized: Some(false),
+            scale_factor: Some(1.5),
+        };
+        settings.analysis_settings.analysis_mode = "advanced".to_string();
+        // Characters the ini format has to quote or escape
+        settings.analysis_settings.advanced_script = "if depth=10; movetime 500 # fast\nelse [slow]".to_string();
+        settings.evaluation_chart_settings.color_scheme = "  padded ".to_string();
+        settings.locale = "vi".to_string();
+        let mut options = BTreeMap::new();
+        options.insert("Hash".to_string(), Value::from("256"));
+        options.insert("Style".to_string(), Value::from("Risky Play"));
+        options.insert("Ponder".to_string(), Value::Bool(true));
+        settings.uci_options.insert("engine-1.2".to_string(), options);
+        settings.extra.insert(
+            "Engines".to_string(),
+            serde_json::json!({ "list": r#"[{"id":"a","name":"Pikafish = strong","path":"C:\\e\\p.exe"}]"# }),
+        );
+
+        let ini = settings.to_ini().unwrap();
+        assert!(ini.contains("[uciOptions.engine-1\\.2]"), "dots in engine IDs are escaped:\n{}", ini);
+        assert_eq!(AppSettings::from_ini(&ini).unwrap(), settings);
+    }
+
+    #[test]
+    fn numbers_in_unknown_keys_stay_text() {
+        let mut settings = AppSettings::default();
+        let mut options = BTreeMap::new();
+        options.insert("Threads".to_string(), Value::from(4));
+        settings.jai_options.insert("e".to_string(), options);
+        let parsed = AppSettings::from_ini(&settings.to_ini().unwrap()).unwrap();
+        assert_eq!(parsed.jai_options["e"]["Threads"], Value::from("4"));
+    }
+
+    #[test]
+    fn reads_frontend_ini_text() {
+        let text = "locale=en\n\
+                    ; a comment\n\
+                    [interfaceSettings]\n\
+                    darkMode=true\n\
+                    soundVolume=500\n\
+                    engineLogLineLimit = 128 ; trailing comment\n\
+                    \n\
+                    [windowSettings]\n\
+                    width=1024\n\
+                    height=768\n\
+                    x=-8\n\
+                    \n\
+                    [Settings]\n\
+                    tags[]=a\n\
+                    tags[]=b\\;c\n";
+        let settings = AppSettings::from_ini(text).unwrap();
+        assert_eq!(settings.locale, "en");
+        assert!(settings.interface_settings.dark_mode);
+        // Out of range, reset to the default
+        assert_eq!(settings.interface_settings.sound_volume, 70);
+        assert_eq!(settings.interface_settings.engine_log_line_limit, 128);
+        assert_eq!((settings.window_settings.width, settings.window_settings.x), (1024, Some(-8)));
+        assert_eq!(settings.extra["Settings"]["tags"], serde_json::json!(["a", "b;c"]));
+        assert_eq!(AppSettings::from_ini(&settings.to_ini().unwrap()).unwrap(), settings);
+    }
+
+    #[test]
+    fn migrates_a_legacy_config_ini_once() {
+        let dir = temp_dir("migrate");
+        let (path, legacy) = (dir.join("config/settings.json"), dir.join("config.ini"));
+        fs::write(&legacy, "locale=ja\n[gameSettings]\nflipMode=free\n").unwrap();
+
+        let settings = load_or_migrate(&path, &legacy).unwrap();
+        assert_eq!((settings.locale.as_str(), settings.game_settings.flip_mode.as_str()), ("ja", "free"));
+        assert!(!legacy.exists() && dir.join("config.ini.migrated").exists());
+        assert_eq!(load_or_migrate(&path, &legacy).unwrap(), settings);
+
+        fs::write(&path, "{ not json").unwrap();
+        assert_eq!(load_or_migrate(&path, &legacy).unwrap(), AppSettings::default());
+        assert!(dir.join("config/settings.json.corrupt").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
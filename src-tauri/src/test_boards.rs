@@ -0,0 +1,106 @@
+// Synthetic screenshots for tests: a 50 pixel grid at (40, 40) with drawn pieces
+use crate::board_recognition::{BoardOrientation, BoardRect, PieceSet, PATCH_SCALE};
+use crate::position::{Piece, Position};
+use image::imageops;
+use image::{Rgba, RgbaImage};
+
+const SPACING: f32 = 50.0;
+pub(crate) const GRID: BoardRect = BoardRect { x: 40.0, y: 40.0, width: 400.0, height: 450.0 };
+pub(crate) const BOARD_COLOR: Rgba<u8> = Rgba([222, 190, 140, 255]);
+const LINE_COLOR: Rgba<u8> = Rgba([70, 45, 20, 255]);
+
+fn fill(image: &mut RgbaImage, x: std::ops::Range<i64>, y: std::ops::Range<i64>, color: Rgba<u8>) {
+    for py in y.clone() {
+        for px in x.clone() {
+            if px >= 0 && py >= 0 && (px as u32) < image.width() && (py as u32) < image.height() {
+                image.put_pixel(px as u32, py as u32, color);
+            }
+        }
+    }
+}
+
+fn disc(image: &mut RgbaImage, (cx, cy): (f32, f32), radius: f32, color: Rgba<u8>) {
+    let r = radius.ceil() as i64;
+    for dy in -r..=r {
+        for dx in -r..=r {
+            if ((dx * dx + dy * dy) as f32) <= radius * radius {
+                fill(image, cx as i64 + dx..cx as i64 + dx + 1, cy as i64 + dy..cy as i64 + dy + 1, color);
+            }
+        }
+    }
+}
+
+// Red or black rim with a mark whose place stands for the piece type; dark pieces are plain
+pub(crate) fn draw_piece(image: &mut RgbaImage, center: (f32, f32), piece: Piece) {
+    let (rim, face, mark) = match piece {
+        Piece::Dark(_) => (Rgba([30, 60, 40, 255]), Rgba([80, 120, 90, 255]), None),
+        Piece::Known(c) => {
+            let rim = if c.is_ascii_uppercase() { Rgba([200, 30, 30, 255]) } else { Rgba([25, 25, 25, 255]) };
+            (rim, Rgba([245, 230, 200, 255]), "KABNRCP".find(c.to_ascii_uppercase()))
+        }
+    };
+    disc(image, center, SPACING * 0.42, rim);
+    disc(image, center, SPACING * 0.34, face);
+    if let Some(kind) = mark {
+        let angle = kind as f32 * std::f32::consts::TAU / 7.0;
+        let at = (center.0 + 9.0 * angle.cos(), center.1 + 9.0 * angle.sin());
+        disc(image, at, 5.0, rim);
+    }
+}
+
+pub(crate) fn render(fen: &str, orientation: BoardOrientation) -> RgbaImage {
+    let position = Position::from_fen(fen).unwrap();
+    let mut image = RgbaImage::from_pixel(480, 530, BOARD_COLOR);
+    for row in 0..10 {
+        let (x, y) = GRID.intersection(row, 0);
+        fill(&mut image, x as i64 - 1..(x + GRID.width) as i64 + 1, y as i64 - 1..y as i64 + 1, LINE_COLOR);
+    }
+    for col in 0..9 {
+        let (x, y) = GRID.intersection(0, col);
+        // Inner files stop at the river
+        let spans = if col == 0 || col == 8 { vec![(0, 9)] } else { vec![(0, 4), (5, 9)] };
+        for (top, bottom) in spans {
+            let (y0, y1) = (y + SPACING * top as f32, y + SPACING * bottom as f32);
+            fill(&mut image, x as i64 - 1..x as i64 + 1, y0 as i64 - 1..y1 as i64 + 1, LINE_COLOR);
+        }
+    }
+    for (sq, piece) in position.board.iter().enumerate() {
+        if let Some(piece) = *piece {
+            let (row, col) = orientation.image_cell(sq);
+            draw_piece(&mut image, GRID.intersection(row, col), piece);
+        }
+    }
+    image
+}
+
+// Templates cropped from a board with one piece of each kind, the way squares are cropped
+pub(crate) fn piece_set() -> PieceSet {
+    let image = render("rnbakabnr/xc5cp/9/9/9/9/9/9/PC5CX/RNBAKABNR w - - 0 1", BoardOrientation::RedBottom);
+    let crop = |sq: usize| {
+        let (cx, cy) = GRID.intersection(sq / 9, sq % 9);
+        let size = (SPACING * PATCH_SCALE).round() as u32;
+        imageops::crop_imm(&image, cx as u32 - size / 2, cy as u32 - size / 2, size, size).to_image()
+    };
+    let mut set = PieceSet::default();
+    let squares = [
+        ("b_chariot", 0),
+        ("b_horse", 1),
+        ("b_elephant", 2),
+        ("b_advisor", 3),
+        ("b_general", 4),
+        ("b_cannon", 10),
+        ("b_soldier", 17),
+        ("dark", 9),
+        ("r_soldier", 72),
+        ("r_cannon", 73),
+        ("r_chariot", 81),
+        ("r_horse", 82),
+        ("r_elephant", 83),
+        ("r_advisor", 84),
+        ("r_general", 85),
+    ];
+    for (label, sq) in squares {
+        set.add(label, &crop(sq)).unwrap();
+    }
+    set
+}
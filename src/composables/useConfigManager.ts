@@ -9,8 +9,20 @@ export interface ManagedEngine {
   name: string
   path: string
   args: string
+  // Local when absent
+  transport?: EngineTransport
+  // Encoding of the engine's output; GBK on Windows and UTF-8 elsewhere when absent
+  encoding?: EngineEncoding
 }
 
+export type EngineEncoding = 'auto' | 'utf8' | 'gbk'
+
+// How a remote engine is reached: over TCP, or through a command such as `ssh host`
+export type EngineTransport =
+  | { kind: 'local' }
+  | { kind: 'tcp'; address: string }
+  | { kind: 'command'; program: string; args: string[] }
+
 // Configuration data structure
 interface ConfigData {
   interfaceSettings: {
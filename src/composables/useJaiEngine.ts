@@ -22,6 +22,12 @@ export interface JaiOption {
   vars?: string[]
 }
 
+// What spawn_engine reports once the handshake is done
+interface SpawnedEngine {
+  name: string
+  author: string
+}
+
 export function useJaiEngine(_generateFen: () => string, gameState: any) {
   const { t } = useI18n()
   const { validationTimeout } = useInterfaceSettings()
@@ -373,51 +379,26 @@ export function useJaiEngine(_generateFen: () => string, gameState: any) {
       console.warn('Failed to kill previous engine:', e)
     )
 
-    // Prepare for validation
-    jaiOkReceived.value = false
-
-    // A promise that resolves when 'jaiok' is received
-    const jaiOkPromise = new Promise<void>((resolve, reject) => {
-      const timeoutId = setTimeout(() => {
-        reject(
-          new Error(
-            `Validation timeout: No 'jaiok' received within ${validationTimeout.value}ms.`
-          )
-        )
-      }, validationTimeout.value)
-
-      // Listen specifically for the jaiok signal
-      listen<string>('engine-output', event => {
-        if (event.payload.trim() === 'jaiok') {
-          console.log(
-            `[DEBUG] Received jaiok for ${engine.name}. Validation successful.`
-          )
-          jaiOkReceived.value = true
-          clearTimeout(timeoutId)
-          resolve()
-        }
-      }).then(unsub => {
-        // When the promise resolves or rejects, we'll stop listening
-        const cleanup = () => unsub()
-        jaiOkPromise.finally(cleanup)
-      })
-    })
-
     try {
       // Spawn engine process
       console.log(
         `[DEBUG] Spawning JAI engine: ${engine.name}, Path: ${engine.path}, Args: ${engine.args}`
       )
-      await invoke('spawn_engine', {
+      // Sends 'jai' itself and fails if 'jaiok' does not arrive in time
+      jaiOkReceived.value = false
+      const info = await invoke<SpawnedEngine | null>('spawn_engine', {
         path: engine.path,
         args: engine.args.split(' ').filter(Boolean),
+        transport: engine.transport,
+        encoding: engine.encoding,
+        protocol: 'jai',
+        handshakeTimeoutMs: validationTimeout.value,
       })
 
-      // Send 'jai' to start validation
-      send('jai')
-
-      // Wait for validation to complete or time out
-      await jaiOkPromise
+      jaiOkReceived.value = info !== null
+      console.log(
+        `[DEBUG] Received jaiok for ${engine.name}. Validation successful.`
+      )
 
       // Stop loading sound when engine is ready
       console.log(
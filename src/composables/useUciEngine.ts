@@ -16,6 +16,12 @@ export interface EngineLine {
   kind: 'sent' | 'recv'
 }
 
+// What spawn_engine reports once the handshake is done
+interface SpawnedEngine {
+  name: string
+  author: string
+}
+
 export function useUciEngine(generateFen: () => string, gameState: any) {
   const { t } = useI18n()
   const { useNewFenFormat, validationTimeout } = useInterfaceSettings()
@@ -378,51 +384,26 @@ export function useUciEngine(generateFen: () => string, gameState: any) {
       console.warn('Failed to kill previous engine:', e)
     )
 
-    // Prepare for validation
-    uciOkReceived.value = false
-
-    // A promise that resolves when 'uciok' is received
-    const uciOkPromise = new Promise<void>((resolve, reject) => {
-      const timeoutId = setTimeout(() => {
-        reject(
-          new Error(
-            `Validation timeout: No 'uciok' received within ${validationTimeout.value}ms.`
-          )
-        )
-      }, validationTimeout.value)
-
-      // Listen specifically for the uciok signal
-      listen<string>('engine-output', event => {
-        if (event.payload.trim() === 'uciok') {
-          console.log(
-            `[DEBUG] Received uciok for ${engine.name}. Validation successful.`
-          )
-          uciOkReceived.value = true
-          clearTimeout(timeoutId)
-          resolve()
-        }
-      }).then(unsub => {
-        // When the promise resolves or rejects, we'll stop listening
-        const cleanup = () => unsub()
-        uciOkPromise.finally(cleanup)
-      })
-    })
-
     try {
       // Spawn engine process
       console.log(
         `[DEBUG] Spawning engine: ${engine.name}, Path: ${engine.path}, Args: ${engine.args}`
       )
-      await invoke('spawn_engine', {
+      // Sends 'uci' itself and fails if 'uciok' does not arrive in time
+      uciOkReceived.value = false
+      const info = await invoke<SpawnedEngine | null>('spawn_engine', {
         path: engine.path,
         args: engine.args.split(' ').filter(Boolean),
+        transport: engine.transport,
+        encoding: engine.encoding,
+        protocol: 'uci',
+        handshakeTimeoutMs: validationTimeout.value,
       })
 
-      // Send 'uci' to start validation
-      send('uci')
-
-      // Wait for validation to complete or time out
-      await uciOkPromise
+      uciOkReceived.value = info !== null
+      console.log(
+        `[DEBUG] Received uciok for ${engine.name}. Validation successful.`
+      )
 
       // Stop loading sound when engine is ready
       console.log(